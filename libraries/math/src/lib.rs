@@ -10,6 +10,7 @@ pub mod error;
 pub mod instruction;
 pub mod precise_number;
 pub mod processor;
+pub mod tick_math;
 pub mod uint;
 
 solana_program::declare_id!("Math111111111111111111111111111111111111111");
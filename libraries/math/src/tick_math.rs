@@ -0,0 +1,195 @@
+//! Tick and square root price math of concentrated liquidity pools
+//!
+//! Prices are `1.0001^tick`, square root prices are Q64.64 fixed point numbers.
+
+use crate::uint::U256;
+
+/// Lowest tick whose square root price fits Q64.64
+pub const MIN_TICK: i32 = -443636;
+/// Highest tick whose square root price fits Q64.64
+pub const MAX_TICK: i32 = -MIN_TICK;
+/// Square root price at `MIN_TICK`
+pub const MIN_SQRT_PRICE_X64: u128 = 4295048016;
+/// Square root price at `MAX_TICK`
+pub const MAX_SQRT_PRICE_X64: u128 = 79226673521066979257578248091;
+
+const Q64: u128 = 1 << 64;
+
+/// `2^64 / sqrt(1.0001)^(2^i)`, the high 64 bits of the Uniswap v3 Q128.128 constants
+const TICK_RATIOS: [u128; 19] = [
+    0xfffcb933bd6fad37,
+    0xfff97272373d4132,
+    0xfff2e50f5f656932,
+    0xffe5caca7e10e4e6,
+    0xffcb9843d60f6159,
+    0xff973b41fa98c081,
+    0xff2ea16466c96a38,
+    0xfe5dee046a99a2a8,
+    0xfcbe86c7900a88ae,
+    0xf987a7253ac41317,
+    0xf3392b0822b70005,
+    0xe7159475a2c29b74,
+    0xd097f3bdfd2022b8,
+    0xa9f746462d870fdf,
+    0x70d869a156d2a1b8,
+    0x31be135f97d08fd9,
+    0x9aa508b5b7a84e1,
+    0x5d6af8dedb8119,
+    0x2216e584f5fa,
+];
+
+/// Square root price of `tick` as Q64.64, `None` outside `MIN_TICK..=MAX_TICK`
+pub fn get_sqrt_price_at_tick(tick: i32) -> Option<u128> {
+    if !(MIN_TICK..=MAX_TICK).contains(&tick) {
+        return None;
+    }
+    let abs_tick = tick.unsigned_abs();
+    // product of sqrt(1.0001)^-(2^i) over the bits of |tick|, a Q64.64 at most 1
+    let mut ratio = Q64;
+    for (bit, tick_ratio) in TICK_RATIOS.iter().enumerate() {
+        if abs_tick & (1 << bit) != 0 {
+            ratio = ratio.checked_mul(*tick_ratio)? >> 64;
+        }
+    }
+    if tick > 0 {
+        ratio = u128::MAX / ratio;
+    }
+    Some(ratio)
+}
+
+/// Greatest tick whose square root price is at most `sqrt_price_x64`
+pub fn get_tick_at_sqrt_price(sqrt_price_x64: u128) -> Option<i32> {
+    if !(MIN_SQRT_PRICE_X64..=MAX_SQRT_PRICE_X64).contains(&sqrt_price_x64) {
+        return None;
+    }
+    let (mut low, mut high) = (MIN_TICK, MAX_TICK);
+    while low < high {
+        // upper middle, so `low = middle` always moves
+        let middle = low + (high - low + 1) / 2;
+        if get_sqrt_price_at_tick(middle)? <= sqrt_price_x64 {
+            low = middle;
+        } else {
+            high = middle - 1;
+        }
+    }
+    Some(low)
+}
+
+/// Token 0 between two square root prices for `liquidity`,
+/// `liquidity * (sqrt_b - sqrt_a) / (sqrt_a * sqrt_b)`
+pub fn get_amount_0_delta(sqrt_price_a_x64: u128, sqrt_price_b_x64: u128, liquidity: u128, round_up: bool) -> Option<u128> {
+    let (lower, upper) = ordered(sqrt_price_a_x64, sqrt_price_b_x64);
+    if lower == 0 {
+        return None;
+    }
+    let numerator = U256::from(liquidity)
+        .checked_mul(U256::from(upper - lower))?
+        .checked_mul(U256::from(Q64))?;
+    let denominator = U256::from(upper).checked_mul(U256::from(lower))?;
+    to_u128(div(numerator, denominator, round_up)?)
+}
+
+/// Token 1 between two square root prices for `liquidity`, `liquidity * (sqrt_b - sqrt_a)`
+pub fn get_amount_1_delta(sqrt_price_a_x64: u128, sqrt_price_b_x64: u128, liquidity: u128, round_up: bool) -> Option<u128> {
+    let (lower, upper) = ordered(sqrt_price_a_x64, sqrt_price_b_x64);
+    let numerator = U256::from(liquidity).checked_mul(U256::from(upper - lower))?;
+    to_u128(div(numerator, U256::from(Q64), round_up)?)
+}
+
+/// Square root price after adding `amount_in` to a pool of `liquidity`, moving down for
+/// token 0 in and up for token 1 in. Rounded so the pool never gives out too much
+pub fn get_next_sqrt_price_from_input(sqrt_price_x64: u128, liquidity: u128, amount_in: u128, zero_for_one: bool) -> Option<u128> {
+    if sqrt_price_x64 == 0 || liquidity == 0 {
+        return None;
+    }
+    if amount_in == 0 {
+        return Some(sqrt_price_x64);
+    }
+    if zero_for_one {
+        // liquidity * sqrt_price / (liquidity + amount_in * sqrt_price), rounded up
+        let liquidity_x64 = U256::from(liquidity).checked_mul(U256::from(Q64))?;
+        let numerator = liquidity_x64.checked_mul(U256::from(sqrt_price_x64))?;
+        let denominator = liquidity_x64.checked_add(U256::from(amount_in).checked_mul(U256::from(sqrt_price_x64))?)?;
+        to_u128(div(numerator, denominator, true)?)
+    } else {
+        // sqrt_price + amount_in / liquidity, rounded down
+        let delta = U256::from(amount_in).checked_mul(U256::from(Q64))? / U256::from(liquidity);
+        sqrt_price_x64.checked_add(to_u128(delta)?)
+    }
+}
+
+fn ordered(a: u128, b: u128) -> (u128, u128) {
+    if a > b {
+        (b, a)
+    } else {
+        (a, b)
+    }
+}
+
+fn div(numerator: U256, denominator: U256, round_up: bool) -> Option<U256> {
+    if denominator.is_zero() {
+        return None;
+    }
+    let quotient = numerator / denominator;
+    if round_up && !(numerator % denominator).is_zero() {
+        quotient.checked_add(U256::one())
+    } else {
+        Some(quotient)
+    }
+}
+
+fn to_u128(value: U256) -> Option<u128> {
+    if value > U256::from(u128::MAX) {
+        None
+    } else {
+        Some(value.as_u128())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {super::*, proptest::prelude::*};
+
+    #[test]
+    fn test_sqrt_price_bounds() {
+        assert_eq!(get_sqrt_price_at_tick(0), Some(Q64));
+        assert_eq!(get_sqrt_price_at_tick(MIN_TICK), Some(MIN_SQRT_PRICE_X64));
+        assert_eq!(get_sqrt_price_at_tick(MAX_TICK), Some(MAX_SQRT_PRICE_X64));
+        assert_eq!(get_sqrt_price_at_tick(MIN_TICK - 1), None);
+        assert_eq!(get_sqrt_price_at_tick(MAX_TICK + 1), None);
+        assert_eq!(get_tick_at_sqrt_price(MIN_SQRT_PRICE_X64), Some(MIN_TICK));
+        assert_eq!(get_tick_at_sqrt_price(MAX_SQRT_PRICE_X64), Some(MAX_TICK));
+    }
+
+    #[test]
+    fn test_sqrt_price_known_ticks() {
+        // sqrt(1.0001^tick) * 2^64 is 18445821805675392311, 18447666387855959850
+        // and 18539204128674405812, positive ticks come out a unit or two above
+        assert_eq!(get_sqrt_price_at_tick(-1), Some(18445821805675392311));
+        assert_eq!(get_sqrt_price_at_tick(1), Some(18447666387855959851));
+        assert_eq!(get_sqrt_price_at_tick(100), Some(18539204128674405814));
+    }
+
+    #[test]
+    fn test_swap_within_range() {
+        let liquidity = 1_000_000_000_000u128;
+        let sqrt_price = Q64;
+        let next = get_next_sqrt_price_from_input(sqrt_price, liquidity, 1_000_000, true).unwrap();
+        assert!(next < sqrt_price);
+        let amount_0 = get_amount_0_delta(next, sqrt_price, liquidity, true).unwrap();
+        assert!(amount_0 <= 1_000_000);
+        let amount_1 = get_amount_1_delta(next, sqrt_price, liquidity, false).unwrap();
+        assert!(amount_1 < 1_000_000 && amount_1 > 999_000);
+    }
+
+    proptest! {
+        #[test]
+        fn test_tick_round_trip(tick in MIN_TICK..=MAX_TICK) {
+            let sqrt_price = get_sqrt_price_at_tick(tick).unwrap();
+            prop_assert_eq!(get_tick_at_sqrt_price(sqrt_price), Some(tick));
+            if tick < MAX_TICK {
+                prop_assert!(get_sqrt_price_at_tick(tick + 1).unwrap() > sqrt_price);
+            }
+        }
+    }
+}
@@ -0,0 +1,1101 @@
+//! Venues swaps and liquidity are routed to
+
+use crate::error::AmmError;
+use crate::serum::MarketState;
+use crate::stable_swap;
+use crate::state::{AmmInfo, ClmmAmmConfig, ClmmPoolState, ClmmTickArrayState, PoolKind, StableAmmInfo};
+use enum_dispatch::enum_dispatch;
+use spl_math::tick_math;
+use solana_program::{
+    account_info::{next_account_info, AccountInfo},
+    entrypoint::ProgramResult,
+    instruction::Instruction,
+    program_error::ProgramError,
+    program_pack::Pack,
+    pubkey::Pubkey,
+};
+use std::convert::{TryFrom, TryInto};
+
+/// Venue selected by the adapter tag of the instruction data
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum AdapterKind {
+    /// Raydium constant product pools, AMM v4
+    #[default]
+    RaydiumV4,
+    /// Raydium stable pools
+    RaydiumStable,
+    /// Raydium concentrated liquidity pools, swaps only. The swap passes
+    /// `tick_array_count` tick arrays after the user accounts
+    RaydiumClmm { tick_array_count: u8 },
+}
+impl AdapterKind {
+    /// Most tick arrays a CLMM swap may pass
+    pub const MAX_TICK_ARRAYS: u8 = 10;
+
+    /// Tag, followed by the tick array count for `RaydiumClmm`
+    pub fn unpack(input: &[u8]) -> Result<(Self, &[u8]), ProgramError> {
+        let (&tag, rest) = input.split_first().ok_or(AmmError::InvalidInstruction)?;
+        match tag {
+            0u8 => Ok((AdapterKind::RaydiumV4, rest)),
+            1u8 => Ok((AdapterKind::RaydiumStable, rest)),
+            2u8 => {
+                let (&tick_array_count, rest) = rest.split_first().ok_or(AmmError::InvalidInstruction)?;
+                if tick_array_count == 0 || tick_array_count > Self::MAX_TICK_ARRAYS {
+                    return Err(AmmError::InvalidInstruction.into());
+                }
+                Ok((AdapterKind::RaydiumClmm { tick_array_count }, rest))
+            }
+            _ => Err(AmmError::InvalidInstruction.into()),
+        }
+    }
+
+    /// Appends the tag, and the tick array count for `RaydiumClmm`
+    pub fn pack(&self, buf: &mut Vec<u8>) {
+        match self {
+            AdapterKind::RaydiumV4 => buf.push(0),
+            AdapterKind::RaydiumStable => buf.push(1),
+            AdapterKind::RaydiumClmm { tick_array_count } => {
+                buf.push(2);
+                buf.push(*tick_array_count);
+            }
+        }
+    }
+}
+
+/// User accounts of a swap
+pub struct SwapAccounts<'a, 'b> {
+    pub source: &'a AccountInfo<'b>,
+    pub destination: &'a AccountInfo<'b>,
+    pub owner: &'a AccountInfo<'b>,
+}
+
+/// User accounts of a deposit
+pub struct DepositAccounts<'a, 'b> {
+    pub coin: &'a AccountInfo<'b>,
+    pub pc: &'a AccountInfo<'b>,
+    pub lp: &'a AccountInfo<'b>,
+    pub owner: &'a AccountInfo<'b>,
+}
+
+/// User accounts of a withdraw
+pub struct WithdrawAccounts<'a, 'b> {
+    pub lp: &'a AccountInfo<'b>,
+    pub coin: &'a AccountInfo<'b>,
+    pub pc: &'a AccountInfo<'b>,
+    pub owner: &'a AccountInfo<'b>,
+}
+
+/// Pool of one venue, parsed from the accounts of a swap, deposit or withdraw.
+/// The instructions it builds are invoked with the accounts they were parsed from.
+#[enum_dispatch]
+pub trait DexAdapter {
+    /// Pool account the registry entry is derived from
+    fn pool(&self) -> &Pubkey;
+
+    /// Checks the pool account is owned by the program of the venue
+    fn check_program(&self) -> ProgramResult;
+
+    /// Coin and pc reserves the pool prices with
+    fn reserves(&self) -> Result<(u64, u64), ProgramError>;
+
+    /// Swaps `amount_in` of the user source token
+    fn swap_instruction(
+        &self,
+        user: &SwapAccounts,
+        amount_in: u64,
+        minimum_amount_out: u64,
+    ) -> Result<Instruction, ProgramError>;
+
+    /// Deposits up to the max amounts for lp tokens
+    fn deposit_instruction(
+        &self,
+        user: &DepositAccounts,
+        max_coin_amount: u64,
+        max_pc_amount: u64,
+        base_side: u64,
+    ) -> Result<Instruction, ProgramError>;
+
+    /// Burns `amount` lp tokens for coin and pc
+    fn withdraw_instruction(
+        &self,
+        user: &WithdrawAccounts,
+        amount: u64,
+    ) -> Result<Instruction, ProgramError>;
+}
+
+#[enum_dispatch(DexAdapter)]
+pub enum Adapter<'a, 'b> {
+    RaydiumV4(RaydiumV4<'a, 'b>),
+    RaydiumStable(RaydiumStable<'a, 'b>),
+    RaydiumClmm(RaydiumClmm<'a, 'b>),
+}
+
+impl<'a, 'b> Adapter<'a, 'b> {
+    /// Registry kind of the pools the venue routes to
+    pub fn pool_kind(&self) -> PoolKind {
+        match self {
+            Adapter::RaydiumV4(_) => PoolKind::RaydiumV4,
+            Adapter::RaydiumStable(_) => PoolKind::RaydiumStable,
+            Adapter::RaydiumClmm(_) => PoolKind::RaydiumClmm,
+        }
+    }
+
+    /// Spl Token program the venue moves the user tokens with
+    pub fn spl_token_program_id(&self) -> &'a AccountInfo<'b> {
+        match self {
+            Adapter::RaydiumV4(pool) => pool.spl_token_program_id,
+            Adapter::RaydiumStable(pool) => pool.spl_token_program_id,
+            Adapter::RaydiumClmm(pool) => pool.spl_token_program_id,
+        }
+    }
+
+    /// Parses the pool and user accounts of a swap of `kind`
+    pub fn next_swap<I: Iterator<Item = &'a AccountInfo<'b>>>(
+        kind: AdapterKind,
+        iter: &mut I,
+    ) -> Result<(Self, SwapAccounts<'a, 'b>), ProgramError> {
+        match kind {
+            AdapterKind::RaydiumV4 => {
+                let (pool, user) = RaydiumV4::next_swap(iter)?;
+                Ok((pool.into(), user))
+            }
+            AdapterKind::RaydiumStable => {
+                let (pool, user) = RaydiumStable::next_swap(iter)?;
+                Ok((pool.into(), user))
+            }
+            AdapterKind::RaydiumClmm { tick_array_count } => {
+                let (pool, user) = RaydiumClmm::next_swap(iter, tick_array_count)?;
+                Ok((pool.into(), user))
+            }
+        }
+    }
+
+    /// Parses the pool and user accounts of a deposit of `kind`
+    pub fn next_deposit<I: Iterator<Item = &'a AccountInfo<'b>>>(
+        kind: AdapterKind,
+        iter: &mut I,
+    ) -> Result<(Self, DepositAccounts<'a, 'b>), ProgramError> {
+        match kind {
+            AdapterKind::RaydiumV4 => {
+                let (pool, user) = RaydiumV4::next_deposit(iter)?;
+                Ok((pool.into(), user))
+            }
+            AdapterKind::RaydiumStable => {
+                let (pool, user) = RaydiumStable::next_deposit(iter)?;
+                Ok((pool.into(), user))
+            }
+            // CLMM liquidity is held in positions, not lp tokens
+            AdapterKind::RaydiumClmm { .. } => Err(AmmError::InvalidInstruction.into()),
+        }
+    }
+
+    /// Parses the pool and user accounts of a withdraw of `kind`
+    pub fn next_withdraw<I: Iterator<Item = &'a AccountInfo<'b>>>(
+        kind: AdapterKind,
+        iter: &mut I,
+    ) -> Result<(Self, WithdrawAccounts<'a, 'b>), ProgramError> {
+        match kind {
+            AdapterKind::RaydiumV4 => {
+                let (pool, user) = RaydiumV4::next_withdraw(iter)?;
+                Ok((pool.into(), user))
+            }
+            AdapterKind::RaydiumStable => {
+                let (pool, user) = RaydiumStable::next_withdraw(iter)?;
+                Ok((pool.into(), user))
+            }
+            AdapterKind::RaydiumClmm { .. } => Err(AmmError::InvalidInstruction.into()),
+        }
+    }
+}
+
+/// Serum market accounts a Raydium v4 pool settles its orders against
+pub struct SerumAccounts<'a, 'b> {
+    pub dex_program_id: &'a AccountInfo<'b>,
+    pub bids: &'a AccountInfo<'b>,
+    pub asks: &'a AccountInfo<'b>,
+    pub event_queue: &'a AccountInfo<'b>,
+    pub coin_vault: &'a AccountInfo<'b>,
+    pub pc_vault: &'a AccountInfo<'b>,
+    pub vault_signer: &'a AccountInfo<'b>,
+}
+
+impl SerumAccounts<'_, '_> {
+    /// Nonce the vault signer of `market` derives from
+    pub fn vault_signer_nonce(&self, market: &AccountInfo) -> Result<u64, ProgramError> {
+        Ok(MarketState::load(self.dex_program_id.key, market)?.vault_signer_nonce)
+    }
+
+    /// Checks the vault signer derives from the nonce of `market`
+    pub fn check_vault_signer(&self, market: &AccountInfo) -> ProgramResult {
+        check_serum_vault_signer(self.dex_program_id, market, self.vault_signer).map(|_| ())
+    }
+}
+
+/// Raydium AMM v4 pool. Only the accounts the parsed operation passes are set
+pub struct RaydiumV4<'a, 'b> {
+    pub program_id: &'a AccountInfo<'b>,
+    pub spl_token_program_id: &'a AccountInfo<'b>,
+    pub amm_account: &'a AccountInfo<'b>,
+    pub authority: &'a AccountInfo<'b>,
+    pub amm_open_orders: &'a AccountInfo<'b>,
+    pub amm_target_orders: &'a AccountInfo<'b>,
+    pub pool_lp_mint: Option<&'a AccountInfo<'b>>,
+    pub pool_token_coin: &'a AccountInfo<'b>,
+    pub pool_token_pc: &'a AccountInfo<'b>,
+    /// withdraw queue and temp lp Accounts
+    pub pool_withdraw_queue: Option<(&'a AccountInfo<'b>, &'a AccountInfo<'b>)>,
+    pub serum_market: &'a AccountInfo<'b>,
+    pub serum: Option<SerumAccounts<'a, 'b>>,
+}
+
+impl<'a, 'b> RaydiumV4<'a, 'b> {
+    /// Accounts as for `ProviderInstruction::Swap`, from the Raydium program id to the user owner
+    pub fn next_swap<I: Iterator<Item = &'a AccountInfo<'b>>>(
+        iter: &mut I,
+    ) -> Result<(Self, SwapAccounts<'a, 'b>), ProgramError> {
+        let program_id = next_account_info(iter)?;
+        let spl_token_program_id = next_account_info(iter)?;
+        let amm_account = next_account_info(iter)?;
+        let authority = next_account_info(iter)?;
+        let amm_open_orders = next_account_info(iter)?;
+        let amm_target_orders = next_account_info(iter)?;
+        let pool_token_coin = next_account_info(iter)?;
+        let pool_token_pc = next_account_info(iter)?;
+        let dex_program_id = next_account_info(iter)?;
+        let serum_market = next_account_info(iter)?;
+        let bids = next_account_info(iter)?;
+        let asks = next_account_info(iter)?;
+        let event_queue = next_account_info(iter)?;
+        let coin_vault = next_account_info(iter)?;
+        let pc_vault = next_account_info(iter)?;
+        let vault_signer = next_account_info(iter)?;
+        let user = SwapAccounts {
+            source: next_account_info(iter)?,
+            destination: next_account_info(iter)?,
+            owner: next_account_info(iter)?,
+        };
+        let pool = Self {
+            program_id,
+            spl_token_program_id,
+            amm_account,
+            authority,
+            amm_open_orders,
+            amm_target_orders,
+            pool_lp_mint: None,
+            pool_token_coin,
+            pool_token_pc,
+            pool_withdraw_queue: None,
+            serum_market,
+            serum: Some(SerumAccounts { dex_program_id, bids, asks, event_queue, coin_vault, pc_vault, vault_signer }),
+        };
+        Ok((pool, user))
+    }
+
+    /// Accounts as for `ProviderInstruction::Deposit`, from the Raydium program id to the user owner
+    pub fn next_deposit<I: Iterator<Item = &'a AccountInfo<'b>>>(
+        iter: &mut I,
+    ) -> Result<(Self, DepositAccounts<'a, 'b>), ProgramError> {
+        let program_id = next_account_info(iter)?;
+        let spl_token_program_id = next_account_info(iter)?;
+        let amm_account = next_account_info(iter)?;
+        let authority = next_account_info(iter)?;
+        let amm_open_orders = next_account_info(iter)?;
+        let amm_target_orders = next_account_info(iter)?;
+        let pool_lp_mint = next_account_info(iter)?;
+        let pool_token_coin = next_account_info(iter)?;
+        let pool_token_pc = next_account_info(iter)?;
+        let serum_market = next_account_info(iter)?;
+        let user = DepositAccounts {
+            coin: next_account_info(iter)?,
+            pc: next_account_info(iter)?,
+            lp: next_account_info(iter)?,
+            owner: next_account_info(iter)?,
+        };
+        let pool = Self {
+            program_id,
+            spl_token_program_id,
+            amm_account,
+            authority,
+            amm_open_orders,
+            amm_target_orders,
+            pool_lp_mint: Some(pool_lp_mint),
+            pool_token_coin,
+            pool_token_pc,
+            pool_withdraw_queue: None,
+            serum_market,
+            serum: None,
+        };
+        Ok((pool, user))
+    }
+
+    /// Accounts as for `ProviderInstruction::Withdraw`, from the Raydium program id to the serum asks
+    pub fn next_withdraw<I: Iterator<Item = &'a AccountInfo<'b>>>(
+        iter: &mut I,
+    ) -> Result<(Self, WithdrawAccounts<'a, 'b>), ProgramError> {
+        let program_id = next_account_info(iter)?;
+        let spl_token_program_id = next_account_info(iter)?;
+        let amm_account = next_account_info(iter)?;
+        let authority = next_account_info(iter)?;
+        let amm_open_orders = next_account_info(iter)?;
+        let amm_target_orders = next_account_info(iter)?;
+        let pool_lp_mint = next_account_info(iter)?;
+        let pool_token_coin = next_account_info(iter)?;
+        let pool_token_pc = next_account_info(iter)?;
+        let pool_withdraw_queue = next_account_info(iter)?;
+        let pool_temp_lp = next_account_info(iter)?;
+        let dex_program_id = next_account_info(iter)?;
+        let serum_market = next_account_info(iter)?;
+        let coin_vault = next_account_info(iter)?;
+        let pc_vault = next_account_info(iter)?;
+        let vault_signer = next_account_info(iter)?;
+        let user = WithdrawAccounts {
+            lp: next_account_info(iter)?,
+            coin: next_account_info(iter)?,
+            pc: next_account_info(iter)?,
+            owner: next_account_info(iter)?,
+        };
+        let event_queue = next_account_info(iter)?;
+        let bids = next_account_info(iter)?;
+        let asks = next_account_info(iter)?;
+        let pool = Self {
+            program_id,
+            spl_token_program_id,
+            amm_account,
+            authority,
+            amm_open_orders,
+            amm_target_orders,
+            pool_lp_mint: Some(pool_lp_mint),
+            pool_token_coin,
+            pool_token_pc,
+            pool_withdraw_queue: Some((pool_withdraw_queue, pool_temp_lp)),
+            serum_market,
+            serum: Some(SerumAccounts { dex_program_id, bids, asks, event_queue, coin_vault, pc_vault, vault_signer }),
+        };
+        Ok((pool, user))
+    }
+
+    fn serum(&self) -> Result<&SerumAccounts<'a, 'b>, ProgramError> {
+        self.serum.as_ref().ok_or_else(|| AmmError::InvalidInput.into())
+    }
+
+    fn pool_lp_mint(&self) -> Result<&'a AccountInfo<'b>, ProgramError> {
+        self.pool_lp_mint.ok_or_else(|| AmmError::InvalidInput.into())
+    }
+
+    fn amm_nonce(&self) -> Result<u8, ProgramError> {
+        let nonce = AmmInfo::load_amm_mut(self.amm_account, true)?.nonce;
+        u8::try_from(nonce).map_err(|_| AmmError::InvalidAuthority.into())
+    }
+
+    /// Output of a swap of `amount_in` on the constant product at the current reserves,
+    /// after the pool swap fee
+    pub fn quote(&self, amount_in: u64, coin_to_pc: bool) -> Result<u64, ProgramError> {
+        let (coin_reserve, pc_reserve) = self.reserves()?;
+        let amm = AmmInfo::load_amm_mut(self.amm_account, true)?;
+        let (reserve_in, reserve_out) = if coin_to_pc {
+            (coin_reserve, pc_reserve)
+        } else {
+            (pc_reserve, coin_reserve)
+        };
+        let amount_in = amount_after_fee(amount_in, amm.fees.swap_fee_numerator, amm.fees.swap_fee_denominator)?;
+        let amount_out = constant_product_out(reserve_in, reserve_out, amount_in).ok_or(AmmError::ConversionFailure)?;
+        amount_out.try_into().map_err(|_| AmmError::ConversionFailure.into())
+    }
+}
+
+/// Output for `amount_in` on the constant product of `reserve_in` and `reserve_out`, rounded down
+fn constant_product_out(reserve_in: u64, reserve_out: u64, amount_in: u128) -> Option<u128> {
+    let denominator = u128::from(reserve_in).checked_add(amount_in)?;
+    if denominator == 0 {
+        return None;
+    }
+    u128::from(reserve_out).checked_mul(amount_in)?.checked_div(denominator)
+}
+
+impl DexAdapter for RaydiumV4<'_, '_> {
+    fn pool(&self) -> &Pubkey {
+        self.amm_account.key
+    }
+
+    fn check_program(&self) -> ProgramResult {
+        if self.amm_account.owner != self.program_id.key {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        let nonce = AmmInfo::load_amm_mut(self.amm_account, true)?.nonce;
+        check_raydium_authority(self.program_id.key, nonce, self.authority)?;
+        if let Some(serum) = &self.serum {
+            serum.check_vault_signer(self.serum_market)?;
+        }
+        Ok(())
+    }
+
+    /// Vault balances less the pnl not taken yet
+    fn reserves(&self) -> Result<(u64, u64), ProgramError> {
+        let amm = AmmInfo::load_amm_mut(self.amm_account, true)?;
+        if amm.token_coin != *self.pool_token_coin.key || amm.token_pc != *self.pool_token_pc.key {
+            return Err(AmmError::InvalidInput.into());
+        }
+        let coin_reserve = token_amount(self.pool_token_coin)?
+            .checked_sub(amm.out_put.need_take_pnl_coin)
+            .ok_or(AmmError::InvalidInput)?;
+        let pc_reserve = token_amount(self.pool_token_pc)?
+            .checked_sub(amm.out_put.need_take_pnl_pc)
+            .ok_or(AmmError::InvalidInput)?;
+        Ok((coin_reserve, pc_reserve))
+    }
+
+    fn swap_instruction(
+        &self,
+        user: &SwapAccounts,
+        amount_in: u64,
+        minimum_amount_out: u64,
+    ) -> Result<Instruction, ProgramError> {
+        let serum = self.serum()?;
+        crate::instruction::swap(
+            self.program_id.key,
+            self.amm_account.key,
+            self.amm_nonce()?,
+            self.amm_open_orders.key,
+            self.amm_target_orders.key,
+            self.pool_token_coin.key,
+            self.pool_token_pc.key,
+            serum.dex_program_id.key,
+            self.serum_market.key,
+            serum.bids.key,
+            serum.asks.key,
+            serum.event_queue.key,
+            serum.coin_vault.key,
+            serum.pc_vault.key,
+            serum.vault_signer_nonce(self.serum_market)?,
+            user.source.key,
+            user.destination.key,
+            user.owner.key,
+            amount_in,
+            minimum_amount_out,
+        )
+    }
+
+    fn deposit_instruction(
+        &self,
+        user: &DepositAccounts,
+        max_coin_amount: u64,
+        max_pc_amount: u64,
+        base_side: u64,
+    ) -> Result<Instruction, ProgramError> {
+        crate::instruction::deposit(
+            self.program_id.key,
+            self.amm_account.key,
+            self.amm_nonce()?,
+            self.amm_open_orders.key,
+            self.amm_target_orders.key,
+            self.pool_lp_mint()?.key,
+            self.pool_token_coin.key,
+            self.pool_token_pc.key,
+            self.serum_market.key,
+            user.coin.key,
+            user.pc.key,
+            user.lp.key,
+            user.owner.key,
+            max_coin_amount,
+            max_pc_amount,
+            base_side,
+        )
+    }
+
+    fn withdraw_instruction(
+        &self,
+        user: &WithdrawAccounts,
+        amount: u64,
+    ) -> Result<Instruction, ProgramError> {
+        let serum = self.serum()?;
+        let (pool_withdraw_queue, pool_temp_lp) = self.pool_withdraw_queue.ok_or(AmmError::InvalidInput)?;
+        crate::instruction::withdraw(
+            self.program_id.key,
+            self.amm_account.key,
+            self.amm_nonce()?,
+            self.amm_open_orders.key,
+            self.amm_target_orders.key,
+            self.pool_lp_mint()?.key,
+            self.pool_token_coin.key,
+            self.pool_token_pc.key,
+            pool_withdraw_queue.key,
+            pool_temp_lp.key,
+            serum.dex_program_id.key,
+            self.serum_market.key,
+            serum.coin_vault.key,
+            serum.pc_vault.key,
+            serum.vault_signer_nonce(self.serum_market)?,
+            user.lp.key,
+            user.coin.key,
+            user.pc.key,
+            user.owner.key,
+            serum.event_queue.key,
+            serum.bids.key,
+            serum.asks.key,
+            amount,
+        )
+    }
+}
+
+/// Raydium stable pool. Only the accounts the parsed operation passes are set
+pub struct RaydiumStable<'a, 'b> {
+    pub program_id: &'a AccountInfo<'b>,
+    pub spl_token_program_id: &'a AccountInfo<'b>,
+    pub amm_account: &'a AccountInfo<'b>,
+    pub authority: &'a AccountInfo<'b>,
+    pub amm_open_orders: &'a AccountInfo<'b>,
+    pub pool_lp_mint: Option<&'a AccountInfo<'b>>,
+    pub pool_token_coin: &'a AccountInfo<'b>,
+    pub pool_token_pc: &'a AccountInfo<'b>,
+    pub model_data: &'a AccountInfo<'b>,
+    pub serum_market: &'a AccountInfo<'b>,
+    pub serum: Option<SerumAccounts<'a, 'b>>,
+}
+
+impl<'a, 'b> RaydiumStable<'a, 'b> {
+    /// Accounts of `instruction::stable_swap` led by the Raydium stable program id
+    pub fn next_swap<I: Iterator<Item = &'a AccountInfo<'b>>>(
+        iter: &mut I,
+    ) -> Result<(Self, SwapAccounts<'a, 'b>), ProgramError> {
+        let program_id = next_account_info(iter)?;
+        let spl_token_program_id = next_account_info(iter)?;
+        let amm_account = next_account_info(iter)?;
+        let authority = next_account_info(iter)?;
+        let amm_open_orders = next_account_info(iter)?;
+        let pool_token_coin = next_account_info(iter)?;
+        let pool_token_pc = next_account_info(iter)?;
+        let model_data = next_account_info(iter)?;
+        let dex_program_id = next_account_info(iter)?;
+        let serum_market = next_account_info(iter)?;
+        let bids = next_account_info(iter)?;
+        let asks = next_account_info(iter)?;
+        let event_queue = next_account_info(iter)?;
+        let coin_vault = next_account_info(iter)?;
+        let pc_vault = next_account_info(iter)?;
+        let vault_signer = next_account_info(iter)?;
+        let user = SwapAccounts {
+            source: next_account_info(iter)?,
+            destination: next_account_info(iter)?,
+            owner: next_account_info(iter)?,
+        };
+        let pool = Self {
+            program_id,
+            spl_token_program_id,
+            amm_account,
+            authority,
+            amm_open_orders,
+            pool_lp_mint: None,
+            pool_token_coin,
+            pool_token_pc,
+            model_data,
+            serum_market,
+            serum: Some(SerumAccounts { dex_program_id, bids, asks, event_queue, coin_vault, pc_vault, vault_signer }),
+        };
+        Ok((pool, user))
+    }
+
+    /// Accounts of `instruction::stable_deposit` led by the Raydium stable program id
+    pub fn next_deposit<I: Iterator<Item = &'a AccountInfo<'b>>>(
+        iter: &mut I,
+    ) -> Result<(Self, DepositAccounts<'a, 'b>), ProgramError> {
+        let program_id = next_account_info(iter)?;
+        let spl_token_program_id = next_account_info(iter)?;
+        let amm_account = next_account_info(iter)?;
+        let authority = next_account_info(iter)?;
+        let amm_open_orders = next_account_info(iter)?;
+        let pool_lp_mint = next_account_info(iter)?;
+        let pool_token_coin = next_account_info(iter)?;
+        let pool_token_pc = next_account_info(iter)?;
+        let model_data = next_account_info(iter)?;
+        let serum_market = next_account_info(iter)?;
+        let user = DepositAccounts {
+            coin: next_account_info(iter)?,
+            pc: next_account_info(iter)?,
+            lp: next_account_info(iter)?,
+            owner: next_account_info(iter)?,
+        };
+        let pool = Self {
+            program_id,
+            spl_token_program_id,
+            amm_account,
+            authority,
+            amm_open_orders,
+            pool_lp_mint: Some(pool_lp_mint),
+            pool_token_coin,
+            pool_token_pc,
+            model_data,
+            serum_market,
+            serum: None,
+        };
+        Ok((pool, user))
+    }
+
+    /// Accounts of `instruction::stable_withdraw` led by the Raydium stable program id
+    pub fn next_withdraw<I: Iterator<Item = &'a AccountInfo<'b>>>(
+        iter: &mut I,
+    ) -> Result<(Self, WithdrawAccounts<'a, 'b>), ProgramError> {
+        let program_id = next_account_info(iter)?;
+        let spl_token_program_id = next_account_info(iter)?;
+        let amm_account = next_account_info(iter)?;
+        let authority = next_account_info(iter)?;
+        let amm_open_orders = next_account_info(iter)?;
+        let pool_lp_mint = next_account_info(iter)?;
+        let pool_token_coin = next_account_info(iter)?;
+        let pool_token_pc = next_account_info(iter)?;
+        let model_data = next_account_info(iter)?;
+        let dex_program_id = next_account_info(iter)?;
+        let serum_market = next_account_info(iter)?;
+        let coin_vault = next_account_info(iter)?;
+        let pc_vault = next_account_info(iter)?;
+        let vault_signer = next_account_info(iter)?;
+        let user = WithdrawAccounts {
+            lp: next_account_info(iter)?,
+            coin: next_account_info(iter)?,
+            pc: next_account_info(iter)?,
+            owner: next_account_info(iter)?,
+        };
+        let event_queue = next_account_info(iter)?;
+        let bids = next_account_info(iter)?;
+        let asks = next_account_info(iter)?;
+        let pool = Self {
+            program_id,
+            spl_token_program_id,
+            amm_account,
+            authority,
+            amm_open_orders,
+            pool_lp_mint: Some(pool_lp_mint),
+            pool_token_coin,
+            pool_token_pc,
+            model_data,
+            serum_market,
+            serum: Some(SerumAccounts { dex_program_id, bids, asks, event_queue, coin_vault, pc_vault, vault_signer }),
+        };
+        Ok((pool, user))
+    }
+
+    fn serum(&self) -> Result<&SerumAccounts<'a, 'b>, ProgramError> {
+        self.serum.as_ref().ok_or_else(|| AmmError::InvalidInput.into())
+    }
+
+    fn pool_lp_mint(&self) -> Result<&'a AccountInfo<'b>, ProgramError> {
+        self.pool_lp_mint.ok_or_else(|| AmmError::InvalidInput.into())
+    }
+
+    fn amm_nonce(&self) -> Result<u8, ProgramError> {
+        let nonce = StableAmmInfo::load_amm_mut(self.amm_account, true)?.nonce;
+        u8::try_from(nonce).map_err(|_| AmmError::InvalidAuthority.into())
+    }
+
+    /// Output of a swap of `amount_in` on the StableSwap invariant at the current reserves,
+    /// after the pool swap fee
+    pub fn quote(&self, amount_in: u64, coin_to_pc: bool) -> Result<u64, ProgramError> {
+        let (coin_reserve, pc_reserve) = self.reserves()?;
+        let amm = StableAmmInfo::load_amm_mut(self.amm_account, true)?;
+        let (reserve_in, decimals_in, reserve_out, decimals_out) = if coin_to_pc {
+            (coin_reserve, amm.coin_decimals, pc_reserve, amm.pc_decimals)
+        } else {
+            (pc_reserve, amm.pc_decimals, coin_reserve, amm.coin_decimals)
+        };
+        let amount_in = amount_after_fee(amount_in, amm.fees.swap_fee_numerator, amm.fees.swap_fee_denominator)?;
+        let amount_out = stable_swap::quote(amm.amp, reserve_in, decimals_in, reserve_out, decimals_out, amount_in)
+            .ok_or(AmmError::ConversionFailure)?;
+        amount_out.try_into().map_err(|_| AmmError::ConversionFailure.into())
+    }
+}
+
+impl DexAdapter for RaydiumStable<'_, '_> {
+    fn pool(&self) -> &Pubkey {
+        self.amm_account.key
+    }
+
+    fn check_program(&self) -> ProgramResult {
+        if self.amm_account.owner != self.program_id.key {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        let amm = StableAmmInfo::load_amm_mut(self.amm_account, true)?;
+        if amm.model_data != *self.model_data.key {
+            return Err(AmmError::InvalidInput.into());
+        }
+        check_raydium_authority(self.program_id.key, amm.nonce, self.authority)?;
+        if let Some(serum) = &self.serum {
+            serum.check_vault_signer(self.serum_market)?;
+        }
+        Ok(())
+    }
+
+    /// Vault balances less the pnl not taken yet
+    fn reserves(&self) -> Result<(u64, u64), ProgramError> {
+        let amm = StableAmmInfo::load_amm_mut(self.amm_account, true)?;
+        if amm.token_coin != *self.pool_token_coin.key || amm.token_pc != *self.pool_token_pc.key {
+            return Err(AmmError::InvalidInput.into());
+        }
+        let coin_reserve = token_amount(self.pool_token_coin)?
+            .checked_sub(amm.out_put.need_take_pnl_coin)
+            .ok_or(AmmError::InvalidInput)?;
+        let pc_reserve = token_amount(self.pool_token_pc)?
+            .checked_sub(amm.out_put.need_take_pnl_pc)
+            .ok_or(AmmError::InvalidInput)?;
+        Ok((coin_reserve, pc_reserve))
+    }
+
+    fn swap_instruction(
+        &self,
+        user: &SwapAccounts,
+        amount_in: u64,
+        minimum_amount_out: u64,
+    ) -> Result<Instruction, ProgramError> {
+        let serum = self.serum()?;
+        crate::instruction::stable_swap(
+            self.program_id.key,
+            self.amm_account.key,
+            self.amm_nonce()?,
+            self.amm_open_orders.key,
+            self.pool_token_coin.key,
+            self.pool_token_pc.key,
+            self.model_data.key,
+            serum.dex_program_id.key,
+            self.serum_market.key,
+            serum.bids.key,
+            serum.asks.key,
+            serum.event_queue.key,
+            serum.coin_vault.key,
+            serum.pc_vault.key,
+            serum.vault_signer_nonce(self.serum_market)?,
+            user.source.key,
+            user.destination.key,
+            user.owner.key,
+            amount_in,
+            minimum_amount_out,
+        )
+    }
+
+    fn deposit_instruction(
+        &self,
+        user: &DepositAccounts,
+        max_coin_amount: u64,
+        max_pc_amount: u64,
+        base_side: u64,
+    ) -> Result<Instruction, ProgramError> {
+        crate::instruction::stable_deposit(
+            self.program_id.key,
+            self.amm_account.key,
+            self.amm_nonce()?,
+            self.amm_open_orders.key,
+            self.pool_lp_mint()?.key,
+            self.pool_token_coin.key,
+            self.pool_token_pc.key,
+            self.model_data.key,
+            self.serum_market.key,
+            user.coin.key,
+            user.pc.key,
+            user.lp.key,
+            user.owner.key,
+            max_coin_amount,
+            max_pc_amount,
+            base_side,
+        )
+    }
+
+    fn withdraw_instruction(
+        &self,
+        user: &WithdrawAccounts,
+        amount: u64,
+    ) -> Result<Instruction, ProgramError> {
+        let serum = self.serum()?;
+        crate::instruction::stable_withdraw(
+            self.program_id.key,
+            self.amm_account.key,
+            self.amm_nonce()?,
+            self.amm_open_orders.key,
+            self.pool_lp_mint()?.key,
+            self.pool_token_coin.key,
+            self.pool_token_pc.key,
+            self.model_data.key,
+            serum.dex_program_id.key,
+            self.serum_market.key,
+            serum.coin_vault.key,
+            serum.pc_vault.key,
+            serum.vault_signer_nonce(self.serum_market)?,
+            user.lp.key,
+            user.coin.key,
+            user.pc.key,
+            user.owner.key,
+            serum.event_queue.key,
+            serum.bids.key,
+            serum.asks.key,
+            amount,
+        )
+    }
+}
+
+/// Raydium concentrated liquidity pool, swaps only
+pub struct RaydiumClmm<'a, 'b> {
+    pub program_id: &'a AccountInfo<'b>,
+    pub spl_token_program_id: &'a AccountInfo<'b>,
+    pub amm_config: &'a AccountInfo<'b>,
+    pub pool_state: &'a AccountInfo<'b>,
+    pub observation_state: &'a AccountInfo<'b>,
+    pub token_vault_0: &'a AccountInfo<'b>,
+    pub token_vault_1: &'a AccountInfo<'b>,
+    /// tick arrays the swap may cross, the one of the current tick first
+    pub tick_arrays: Vec<&'a AccountInfo<'b>>,
+}
+
+impl<'a, 'b> RaydiumClmm<'a, 'b> {
+    /// Raydium CLMM program id, Spl Token program id, amm config, pool state, observation
+    /// state, token 0 vault, token 1 vault, the user source, destination and owner, then
+    /// `tick_array_count` tick arrays
+    pub fn next_swap<I: Iterator<Item = &'a AccountInfo<'b>>>(
+        iter: &mut I,
+        tick_array_count: u8,
+    ) -> Result<(Self, SwapAccounts<'a, 'b>), ProgramError> {
+        let program_id = next_account_info(iter)?;
+        let spl_token_program_id = next_account_info(iter)?;
+        let amm_config = next_account_info(iter)?;
+        let pool_state = next_account_info(iter)?;
+        let observation_state = next_account_info(iter)?;
+        let token_vault_0 = next_account_info(iter)?;
+        let token_vault_1 = next_account_info(iter)?;
+        let user = SwapAccounts {
+            source: next_account_info(iter)?,
+            destination: next_account_info(iter)?,
+            owner: next_account_info(iter)?,
+        };
+        let tick_arrays = (0..tick_array_count)
+            .map(|_| next_account_info(iter))
+            .collect::<Result<Vec<_>, _>>()?;
+        let pool = Self {
+            program_id,
+            spl_token_program_id,
+            amm_config,
+            pool_state,
+            observation_state,
+            token_vault_0,
+            token_vault_1,
+            tick_arrays,
+        };
+        Ok((pool, user))
+    }
+
+    fn load(&self) -> Result<ClmmPoolState, ProgramError> {
+        ClmmPoolState::load(self.program_id.key, self.pool_state)
+    }
+
+    /// Output of a swap of `amount_in` after the trade fee, priced with the liquidity in
+    /// range at the current price. Exact as long as the swap does not cross an initialized tick
+    pub fn quote(&self, amount_in: u64, zero_for_one: bool) -> Result<u64, ProgramError> {
+        let pool_state = self.load()?;
+        if pool_state.amm_config != *self.amm_config.key {
+            return Err(AmmError::InvalidInput.into());
+        }
+        let amm_config = ClmmAmmConfig::load(self.program_id.key, self.amm_config)?;
+        let amount_in = amount_after_fee(
+            amount_in,
+            amm_config.trade_fee_rate.into(),
+            ClmmAmmConfig::FEE_RATE_DENOMINATOR,
+        )?;
+        let amount_out = clmm_amount_out(pool_state.sqrt_price_x64, pool_state.liquidity, amount_in, zero_for_one)
+            .ok_or(AmmError::ConversionFailure)?;
+        amount_out.try_into().map_err(|_| AmmError::ConversionFailure.into())
+    }
+}
+
+/// Output for `amount_in` of liquidity `liquidity` at `sqrt_price_x64`, the price moving
+/// no further than the bounds of the tick range
+fn clmm_amount_out(sqrt_price_x64: u128, liquidity: u128, amount_in: u128, zero_for_one: bool) -> Option<u128> {
+    let next_sqrt_price = tick_math::get_next_sqrt_price_from_input(sqrt_price_x64, liquidity, amount_in, zero_for_one)?
+        .clamp(tick_math::MIN_SQRT_PRICE_X64, tick_math::MAX_SQRT_PRICE_X64);
+    if zero_for_one {
+        tick_math::get_amount_1_delta(next_sqrt_price, sqrt_price_x64, liquidity, false)
+    } else {
+        tick_math::get_amount_0_delta(sqrt_price_x64, next_sqrt_price, liquidity, false)
+    }
+}
+
+impl DexAdapter for RaydiumClmm<'_, '_> {
+    fn pool(&self) -> &Pubkey {
+        self.pool_state.key
+    }
+
+    fn check_program(&self) -> ProgramResult {
+        let pool_state = self.load()?;
+        if pool_state.amm_config != *self.amm_config.key
+            || pool_state.observation_key != *self.observation_state.key
+            || pool_state.token_vault_0 != *self.token_vault_0.key
+            || pool_state.token_vault_1 != *self.token_vault_1.key
+        {
+            return Err(AmmError::InvalidInput.into());
+        }
+        for tick_array in self.tick_arrays.iter() {
+            if ClmmTickArrayState::load(self.program_id.key, tick_array)?.pool_id != *self.pool_state.key {
+                return Err(AmmError::InvalidInput.into());
+            }
+        }
+        Ok(())
+    }
+
+    /// Token 0 and token 1 vault balances, the liquidity in range is only part of them
+    fn reserves(&self) -> Result<(u64, u64), ProgramError> {
+        let pool_state = self.load()?;
+        if pool_state.token_vault_0 != *self.token_vault_0.key || pool_state.token_vault_1 != *self.token_vault_1.key {
+            return Err(AmmError::InvalidInput.into());
+        }
+        Ok((token_amount(self.token_vault_0)?, token_amount(self.token_vault_1)?))
+    }
+
+    fn swap_instruction(
+        &self,
+        user: &SwapAccounts,
+        amount_in: u64,
+        minimum_amount_out: u64,
+    ) -> Result<Instruction, ProgramError> {
+        let pool_state = self.load()?;
+        let source_mint = spl_token::state::Account::unpack(&user.source.data.borrow())?.mint;
+        let (input_vault, output_vault) = if source_mint == pool_state.token_mint_0 {
+            (self.token_vault_0, self.token_vault_1)
+        } else if source_mint == pool_state.token_mint_1 {
+            (self.token_vault_1, self.token_vault_0)
+        } else {
+            return Err(AmmError::InvalidMint.into());
+        };
+        let tick_arrays = self.tick_arrays.iter().map(|tick_array| *tick_array.key).collect::<Vec<_>>();
+        crate::instruction::clmm_swap(
+            self.program_id.key,
+            self.amm_config.key,
+            self.pool_state.key,
+            input_vault.key,
+            output_vault.key,
+            self.observation_state.key,
+            &tick_arrays,
+            user.source.key,
+            user.destination.key,
+            user.owner.key,
+            amount_in,
+            minimum_amount_out,
+        )
+    }
+
+    fn deposit_instruction(
+        &self,
+        _user: &DepositAccounts,
+        _max_coin_amount: u64,
+        _max_pc_amount: u64,
+        _base_side: u64,
+    ) -> Result<Instruction, ProgramError> {
+        Err(AmmError::InvalidInstruction.into())
+    }
+
+    fn withdraw_instruction(
+        &self,
+        _user: &WithdrawAccounts,
+        _amount: u64,
+    ) -> Result<Instruction, ProgramError> {
+        Err(AmmError::InvalidInstruction.into())
+    }
+}
+
+/// `amount` less the pool swap fee `numerator / denominator`
+fn amount_after_fee(amount: u64, numerator: u64, denominator: u64) -> Result<u128, ProgramError> {
+    if denominator == 0 {
+        return Err(AmmError::InvalidFee.into());
+    }
+    let fee = u128::from(amount)
+        .checked_mul(numerator.into())
+        .ok_or(AmmError::ConversionFailure)?
+        / u128::from(denominator);
+    u128::from(amount).checked_sub(fee).ok_or_else(|| AmmError::InvalidFee.into())
+}
+
+fn token_amount(token_account: &AccountInfo) -> Result<u64, ProgramError> {
+    Ok(spl_token::state::Account::unpack(&token_account.data.borrow())?.amount)
+}
+
+/// Checks `authority` is the Raydium authority of the pool `nonce`
+pub fn check_raydium_authority(program_id: &Pubkey, nonce: u64, authority: &AccountInfo) -> ProgramResult {
+    let nonce = u8::try_from(nonce).map_err(|_| AmmError::InvalidAuthority)?;
+    match crate::instruction::raydium_authority(program_id, nonce) {
+        Ok(expected) if expected == *authority.key => Ok(()),
+        _ => Err(AmmError::InvalidAuthority.into()),
+    }
+}
+
+/// Checks `vault_signer` is the vault signer of `market`, returns the nonce it derives from
+pub fn check_serum_vault_signer(
+    dex_program_id: &AccountInfo,
+    market: &AccountInfo,
+    vault_signer: &AccountInfo,
+) -> Result<u64, ProgramError> {
+    let nonce = MarketState::load(dex_program_id.key, market)?.vault_signer_nonce;
+    match crate::instruction::serum_vault_signer(dex_program_id.key, market.key, nonce) {
+        Ok(expected) if expected == *vault_signer.key => Ok(nonce),
+        _ => Err(AmmError::InvalidVaultSigner.into()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const Q64: u128 = 1 << 64;
+
+    #[test]
+    fn test_tick_math_known_ticks() {
+        assert_eq!(tick_math::get_sqrt_price_at_tick(0), Some(Q64));
+        // sqrt(1.0001^tick) * 2^64 is 18445821805675392311, 18447666387855959850
+        // and 18539204128674405812, positive ticks come out a unit or two above
+        assert_eq!(tick_math::get_sqrt_price_at_tick(-1), Some(18445821805675392311));
+        assert_eq!(tick_math::get_sqrt_price_at_tick(1), Some(18447666387855959851));
+        assert_eq!(tick_math::get_sqrt_price_at_tick(100), Some(18539204128674405814));
+        assert_eq!(tick_math::get_sqrt_price_at_tick(tick_math::MIN_TICK), Some(tick_math::MIN_SQRT_PRICE_X64));
+        assert_eq!(tick_math::get_sqrt_price_at_tick(tick_math::MAX_TICK), Some(tick_math::MAX_SQRT_PRICE_X64));
+        assert_eq!(tick_math::get_sqrt_price_at_tick(tick_math::MAX_TICK + 1), None);
+    }
+
+    #[test]
+    fn test_tick_math_round_trip() {
+        let ticks = (tick_math::MIN_TICK..=tick_math::MAX_TICK)
+            .step_by(9_973)
+            .chain([-60, -1, 0, 1, 60, tick_math::MAX_TICK]);
+        for tick in ticks {
+            let sqrt_price = tick_math::get_sqrt_price_at_tick(tick).unwrap();
+            assert_eq!(tick_math::get_tick_at_sqrt_price(sqrt_price), Some(tick));
+            if tick < tick_math::MAX_TICK {
+                assert!(tick_math::get_sqrt_price_at_tick(tick + 1).unwrap() > sqrt_price);
+                assert_eq!(tick_math::get_tick_at_sqrt_price(sqrt_price + 1), Some(tick));
+            }
+        }
+    }
+
+    #[test]
+    fn test_clmm_amount_out() {
+        let liquidity = 1_000_000_000_000u128;
+        // at price 1 the output is `L * x / (L + x)`, 999999.000001, either way
+        assert_eq!(clmm_amount_out(Q64, liquidity, 1_000_000, true), Some(999_999));
+        assert_eq!(clmm_amount_out(Q64, liquidity, 1_000_000, false), Some(999_999));
+        assert_eq!(clmm_amount_out(Q64, liquidity, 0, true), Some(0));
+        // at tick 100 token 1 is worth 1.0001^-100 of token 0
+        let sqrt_price = tick_math::get_sqrt_price_at_tick(100).unwrap();
+        assert_eq!(clmm_amount_out(sqrt_price, liquidity, 1_000_000, false), Some(990_049));
+        assert_eq!(clmm_amount_out(sqrt_price, liquidity, 1_000_000, true), Some(1_010_048));
+        assert_eq!(clmm_amount_out(Q64, 0, 1_000_000, true), None);
+    }
+
+    #[test]
+    fn test_constant_product_out() {
+        // 1_000 * 100 / 1_100
+        assert_eq!(constant_product_out(1_000, 1_000, 100), Some(90));
+        assert_eq!(constant_product_out(1_000_000_000, 2_000_000_000, 1_000_000), Some(1_998_001));
+        assert_eq!(constant_product_out(1_000, 1_000, 0), Some(0));
+        assert_eq!(constant_product_out(0, 1_000, 0), None);
+        assert_eq!(constant_product_out(u64::MAX, u64::MAX, u128::MAX), None);
+    }
+
+    #[test]
+    fn test_amount_after_fee() {
+        // 0.25% of a CLMM trade fee rate of 2500
+        assert_eq!(amount_after_fee(1_000_000, 2_500, ClmmAmmConfig::FEE_RATE_DENOMINATOR).unwrap(), 997_500);
+        assert_eq!(amount_after_fee(1, 25, 10_000).unwrap(), 1);
+        assert!(amount_after_fee(1, 1, 0).is_err());
+        assert!(amount_after_fee(1, 2, 1).is_err());
+    }
+}
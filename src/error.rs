@@ -13,11 +13,65 @@ pub enum AmmError {
     /// Invalid fess
     #[error("Invalid fess")]
     InvalidFee,
-/// Invalid status
+    /// Invalid status
     #[error("Invalid status")]
     InvalidStatus,
     #[error("Invalid status")]
-    ConversionFailure
+    ConversionFailure,
+    /// Config account is not the expected PDA
+    #[error("Invalid config")]
+    InvalidConfig,
+    /// Signer is not the config admin
+    #[error("Invalid admin")]
+    InvalidAdmin,
+    /// Fee receiver account does not match the fee config
+    #[error("Invalid fee receiver")]
+    InvalidFeeReceiver,
+    /// Instruction landed after its valid_until_slot
+    #[error("Expired")]
+    Expired,
+    /// Operation is paused by the guardian
+    #[error("Paused")]
+    Paused,
+    /// Signer is not the config guardian
+    #[error("Invalid guardian")]
+    InvalidGuardian,
+    /// Pool has no registry entry
+    #[error("Pool not listed")]
+    PoolNotListed,
+    /// Pool registry entry is disabled
+    #[error("Pool disabled")]
+    PoolDisabled,
+    /// Token account mint is not one of the pool mints
+    #[error("Invalid mint")]
+    InvalidMint,
+    /// Received less than the minimum amount requested
+    #[error("Exceeds desired slippage limit")]
+    ExceededSlippage,
+    /// Lp vault account or one of its token accounts does not match
+    #[error("Invalid lp vault")]
+    InvalidVault,
+    /// Order account or one of its escrows does not match
+    #[error("Invalid order")]
+    InvalidOrder,
+    /// Pool does not pay the order price yet
+    #[error("Price not reached")]
+    PriceNotReached,
+    /// Pool authority does not derive from the pool nonce
+    #[error("Invalid authority")]
+    InvalidAuthority,
+    /// Vault signer does not derive from the market nonce
+    #[error("Invalid vault signer")]
+    InvalidVaultSigner,
+    /// Lp vault deposit without a compound earlier in the same slot
+    #[error("Lp vault not compounded")]
+    VaultNotCompounded,
+    /// Farm reward mint is neither the coin nor the pc mint of the pool
+    #[error("Reward mint not in pool")]
+    RewardMintNotInPool,
+    /// Pool registry entry is for a venue the instruction does not route to
+    #[error("Unsupported venue")]
+    UnsupportedVenue,
 }
 
 impl From<AmmError> for ProgramError {
@@ -2,18 +2,20 @@
 
 #![allow(clippy::too_many_arguments)]
 
+use crate::adapter::AdapterKind;
 use crate::error::AmmError;
-use crate::state::{Fees, AmmParams};
+use crate::serum::MarketState;
+use crate::state::{AmmInfo, Fees, FeeReceiver, FeeModel, ProviderConfig, FeeConfig, PoolKind, PoolRegistry, LpVault, ClmmPoolState, ClmmTickArrayState, MAX_FEE_RECEIVERS};
 use solana_program::{
     instruction::{AccountMeta, Instruction},
     program_error::ProgramError,
     pubkey::Pubkey,
-    program_pack::Pack,
+    system_program,
     sysvar,
 };
+use spl_associated_token_account::get_associated_token_address;
 use std::convert::TryInto;
 use std::mem::size_of;
-use arrayref::{array_ref};
 
 #[repr(C)]
 #[derive(Clone, Copy, Debug, Default, PartialEq)]
@@ -22,6 +24,19 @@ pub struct InitializeInstruction {
     pub nonce: u8,
 }
 
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Initialize2Instruction {
+    /// nonce used to create valid program address
+    pub nonce: u8,
+    /// unix timestamp swaps open at
+    pub open_time: u64,
+    /// pc transferred from the user into the new pool
+    pub init_pc_amount: u64,
+    /// coin transferred from the user into the new pool
+    pub init_coin_amount: u64,
+}
+
 #[repr(C)]
 #[derive(Clone, Copy, Debug, Default, PartialEq)]
 pub struct MonitorStepInstruction {
@@ -39,6 +54,10 @@ pub struct DepositInstruction {
     pub max_coin_amount: u64,
     pub max_pc_amount: u64,
     pub base_side: u64,
+    /// Last slot the deposit may execute in, not forwarded to Raydium
+    pub valid_until_slot: Option<u64>,
+    /// Venue the deposit is routed to, not forwarded to Raydium
+    pub adapter: AdapterKind,
 }
 
 #[repr(C)]
@@ -47,6 +66,121 @@ pub struct WithdrawInstruction {
     /// Pool token amount to transfer. token_a and token_b amount are set by
     /// the current exchange rate and size of the pool
     pub amount: u64,
+    /// Last slot the withdraw may execute in, not forwarded to Raydium
+    pub valid_until_slot: Option<u64>,
+    /// Venue the withdraw is routed to, not forwarded to Raydium
+    pub adapter: AdapterKind,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct ZapInInstruction {
+    /// Amount of the single input token, part of it is swapped to the other side of the pool
+    pub amount_in: u64,
+    /// Minimum amount of lp tokens to receive, prevents excessive slippage
+    pub minimum_lp_amount: u64,
+    /// Last slot the zap may execute in
+    pub valid_until_slot: Option<u64>,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct ZapOutInstruction {
+    /// Amount of lp tokens to burn
+    pub amount: u64,
+    /// Minimum amount of the chosen token to receive after the commission, prevents excessive slippage
+    pub minimum_amount_out: u64,
+    /// Last slot the zap may execute in
+    pub valid_until_slot: Option<u64>,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct FarmInstruction {
+    /// Amount of lp tokens to stake or unstake
+    pub amount: u64,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct LpVaultInstruction {
+    /// Lp tokens to deposit, or shares to redeem
+    pub amount: u64,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct PlaceLimitOrderInstruction {
+    /// Distinguishes the orders of one owner, part of the order PDA seeds
+    pub order_id: u64,
+    /// SOURCE amount to escrow and swap
+    pub amount_in: u64,
+    /// Limit price as the minimum amount of DESTINATION token for `amount_in`
+    pub minimum_amount_out: u64,
+    /// Last slot the order may execute in
+    pub expiry_slot: u64,
+    /// Lamports paid to the keeper executing the order
+    pub keeper_tip_lamports: u64,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct CreateDcaInstruction {
+    /// Distinguishes the schedules of one owner, part of the schedule PDA seeds
+    pub schedule_id: u64,
+    /// SOURCE amount to escrow for all executions
+    pub total_amount: u64,
+    /// SOURCE amount swapped per execution
+    pub amount_per_period: u64,
+    /// Slots between two executions
+    pub interval_slots: u64,
+    /// Minimum amount of DESTINATION token per `amount_per_period`, prevents excessive slippage
+    pub minimum_amount_out: u64,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct PlaceTriggerOrderInstruction {
+    /// Distinguishes the orders of one owner, part of the order PDA seeds
+    pub order_id: u64,
+    /// SOURCE amount to escrow and swap
+    pub amount_in: u64,
+    /// Stop price as the spot value of `amount_in` in DESTINATION token, 0 for none
+    pub stop_amount_out: u64,
+    /// Take-profit price as the spot value of `amount_in` in DESTINATION token, 0 for none
+    pub take_profit_amount_out: u64,
+    /// Largest shortfall of the output against the crossed price, in basis points
+    pub max_slippage_bps: u16,
+    /// Last slot the order may execute in
+    pub expiry_slot: u64,
+    /// Lamports paid to the keeper executing the order
+    pub keeper_tip_lamports: u64,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct SerumSwapInstruction {
+    /// SOURCE amount to sell, or to spend including the dex fee when buying the coin
+    pub amount_in: u64,
+    /// Minimum amount of DESTINATION token left to the user after the commission, prevents excessive slippage
+    pub minimum_amount_out: u64,
+    /// Worst price the order fills at, in pc lots per coin lot
+    pub limit_price: u64,
+    /// Last slot the order may be placed in
+    pub valid_until_slot: Option<u64>,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct CreatePoolInstruction {
+    /// nonce of the Raydium authority, forwarded to Raydium's initialize2
+    pub nonce: u8,
+    /// coin the creator seeds the pool with
+    pub init_coin_amount: u64,
+    /// pc the creator seeds the pool with
+    pub init_pc_amount: u64,
+    /// unix timestamp swaps through the pool open at
+    pub open_time: u64,
 }
 
 #[repr(C)]
@@ -70,6 +204,45 @@ pub struct WithdrawSrmInstruction {
     pub amount: u64,
 }
 
+/// Which side of a swap the provider commission is taken from
+#[repr(u8)]
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum FeeSide {
+    /// Fee is transferred out of the DESTINATION token after the swap
+    #[default]
+    Output = 0u8,
+    /// Fee is transferred out of the SOURCE token and deducted from `amount_in` before the swap
+    Input = 1u8,
+}
+impl FeeSide {
+    pub fn from_u8(side: u8) -> Result<Self, AmmError> {
+        match side {
+            0u8 => Ok(FeeSide::Output),
+            1u8 => Ok(FeeSide::Input),
+            _ => Err(AmmError::InvalidInstruction),
+        }
+    }
+}
+
+/// Which user token account of a native swap is the temporary wrapped SOL account
+#[repr(u8)]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum NativeSide {
+    /// SOL is swapped FROM, the account is funded from the signer's lamports
+    Input = 0u8,
+    /// SOL is swapped TO, the account is closed back to the signer
+    Output = 1u8,
+}
+impl NativeSide {
+    pub fn from_u8(side: u8) -> Result<Self, AmmError> {
+        match side {
+            0u8 => Ok(NativeSide::Input),
+            1u8 => Ok(NativeSide::Output),
+            _ => Err(AmmError::InvalidInstruction),
+        }
+    }
+}
+
 #[repr(C)]
 #[derive(Clone, Copy, Debug, Default, PartialEq)]
 pub struct SwapInstruction {
@@ -77,80 +250,859 @@ pub struct SwapInstruction {
     pub amount_in: u64,
     /// Minimum amount of DESTINATION token to output, prevents excessive slippage
     pub minimum_amount_out: u64,
+    /// Token the provider commission is collected in, not forwarded to Raydium
+    pub fee_side: FeeSide,
+    /// Last slot the swap may execute in, not forwarded to Raydium
+    pub valid_until_slot: Option<u64>,
+    /// Venue the swap is routed to, not forwarded to Raydium
+    pub adapter: AdapterKind,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct SetFeeReceiversInstruction {
+    /// number of used entries in `receivers`
+    pub receiver_count: u8,
+    /// receivers and their basis-point weights, must add up to 10,000
+    pub receivers: [FeeReceiver; MAX_FEE_RECEIVERS],
+    /// collect fees in the fee vault instead of paying the receivers on every trade
+    pub accrue_to_vault: bool,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct SetFlatFeeInstruction {
+    /// which fees the mint is charged
+    pub fee_model: FeeModel,
+    /// flat fee in lamports, charged to the signer
+    pub flat_fee_lamports: u64,
+    /// system account receiving the flat fee
+    pub treasury: Pubkey,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct SetPauseInstruction {
+    /// stops every user facing operation
+    pub paused: bool,
+    pub swap_paused: bool,
+    pub deposit_paused: bool,
+    pub withdraw_paused: bool,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct SetPoolRegistryInstruction {
+    /// approved coin mint of the pool
+    pub coin_mint: Pubkey,
+    /// approved pc mint of the pool
+    pub pc_mint: Pubkey,
+    /// allow traffic through the pool
+    pub enabled: bool,
+    /// venue of the pool
+    pub kind: PoolKind,
 }
 
-/// Instructions supported by the AmmInfo program.
+/// Instructions of the provider program, as the processor decodes them. The CPI payloads
+/// sent to Raydium are `RaydiumInstruction`s.
 #[repr(C)]
 #[derive(Clone, Debug, PartialEq)]
-pub enum AmmInstruction {
+pub enum ProviderInstruction {
 
     ///   Deposit some tokens into the pool.  The output is a "pool" token representing ownership
     ///   into the pool. Inputs are converted to the current ratio.
     ///
-    ///   0. `[]` Raydium Program id
-    ///   1. `[]` Spl Token program id
-    ///   2. `[writable]` amm Account
-    ///   3. `[]` $authority
-    ///   4. `[]` amm open_orders Account
-    ///   5. `[writable]` amm target_orders Account. To store plan orders infomations.
-    ///   6. `[writable]` pool lp mint address. Must be empty, owned by $authority.
-    ///   7. `[writable]` pool_token_coin $authority can transfer amount,
-    ///   8. `[writable]` pool_token_pc $authority can transfer amount,
-    ///   9. `[]` serum market Account. serum_dex program is the owner.
-    ///   10. `[writable]` user coin token Base Account to deposit into.
-    ///   11. `[writable]` user pc token Base Account to deposit into.
-    ///   12. `[writable]` user lp token. To deposit the generated tokens, user is the owner.
-    ///   13. '[signer]` user owner Account
-    ///   14. '[writable]` fee receiver Account
+    ///   Accounts 2 to 15 are those of the venue selected by `adapter`, listed here for
+    ///   `AdapterKind::RaydiumV4`. `AdapterKind::RaydiumStable` passes its program id followed
+    ///   by the accounts of `stable_deposit` instead, shifting the accounts after them.
+    ///
+    ///   0. `[]` config Account
+    ///   1. `[]` pool registry Account of the amm
+    ///   2. `[]` Raydium Program id
+    ///   3. `[]` Spl Token program id
+    ///   4. `[writable]` amm Account
+    ///   5. `[]` $authority, derived from the amm nonce
+    ///   6. `[]` amm open_orders Account
+    ///   7. `[writable]` amm target_orders Account. To store plan orders infomations.
+    ///   8. `[writable]` pool lp mint address. Must be empty, owned by $authority.
+    ///   9. `[writable]` pool_token_coin $authority can transfer amount,
+    ///   10. `[writable]` pool_token_pc $authority can transfer amount,
+    ///   11. `[]` serum market Account. serum_dex program is the owner.
+    ///   12. `[writable]` user coin token Base Account to deposit into.
+    ///   13. `[writable]` user pc token Base Account to deposit into.
+    ///   14. `[writable]` user lp token. To deposit the generated tokens, user is the owner.
+    ///   15. '[signer]` user owner Account, writable if a flat fee is charged
+    ///   16. `[]` coin fee config Account
+    ///   17. `[writable]` coin fee receiver Accounts, one per receiver of the coin fee config,
+    ///       or only the coin fee vault Account if the coin fee config accrues to its vault.
+    ///       Omitted if the coin fee config only charges a flat fee
+    ///   18. `[writable]` coin treasury Account, only if the coin fee config charges a flat fee
+    ///   19. `[]` System program id, only if the coin fee config charges a flat fee
+    ///   20. `[]` pc fee config Account, followed by its accounts as for 17 to 19
     Deposit(DepositInstruction),
 
     /// Swap coin or pc from pool
     ///
-    ///   0. `[]` Raydium Program id
-    ///   1. `[]` Spl Token program id
-    ///   2. `[writable]` amm Account
-    ///   3. `[]` $authority
-    ///   4. `[writable]` amm open_orders Account
-    ///   5. `[writable]` amm target_orders Account
-    ///   6. `[writable]` pool_token_coin Amm Account to swap FROM or To,
-    ///   7. `[writable]` pool_token_pc Amm Account to swap FROM or To,
-    ///   8. `[]` serum dex program id
-    ///   9. `[writable]` serum market Account. serum_dex program is the owner.
-    ///   10. `[writable]` bids Account
-    ///   11. `[writable]` asks Account
-    ///   12. `[writable]` event_q Account
-    ///   13. `[writable]` coin_vault Account
-    ///   14. `[writable]` pc_vault Account
-    ///   15. `[]` vault_signer Account
-    ///   16. `[writable]` user source token Account. user Account to swap from.
-    ///   17. `[writable]` user destination token Account. user Account to swap to.
-    ///   18. `[singer]` user owner Account
-    ///   19. `[writable]` fee receiver Account
+    ///   With `FeeSide::Output` the fee receiver holds the destination mint and the
+    ///   fee is taken after the swap. With `FeeSide::Input` it holds the source mint
+    ///   and the fee is taken from `amount_in` before the swap.
+    ///
+    ///   Accounts 2 to 20 are those of the venue selected by `adapter`, listed here for
+    ///   `AdapterKind::RaydiumV4`. `AdapterKind::RaydiumStable` passes its program id followed
+    ///   by the accounts of `stable_swap` instead, shifting the accounts after them.
+    ///   `AdapterKind::RaydiumClmm` passes the CLMM program id, the Spl Token program id, the
+    ///   amm config, pool state, observation state, token 0 vault and token 1 vault, the three
+    ///   user accounts, then its `tick_array_count` tick arrays starting with the one of the
+    ///   current tick, see `clmm_swap_tick_arrays`. Its pool registry is that of the pool state.
+    ///
+    ///   0. `[]` config Account
+    ///   1. `[]` pool registry Account of the amm
+    ///   2. `[]` Raydium Program id
+    ///   3. `[]` Spl Token program id
+    ///   4. `[writable]` amm Account
+    ///   5. `[]` $authority, derived from the amm nonce
+    ///   6. `[writable]` amm open_orders Account
+    ///   7. `[writable]` amm target_orders Account
+    ///   8. `[writable]` pool_token_coin Amm Account to swap FROM or To,
+    ///   9. `[writable]` pool_token_pc Amm Account to swap FROM or To,
+    ///   10. `[]` serum dex program id
+    ///   11. `[writable]` serum market Account. serum_dex program is the owner.
+    ///   12. `[writable]` bids Account
+    ///   13. `[writable]` asks Account
+    ///   14. `[writable]` event_q Account
+    ///   15. `[writable]` coin_vault Account
+    ///   16. `[writable]` pc_vault Account
+    ///   17. `[]` vault_signer Account, derived from the market nonce
+    ///   18. `[writable]` user source token Account. user Account to swap from.
+    ///   19. `[writable]` user destination token Account. user Account to swap to.
+    ///   20. `[singer]` user owner Account, writable if a flat fee is charged
+    ///   21. `[]` fee config Account of the `fee_side` token mint
+    ///   22. `[writable]` fee receiver Accounts, one per receiver of the fee config,
+    ///       or only the fee vault Account if the fee config accrues to its vault.
+    ///       Omitted if the fee config only charges a flat fee
+    ///   23. `[writable]` treasury Account, only if the fee config charges a flat fee
+    ///   24. `[]` System program id, only if the fee config charges a flat fee
+    ///
+    ///   Optionally followed by the accounts to create a missing user destination account
+    ///   or fee receiver account as an associated token account, with rent paid by the
+    ///   user owner, which must then be writable:
+    ///
+    ///   25. `[]` Associated token program id
+    ///   26. `[]` System program id
+    ///   27. `[]` Rent sysvar
+    ///   28. `[]` source token mint
+    ///   29. `[]` destination token mint
+    ///   30. `[]` wallet Accounts, one per fee receiver Account that does not exist yet, in order
     Swap(SwapInstruction),
+
+    ///   Creates the provider config, the signer becomes its admin.
+    ///   The guardian key in the data should be distinct from the admin.
+    ///
+    ///   0. `[writable]` config Account, PDA of `CONFIG_SEED`
+    ///   1. `[signer, writable]` admin Account, pays for the config
+    ///   2. `[]` System program id
+    InitializeConfig {
+        guardian: Pubkey,
+    },
+
+    ///   Creates or replaces the commission split of a mint. Accruing to the vault
+    ///   requires `InitializeFeeVault` to have been run for the mint.
+    ///
+    ///   0. `[]` config Account
+    ///   1. `[signer, writable]` admin Account, pays for a new fee config
+    ///   2. `[writable]` fee config Account, PDA of `[FEE_CONFIG_SEED, mint]`
+    ///   3. `[]` mint
+    ///   4. `[]` System program id
+    SetFeeReceivers(SetFeeReceiversInstruction),
+
+    ///   Creates the fee vault token account of a mint, owned by its fee config PDA
+    ///
+    ///   0. `[]` config Account
+    ///   1. `[signer, writable]` admin Account, pays for the vault
+    ///   2. `[writable]` fee config Account of the mint
+    ///   3. `[writable]` fee vault Account, PDA of `[FEE_VAULT_SEED, mint]`
+    ///   4. `[]` mint
+    ///   5. `[]` Spl Token program id
+    ///   6. `[]` System program id
+    ///   7. `[]` Rent sysvar
+    InitializeFeeVault,
+
+    ///   Moves the whole fee vault balance to the receivers of the fee config,
+    ///   split by their weights. Anyone can trigger it, the admin decides the receivers.
+    ///
+    ///   0. `[]` config Account
+    ///   1. `[]` fee config Account
+    ///   2. `[writable]` fee vault Account
+    ///   3. `[]` Spl Token program id
+    ///   4. `[writable]` fee receiver Accounts, one per receiver of the fee config
+    SweepFees,
+
+    ///   Sets the fee model of a mint and its flat lamport fee
+    ///
+    ///   0. `[]` config Account
+    ///   1. `[signer]` admin Account
+    ///   2. `[writable]` fee config Account of the mint
+    ///   3. `[]` mint
+    SetFlatFee(SetFlatFeeInstruction),
+
+    ///   Withdraw coin and pc from the pool by burning lp tokens, no commission is charged
+    ///
+    ///   Accounts 2 to 24 are those of the venue selected by `adapter`, listed here for
+    ///   `AdapterKind::RaydiumV4`. `AdapterKind::RaydiumStable` passes its program id followed
+    ///   by the accounts of `stable_withdraw` instead, shifting the accounts after them.
+    ///
+    ///   0. `[]` config Account
+    ///   1. `[]` pool registry Account of the amm
+    ///   2. `[]` Raydium Program id
+    ///   3. `[]` Spl Token program id
+    ///   4. `[writable]` amm Account
+    ///   5. `[]` $authority, derived from the amm nonce
+    ///   6. `[writable]` amm open_orders Account
+    ///   7. `[writable]` amm target_orders Account
+    ///   8. `[writable]` pool lp mint address. Must be empty, owned by $authority.
+    ///   9. `[writable]` pool_token_coin Amm Account to withdraw FROM,
+    ///   10. `[writable]` pool_token_pc Amm Account to withdraw FROM,
+    ///   11. `[writable]` withdraw queue Account
+    ///   12. `[writable]` token_temp_lp Account
+    ///   13. `[]` serum dex program id
+    ///   14. `[writable]` serum market Account. serum_dex program is the owner.
+    ///   15. `[writable]` coin_vault Account
+    ///   16. `[writable]` pc_vault Account
+    ///   17. `[]` vault_signer Account, derived from the market nonce
+    ///   18. `[writable]` user lp token Account. Source lp, amount is transferable by $authority.
+    ///   19. `[writable]` user token coin Account. user Account to credit.
+    ///   20. `[writable]` user token pc Account. user Account to credit.
+    ///   21. `[singer]` user owner Account
+    ///   22. `[writable]` event_q Account
+    ///   23. `[writable]` bids Account
+    ///   24. `[writable]` asks Account
+    ///
+    ///   Optionally followed by the accounts to create missing user coin or pc accounts as
+    ///   associated token accounts, with rent paid by the user owner, which must then be writable:
+    ///
+    ///   25. `[]` Associated token program id
+    ///   26. `[]` System program id
+    ///   27. `[]` Rent sysvar
+    ///   28. `[]` coin mint
+    ///   29. `[]` pc mint
+    Withdraw(WithdrawInstruction),
+
+    ///   Replaces the guardian
+    ///
+    ///   0. `[writable]` config Account
+    ///   1. `[signer]` admin Account
+    SetGuardian {
+        guardian: Pubkey,
+    },
+
+    ///   Pauses or resumes the provider, globally or per operation
+    ///
+    ///   0. `[writable]` config Account
+    ///   1. `[signer]` guardian Account
+    SetPause(SetPauseInstruction),
+
+    ///   Lists, updates or disables a reviewed Raydium pool. Swaps, deposits and withdrawals
+    ///   route to any venue through the adapter of their `kind`, zaps, orders and lp vaults
+    ///   only support `PoolKind::RaydiumV4`
+    ///
+    ///   0. `[]` config Account
+    ///   1. `[signer, writable]` admin Account, pays for a new registry entry
+    ///   2. `[writable]` pool registry Account, PDA of `[POOL_REGISTRY_SEED, amm]`
+    ///   3. `[]` amm Account, or the market Account of a `PoolKind::SerumMarket`
+    ///   4. `[]` System program id
+    SetPoolRegistry(SetPoolRegistryInstruction),
+
+    ///   Swap with native SOL on one side. The user token account of `native_side` is
+    ///   replaced by a temporary wrapped SOL account, created at the `[WSOL_SEED, user owner]`
+    ///   PDA, funded with `amount_in` lamports for `NativeSide::Input` and closed back to the
+    ///   user owner at the end of the instruction.
+    ///
+    ///   Accounts as for `Swap`, the user owner must be writable, followed by
+    ///
+    ///   0. `[]` System program id
+    ///   1. `[]` Rent sysvar
+    ///   2. `[]` wrapped SOL mint
+    ///
+    ///   and optionally the associated token accounts of `Swap`. The wrapped SOL side is never created.
+    SwapNative {
+        swap: SwapInstruction,
+        native_side: NativeSide,
+    },
+
+    ///   Deposit with native SOL as the coin or pc of the pool. The user token account of
+    ///   the SOL side is replaced by a temporary wrapped SOL account, as for `SwapNative`,
+    ///   funded with the max amount of that side. Anything Raydium does not take is
+    ///   returned as lamports when the account is closed.
+    ///
+    ///   Accounts as for `Deposit`, the user owner must be writable, followed by
+    ///
+    ///   0. `[]` System program id
+    ///   1. `[]` Rent sysvar
+    ///   2. `[]` wrapped SOL mint
+    DepositNative(DepositInstruction),
+
+    ///   Deposit a single token. The commission is taken once from `amount_in`, then the
+    ///   share that leaves the least dust at the pool reserves is swapped to the other side
+    ///   and both sides are deposited. Whatever Raydium does not take stays with the user.
+    ///
+    ///   0. `[]` config Account
+    ///   1. `[]` pool registry Account of the amm
+    ///   2. `[]` Raydium Program id
+    ///   3. `[]` Spl Token program id
+    ///   4. `[writable]` amm Account
+    ///   5. `[]` $authority, derived from the amm nonce
+    ///   6. `[writable]` amm open_orders Account
+    ///   7. `[writable]` amm target_orders Account
+    ///   8. `[writable]` pool lp mint address
+    ///   9. `[writable]` pool_token_coin Amm Account
+    ///   10. `[writable]` pool_token_pc Amm Account
+    ///   11. `[]` serum dex program id
+    ///   12. `[writable]` serum market Account. serum_dex program is the owner.
+    ///   13. `[writable]` bids Account
+    ///   14. `[writable]` asks Account
+    ///   15. `[writable]` event_q Account
+    ///   16. `[writable]` coin_vault Account
+    ///   17. `[writable]` pc_vault Account
+    ///   18. `[]` vault_signer Account, derived from the market nonce
+    ///   19. `[writable]` user source token Account, coin or pc of the pool
+    ///   20. `[writable]` user token Account of the other side of the pool
+    ///   21. `[writable]` user lp token Account
+    ///   22. `[signer]` user owner Account, writable if a flat fee is charged
+    ///   23. `[]` fee config Account of the source token mint, followed by its
+    ///       accounts as for `Swap`
+    ZapIn(ZapInInstruction),
+
+    ///   Withdraw into a single token. The lp tokens are burned, the side of the pool that
+    ///   is not wanted is swapped through the same pool and the commission is taken once
+    ///   from everything received in the chosen token.
+    ///
+    ///   0. `[]` config Account
+    ///   1. `[]` pool registry Account of the amm
+    ///   2. to 18. Raydium pool and serum market Accounts as for `ZapIn`
+    ///   19. `[writable]` withdraw queue Account
+    ///   20. `[writable]` token_temp_lp Account
+    ///   21. `[writable]` user lp token Account. Source lp, amount is transferable by $authority.
+    ///   22. `[writable]` user destination token Account of the chosen token, coin or pc of the pool
+    ///   23. `[writable]` user token Account of the other side of the pool
+    ///   24. `[signer]` user owner Account, writable if a flat fee is charged
+    ///   25. `[]` fee config Account of the destination token mint, followed by its
+    ///       accounts as for `Swap`
+    ZapOut(ZapOutInstruction),
+
+    ///   Stake lp tokens in a Raydium farm. Pending rewards paid out by the farm are
+    ///   charged the reward commission of their mint.
+    ///
+    ///   0. `[]` config Account
+    ///   1. `[]` Raydium farm program id, the one set by `SetFarmProgram`
+    ///   2. `[writable]` farm Account. farm program is the owner.
+    ///   3. `[]` farm authority
+    ///   4. `[writable]` user staking info Account of the farm
+    ///   5. `[signer]` user owner Account
+    ///   6. `[writable]` user lp token Account
+    ///   7. `[writable]` farm lp vault Account
+    ///   8. `[writable]` user reward token Account
+    ///   9. `[writable]` farm reward vault Account
+    ///   10. `[]` Clock sysvar
+    ///   11. `[]` Spl Token program id
+    ///   12. `[]` fee config Account of the reward mint. No commission is charged if the
+    ///       fee config of the mint was never set
+    ///   13. `[writable]` fee receiver Accounts, one per receiver of the fee config,
+    ///       or only the fee vault Account if the fee config accrues to its vault.
+    ///       Omitted if there is no fee config or it charges no reward commission
+    StakeLp(FarmInstruction),
+
+    ///   Unstake lp tokens from a Raydium farm, pending rewards are paid out as for `StakeLp`
+    ///
+    ///   Accounts as for `StakeLp`
+    UnstakeLp(FarmInstruction),
+
+    ///   Harvest the pending rewards of a Raydium farm, charged the reward commission of their mint
+    ///
+    ///   Accounts as for `StakeLp`
+    HarvestRewards,
+
+    ///   Sets the commission taken on harvested farm rewards of a mint
+    ///
+    ///   0. `[]` config Account
+    ///   1. `[signer]` admin Account
+    ///   2. `[writable]` fee config Account of the mint
+    ///   3. `[]` mint
+    SetRewardFee {
+        /// commission in basis points
+        reward_fee_bps: u16,
+    },
+
+    ///   Creates the auto-compounding lp vault of a listed pool, its share mint and its
+    ///   associated lp, coin and pc token accounts. The farm must reward the coin or pc
+    ///   of the pool, the treasury receives the performance fee in that mint. Farms paying
+    ///   another mint, such as RAY on a pool without RAY, fail with `RewardMintNotInPool`.
+    ///   Vaults are Raydium v4 only, `Compound` swaps and deposits through the v4 adapter and
+    ///   sizes its swap on the constant product, pools of another kind fail with `UnsupportedVenue`.
+    ///
+    ///   0. `[]` config Account
+    ///   1. `[signer, writable]` admin Account, pays for the new accounts
+    ///   2. `[]` pool registry Account of the amm
+    ///   3. `[]` amm Account
+    ///   4. `[writable]` lp vault Account, PDA of `[LP_VAULT_SEED, amm]`
+    ///   5. `[writable]` share mint, PDA of `[SHARE_MINT_SEED, lp vault]`
+    ///   6. `[writable]` associated lp token Account of the lp vault
+    ///   7. `[writable]` associated coin token Account of the lp vault
+    ///   8. `[writable]` associated pc token Account of the lp vault
+    ///   9. `[]` pool lp mint
+    ///   10. `[]` coin mint
+    ///   11. `[]` pc mint
+    ///   12. `[]` Raydium farm Account, owned by the farm program set by `SetFarmProgram`
+    ///   13. `[]` staking info Account of the lp vault, created for the farm program beforehand
+    ///   14. `[]` treasury token Account of the farm reward mint
+    ///   15. `[]` System program id
+    ///   16. `[]` Spl Token program id
+    ///   17. `[]` Associated token program id
+    ///   18. `[]` Rent sysvar
+    InitializeLpVault {
+        /// performance fee on compounded rewards, in basis points
+        performance_fee_bps: u16,
+        /// most the compound swap may get below its quote at the pool reserves, in basis points
+        max_slippage_bps: u16,
+    },
+
+    ///   Deposit lp tokens into an lp vault and receive shares at the current share price.
+    ///   The lp tokens are staked in the farm, pending rewards stay in the vault. Once the
+    ///   vault holds shares, `Compound` must run earlier in the same slot so the share price
+    ///   includes the pending rewards, put both in one transaction.
+    ///
+    ///   0. `[]` config Account
+    ///   1. `[writable]` lp vault Account
+    ///   2. `[writable]` share mint
+    ///   3. `[writable]` lp token Account of the lp vault
+    ///   4. `[writable]` user lp token Account
+    ///   5. `[writable]` user share token Account
+    ///   6. `[signer]` user owner Account
+    ///   7. `[]` Raydium farm program id, the one set by `SetFarmProgram`
+    ///   8. `[writable]` farm Account
+    ///   9. `[]` farm authority
+    ///   10. `[writable]` staking info Account of the lp vault
+    ///   11. `[writable]` farm lp vault Account
+    ///   12. `[writable]` farm reward vault Account
+    ///   13. `[]` Clock sysvar
+    ///   14. `[writable]` coin or pc token Account of the lp vault, the one of the reward mint
+    ///   15. `[]` Spl Token program id
+    DepositLpVault(LpVaultInstruction),
+
+    ///   Burn shares of an lp vault for their lp tokens, unstaked from the farm
+    ///
+    ///   Accounts as for `DepositLpVault`
+    WithdrawLpVault(LpVaultInstruction),
+
+    ///   Harvests the farm rewards of an lp vault, sends the performance fee on the rewards
+    ///   harvested since the last compound to the treasury, swaps the share of the rewards
+    ///   that balances the pool ratio, deposits both sides and stakes the new lp tokens.
+    ///   Anyone can crank it.
+    ///
+    ///   0. `[]` config Account
+    ///   1. `[]` pool registry Account of the amm
+    ///   2. `[writable]` lp vault Account
+    ///   3. to 19. Raydium pool and serum market Accounts as for `ZapIn`
+    ///   20. `[writable]` coin token Account of the lp vault
+    ///   21. `[writable]` pc token Account of the lp vault
+    ///   22. `[writable]` lp token Account of the lp vault
+    ///   23. `[]` Raydium farm program id, the one set by `SetFarmProgram`
+    ///   24. `[writable]` farm Account
+    ///   25. `[]` farm authority
+    ///   26. `[writable]` staking info Account of the lp vault
+    ///   27. `[writable]` farm lp vault Account
+    ///   28. `[writable]` farm reward vault Account
+    ///   29. `[]` Clock sysvar
+    ///   30. `[writable]` treasury token Account
+    ///
+    ///   The swap must return the quote at the pool reserves less the `max_slippage_bps`
+    ///   of the vault.
+    Compound {
+        /// minimum lp tokens the rewards must compound into, prevents excessive slippage
+        minimum_lp_amount: u64,
+    },
+
+    ///   Escrows `amount_in` of the source token under a new limit order. The order account
+    ///   holds the keeper tip on top of its rent, the user pays for it and both escrows.
+    ///
+    ///   0. `[]` config Account
+    ///   1. `[]` pool registry Account of the amm
+    ///   2. `[]` amm Account
+    ///   3. `[writable]` limit order Account, PDA of `[LIMIT_ORDER_SEED, user owner, order_id]`
+    ///   4. `[writable]` input escrow Account, PDA of `[ESCROW_SEED, limit order, source mint]`
+    ///   5. `[writable]` output escrow Account, PDA of `[ESCROW_SEED, limit order, destination mint]`
+    ///   6. `[]` source mint, coin or pc of the pool
+    ///   7. `[]` destination mint, the other side of the pool
+    ///   8. `[writable]` user source token Account
+    ///   9. `[]` user destination token Account, paid on execution
+    ///   10. `[signer, writable]` user owner Account
+    ///   11. `[]` System program id
+    ///   12. `[]` Spl Token program id
+    ///   13. `[]` Rent sysvar
+    PlaceLimitOrder(PlaceLimitOrderInstruction),
+
+    ///   Swaps the escrow of a limit order once the pool pays the limit price, checked on the
+    ///   output less the commission. That goes to the user, the tip to the keeper and the
+    ///   order closes. Anyone can execute it.
+    ///
+    ///   0. `[]` config Account
+    ///   1. `[]` pool registry Account of the amm
+    ///   2. `[writable]` limit order Account
+    ///   3. `[writable]` input escrow Account
+    ///   4. `[writable]` output escrow Account
+    ///   5. to 21. Raydium pool and serum market Accounts as for `ZapIn`
+    ///   22. `[writable]` user source token Account
+    ///   23. `[writable]` user destination token Account
+    ///   24. `[writable]` user owner Account, receives the rent
+    ///   25. `[signer, writable]` keeper Account, receives the tip
+    ///   26. `[]` fee config Account of the destination mint
+    ///   27. `[writable]` fee receiver Accounts, one per receiver of the fee config,
+    ///       or only the fee vault Account if the fee config accrues to its vault.
+    ///       Omitted if the fee config only charges a flat fee, which is not charged here
+    ExecuteLimitOrder,
+
+    ///   Refunds the escrow of a limit order, its tip and rent, and closes it
+    ///
+    ///   0. `[writable]` limit order Account
+    ///   1. `[writable]` input escrow Account
+    ///   2. `[writable]` output escrow Account
+    ///   3. `[writable]` user source token Account
+    ///   4. `[writable]` user destination token Account
+    ///   5. `[signer, writable]` user owner Account
+    ///   6. `[]` Spl Token program id
+    CancelLimitOrder,
+
+    ///   Escrows `total_amount` of the source token under a new dollar-cost-averaging
+    ///   schedule. The user pays for the schedule and both escrows.
+    ///
+    ///   0. `[]` config Account
+    ///   1. `[]` pool registry Account of the amm
+    ///   2. `[]` amm Account
+    ///   3. `[writable]` dca schedule Account, PDA of `[DCA_SEED, user owner, schedule_id]`
+    ///   4. `[writable]` input escrow Account, PDA of `[ESCROW_SEED, dca schedule, source mint]`
+    ///   5. `[writable]` output escrow Account, PDA of `[ESCROW_SEED, dca schedule, destination mint]`
+    ///   6. `[]` source mint, coin or pc of the pool
+    ///   7. `[]` destination mint, the other side of the pool
+    ///   8. `[writable]` user source token Account
+    ///   9. `[]` user destination token Account, paid on every execution
+    ///   10. `[signer, writable]` user owner Account
+    ///   11. `[]` System program id
+    ///   12. `[]` Spl Token program id
+    ///   13. `[]` Rent sysvar
+    CreateDca(CreateDcaInstruction),
+
+    ///   Swaps the next period of a dca schedule once its interval has elapsed, the last
+    ///   execution swaps whatever is left. The output less the commission must meet the
+    ///   price bound and goes to the user. Anyone can execute it.
+    ///
+    ///   0. `[]` config Account
+    ///   1. `[]` pool registry Account of the amm
+    ///   2. `[writable]` dca schedule Account
+    ///   3. `[writable]` input escrow Account
+    ///   4. `[writable]` output escrow Account
+    ///   5. to 21. Raydium pool and serum market Accounts as for `ZapIn`
+    ///   22. `[writable]` user destination token Account
+    ///   23. `[]` fee config Account of the destination mint, followed by its
+    ///       accounts as for `ExecuteLimitOrder`
+    ExecuteDca,
+
+    ///   Refunds what is left in the escrows of a dca schedule, its rent, and closes it
+    ///
+    ///   Accounts as for `CancelLimitOrder`, with the dca schedule Account first
+    CloseDca,
+
+    ///   Escrows `amount_in` of the source token under a new stop-loss / take-profit order.
+    ///
+    ///   Accounts as for `PlaceLimitOrder`, with the trigger order Account, PDA of
+    ///   `[TRIGGER_ORDER_SEED, user owner, order_id]`, in place of the limit order Account
+    PlaceTriggerOrder(PlaceTriggerOrderInstruction),
+
+    ///   Swaps the escrow of a trigger order once the spot value of its input, read from the
+    ///   pool reserves, crosses the stop or take-profit price. The output less the commission
+    ///   must come within `max_slippage_bps` of the crossed price. Anyone can execute it.
+    ///
+    ///   Accounts as for `ExecuteLimitOrder`, with the trigger order Account in place of the
+    ///   limit order Account
+    ExecuteTriggerOrder,
+
+    ///   Refunds the escrow of a trigger order, its tip and rent, and closes it
+    ///
+    ///   Accounts as for `CancelLimitOrder`, with the trigger order Account first
+    CancelTriggerOrder,
+
+    ///   Swaps on a Serum / OpenBook market directly with an immediate-or-cancel `new_order_v3`
+    ///   from the user open orders Account, then settles the fill and the unfilled rest back
+    ///   to the user. Selling the coin asks, buying it bids. The commission is taken from the
+    ///   settled output. The market is listed in the pool registry as a `PoolKind::SerumMarket`.
+    ///
+    ///   0. `[]` config Account
+    ///   1. `[]` pool registry Account of the market
+    ///   2. `[]` serum dex program id
+    ///   3. `[writable]` serum market Account
+    ///   4. `[writable]` user open orders Account of the market, owned by the user owner
+    ///   5. `[writable]` request queue Account
+    ///   6. `[writable]` event queue Account
+    ///   7. `[writable]` bids Account
+    ///   8. `[writable]` asks Account
+    ///   9. `[writable]` coin vault Account
+    ///   10. `[writable]` pc vault Account
+    ///   11. `[]` vault signer Account, derived from the market nonce
+    ///   12. `[writable]` user source token Account
+    ///   13. `[writable]` user destination token Account
+    ///   14. `[signer]` user owner Account, writable if a flat fee is charged
+    ///   15. `[]` Spl Token program id
+    ///   16. `[]` Rent sysvar
+    ///   17. `[]` fee config Account of the destination mint, followed by its
+    ///       accounts as for `Swap`
+    SerumSwap(SerumSwapInstruction),
+
+    ///   Creates a Raydium pool of two mints with a fee config through Raydium's `initialize2`,
+    ///   which moves the initial liquidity from the creator, who pays the listing fee and
+    ///   receives the lp tokens. The Raydium authority must derive from `nonce`. Closed while
+    ///   deposits are paused. The pool registry entry of the new pool is created disabled,
+    ///   swaps through the pool start once the admin enables it with `SetPoolRegistry`.
+    ///
+    ///   0. `[]` config Account
+    ///   1. `[]` Raydium Program id
+    ///   2. `[]` Spl Token program id
+    ///   3. `[]` Associated token program id
+    ///   4. `[]` System program id
+    ///   5. `[]` Rent sysvar
+    ///   6. `[writable]` amm Account to create
+    ///   7. `[]` $authority
+    ///   8. `[writable]` amm open_orders Account
+    ///   9. `[writable]` pool lp mint Account
+    ///   10. `[]` coin mint
+    ///   11. `[]` pc mint
+    ///   12. `[writable]` pool_token_coin Account
+    ///   13. `[writable]` pool_token_pc Account
+    ///   14. `[writable]` amm target_orders Account
+    ///   15. `[]` Raydium amm config Account
+    ///   16. `[writable]` Raydium create pool fee destination Account
+    ///   17. `[]` serum dex program id
+    ///   18. `[]` serum market Account. serum_dex program is the owner.
+    ///   19. `[signer, writable]` creator Account
+    ///   20. `[writable]` creator coin token Account
+    ///   21. `[writable]` creator pc token Account
+    ///   22. `[writable]` associated lp token Account of the creator, receives the initial lp tokens
+    ///   23. `[]` fee config Account of the coin mint
+    ///   24. `[]` fee config Account of the pc mint
+    ///   25. `[writable]` pool registry Account, PDA of `[POOL_REGISTRY_SEED, amm]`
+    ///   26. `[writable]` listing treasury Account, only if a listing fee is charged
+    CreatePool(CreatePoolInstruction),
+
+    ///   Sets the lamports charged to the creator of a Raydium pool
+    ///
+    ///   0. `[writable]` config Account
+    ///   1. `[signer]` admin Account
+    SetListingFee {
+        listing_fee_lamports: u64,
+        treasury: Pubkey,
+    },
+
+    ///   Sets the Raydium farm program staking instructions and lp vaults go to. Staking
+    ///   fails with `IncorrectProgramId` until it is set.
+    ///
+    ///   0. `[writable]` config Account
+    ///   1. `[signer]` admin Account
+    SetFarmProgram {
+        farm_program_id: Pubkey,
+    },
 }
 
-impl AmmInstruction {
-    /// Unpacks a byte buffer into a [AmmInstruction](enum.AmmInstruction.html).
+impl ProviderInstruction {
+    /// Unpacks a byte buffer into a [ProviderInstruction](enum.ProviderInstruction.html).
     pub fn unpack(input: &[u8]) -> Result<Self, ProgramError> {
         let (&tag, rest) = input.split_first().ok_or(AmmError::InvalidInstruction)?;
         Ok(match tag {
-            0 => {
+            0 => Self::Swap(Self::unpack_swap(rest)?),
+            1 => Self::Deposit(Self::unpack_deposit(rest)?),
+            2 => {
+                let (guardian, _rest) = Self::unpack_pubkey(rest)?;
+                Self::InitializeConfig { guardian }
+            }
+            3 => {
+                let (receiver_count, mut rest) = Self::unpack_u8(rest)?;
+                if receiver_count as usize > MAX_FEE_RECEIVERS {
+                    return Err(AmmError::InvalidInstruction.into());
+                }
+                let mut receivers = [FeeReceiver::default(); MAX_FEE_RECEIVERS];
+                for receiver in receivers.iter_mut().take(receiver_count as usize) {
+                    let (token_account, next) = Self::unpack_pubkey(rest)?;
+                    let (weight_bps, next) = Self::unpack_u16(next)?;
+                    *receiver = FeeReceiver { token_account, weight_bps };
+                    rest = next;
+                }
+                let (accrue_to_vault, _rest) = Self::unpack_bool(rest)?;
+                Self::SetFeeReceivers(SetFeeReceiversInstruction{ receiver_count, receivers, accrue_to_vault })
+            }
+            4 => Self::InitializeFeeVault,
+            5 => Self::SweepFees,
+            6 => {
+                let (fee_model, rest) = Self::unpack_u8(rest)?;
+                let fee_model = FeeModel::from_u8(fee_model)?;
+                let (flat_fee_lamports, rest) = Self::unpack_u64(rest)?;
+                let (treasury, _rest) = Self::unpack_pubkey(rest)?;
+                Self::SetFlatFee(SetFlatFeeInstruction{ fee_model, flat_fee_lamports, treasury })
+            }
+            7 => {
+                let (amount, rest) = Self::unpack_u64(rest)?;
+                let (valid_until_slot, rest) = Self::unpack_option_u64(rest)?;
+                let (adapter, _rest) = Self::unpack_adapter(rest)?;
+                Self::Withdraw(WithdrawInstruction{ amount, valid_until_slot, adapter })
+            }
+            8 => {
+                let (guardian, _rest) = Self::unpack_pubkey(rest)?;
+                Self::SetGuardian { guardian }
+            }
+            9 => {
+                let (paused, rest) = Self::unpack_bool(rest)?;
+                let (swap_paused, rest) = Self::unpack_bool(rest)?;
+                let (deposit_paused, rest) = Self::unpack_bool(rest)?;
+                let (withdraw_paused, _rest) = Self::unpack_bool(rest)?;
+                Self::SetPause(SetPauseInstruction{ paused, swap_paused, deposit_paused, withdraw_paused })
+            }
+            10 => {
+                let (coin_mint, rest) = Self::unpack_pubkey(rest)?;
+                let (pc_mint, rest) = Self::unpack_pubkey(rest)?;
+                let (enabled, rest) = Self::unpack_bool(rest)?;
+                let (kind, _rest) = Self::unpack_u8(rest)?;
+                let kind = PoolKind::from_u8(kind)?;
+                Self::SetPoolRegistry(SetPoolRegistryInstruction{ coin_mint, pc_mint, enabled, kind })
+            }
+            11 => {
+                let (native_side, rest) = Self::unpack_u8(rest)?;
+                let native_side = NativeSide::from_u8(native_side)?;
+                Self::SwapNative { swap: Self::unpack_swap(rest)?, native_side }
+            }
+            12 => Self::DepositNative(Self::unpack_deposit(rest)?),
+            13 => {
+                let (amount_in, rest) = Self::unpack_u64(rest)?;
+                let (minimum_lp_amount, rest) = Self::unpack_u64(rest)?;
+                let (valid_until_slot, _rest) = Self::unpack_option_u64(rest)?;
+                Self::ZapIn(ZapInInstruction{ amount_in, minimum_lp_amount, valid_until_slot })
+            }
+            14 => {
+                let (amount, rest) = Self::unpack_u64(rest)?;
+                let (minimum_amount_out, rest) = Self::unpack_u64(rest)?;
+                let (valid_until_slot, _rest) = Self::unpack_option_u64(rest)?;
+                Self::ZapOut(ZapOutInstruction{ amount, minimum_amount_out, valid_until_slot })
+            }
+            15 => {
+                let (amount, _rest) = Self::unpack_u64(rest)?;
+                Self::StakeLp(FarmInstruction{ amount })
+            }
+            16 => {
+                let (amount, _rest) = Self::unpack_u64(rest)?;
+                Self::UnstakeLp(FarmInstruction{ amount })
+            }
+            17 => Self::HarvestRewards,
+            18 => {
+                let (reward_fee_bps, _rest) = Self::unpack_u16(rest)?;
+                Self::SetRewardFee { reward_fee_bps }
+            }
+            19 => {
+                let (performance_fee_bps, rest) = Self::unpack_u16(rest)?;
+                let (max_slippage_bps, _rest) = Self::unpack_u16(rest)?;
+                Self::InitializeLpVault { performance_fee_bps, max_slippage_bps }
+            }
+            20 => {
+                let (amount, _rest) = Self::unpack_u64(rest)?;
+                Self::DepositLpVault(LpVaultInstruction{ amount })
+            }
+            21 => {
+                let (amount, _rest) = Self::unpack_u64(rest)?;
+                Self::WithdrawLpVault(LpVaultInstruction{ amount })
+            }
+            22 => {
+                let (minimum_lp_amount, _rest) = Self::unpack_u64(rest)?;
+                Self::Compound { minimum_lp_amount }
+            }
+            23 => {
+                let (order_id, rest) = Self::unpack_u64(rest)?;
                 let (amount_in, rest) = Self::unpack_u64(rest)?;
+                let (minimum_amount_out, rest) = Self::unpack_u64(rest)?;
+                let (expiry_slot, rest) = Self::unpack_u64(rest)?;
+                let (keeper_tip_lamports, _rest) = Self::unpack_u64(rest)?;
+                Self::PlaceLimitOrder(PlaceLimitOrderInstruction{
+                    order_id,
+                    amount_in,
+                    minimum_amount_out,
+                    expiry_slot,
+                    keeper_tip_lamports,
+                })
+            }
+            24 => Self::ExecuteLimitOrder,
+            25 => Self::CancelLimitOrder,
+            26 => {
+                let (schedule_id, rest) = Self::unpack_u64(rest)?;
+                let (total_amount, rest) = Self::unpack_u64(rest)?;
+                let (amount_per_period, rest) = Self::unpack_u64(rest)?;
+                let (interval_slots, rest) = Self::unpack_u64(rest)?;
                 let (minimum_amount_out, _rest) = Self::unpack_u64(rest)?;
-                Self::Swap(SwapInstruction{amount_in, minimum_amount_out})
-            },
-            1  => {
-                let (max_coin_amount, rest) = Self::unpack_u64(rest)?;
-                let (max_pc_amount, rest) = Self::unpack_u64(rest)?;
-                let (base_side, _rest) = Self::unpack_u64(rest)?;
-                Self::Deposit(DepositInstruction{ max_coin_amount, max_pc_amount, base_side })
+                Self::CreateDca(CreateDcaInstruction{
+                    schedule_id,
+                    total_amount,
+                    amount_per_period,
+                    interval_slots,
+                    minimum_amount_out,
+                })
+            }
+            27 => Self::ExecuteDca,
+            28 => Self::CloseDca,
+            29 => {
+                let (order_id, rest) = Self::unpack_u64(rest)?;
+                let (amount_in, rest) = Self::unpack_u64(rest)?;
+                let (stop_amount_out, rest) = Self::unpack_u64(rest)?;
+                let (take_profit_amount_out, rest) = Self::unpack_u64(rest)?;
+                let (max_slippage_bps, rest) = Self::unpack_u16(rest)?;
+                let (expiry_slot, rest) = Self::unpack_u64(rest)?;
+                let (keeper_tip_lamports, _rest) = Self::unpack_u64(rest)?;
+                Self::PlaceTriggerOrder(PlaceTriggerOrderInstruction{
+                    order_id,
+                    amount_in,
+                    stop_amount_out,
+                    take_profit_amount_out,
+                    max_slippage_bps,
+                    expiry_slot,
+                    keeper_tip_lamports,
+                })
+            }
+            30 => Self::ExecuteTriggerOrder,
+            31 => Self::CancelTriggerOrder,
+            32 => {
+                let (amount_in, rest) = Self::unpack_u64(rest)?;
+                let (minimum_amount_out, rest) = Self::unpack_u64(rest)?;
+                let (limit_price, rest) = Self::unpack_u64(rest)?;
+                let (valid_until_slot, _rest) = Self::unpack_option_u64(rest)?;
+                Self::SerumSwap(SerumSwapInstruction{
+                    amount_in,
+                    minimum_amount_out,
+                    limit_price,
+                    valid_until_slot,
+                })
+            }
+            33 => {
+                let (nonce, rest) = Self::unpack_u8(rest)?;
+                let (init_coin_amount, rest) = Self::unpack_u64(rest)?;
+                let (init_pc_amount, rest) = Self::unpack_u64(rest)?;
+                let (open_time, _rest) = Self::unpack_u64(rest)?;
+                Self::CreatePool(CreatePoolInstruction{ nonce, init_coin_amount, init_pc_amount, open_time })
+            }
+            34 => {
+                let (listing_fee_lamports, rest) = Self::unpack_u64(rest)?;
+                let (treasury, _rest) = Self::unpack_pubkey(rest)?;
+                Self::SetListingFee { listing_fee_lamports, treasury }
+            }
+            35 => {
+                let (farm_program_id, _rest) = Self::unpack_pubkey(rest)?;
+                Self::SetFarmProgram { farm_program_id }
             }
             _ => return Err(AmmError::InvalidInstruction.into()),
         })
     }
 
     fn unpack_u8(input: &[u8]) -> Result<(u8, &[u8]), ProgramError> {
-        if input.len() >= 1 {
+        if !input.is_empty() {
             let (amount, rest) = input.split_at(1);
             let amount = amount
                 .get(..1)
@@ -163,6 +1115,14 @@ impl AmmInstruction {
         }
     }
 
+    fn unpack_bool(input: &[u8]) -> Result<(bool, &[u8]), ProgramError> {
+        match Self::unpack_u8(input)? {
+            (0, rest) => Ok((false, rest)),
+            (1, rest) => Ok((true, rest)),
+            _ => Err(AmmError::InvalidInstruction.into()),
+        }
+    }
+
     fn unpack_u16(input: &[u8]) -> Result<(u16, &[u8]), ProgramError> {
         if input.len() >= 2 {
             let (amount, rest) = input.split_at(2);
@@ -177,6 +1137,16 @@ impl AmmInstruction {
         }
     }
 
+    fn unpack_pubkey(input: &[u8]) -> Result<(Pubkey, &[u8]), ProgramError> {
+        if input.len() >= 32 {
+            let (key, rest) = input.split_at(32);
+            let pk = Pubkey::new(key);
+            Ok((pk, rest))
+        } else {
+            Err(AmmError::InvalidInstruction.into())
+        }
+    }
+
     fn unpack_u64(input: &[u8]) -> Result<(u64, &[u8]), ProgramError> {
         if input.len() >= 8 {
             let (amount, rest) = input.split_at(8);
@@ -191,67 +1161,623 @@ impl AmmInstruction {
         }
     }
 
-    /// Packs a [AmmInstruction](enum.AmmInstruction.html) into a byte buffer.
-    pub fn pack(&self) -> Result<Vec<u8>, ProgramError> {
-        let mut buf = Vec::with_capacity(size_of::<Self>());
-        match &*self {
-            Self::Swap(SwapInstruction{amount_in, minimum_amount_out}) => {
-                buf.push(9);
-                buf.extend_from_slice(&amount_in.to_le_bytes());
-                buf.extend_from_slice(&minimum_amount_out.to_le_bytes());
-            }
-            Self::Deposit(DepositInstruction{ max_coin_amount, max_pc_amount, base_side }) => {
-                buf.push(3);
-                buf.extend_from_slice(&max_coin_amount.to_le_bytes());
-                buf.extend_from_slice(&max_pc_amount.to_le_bytes());
-                buf.extend_from_slice(&base_side.to_le_bytes());
+    fn unpack_swap(input: &[u8]) -> Result<SwapInstruction, ProgramError> {
+        let (amount_in, rest) = Self::unpack_u64(input)?;
+        let (minimum_amount_out, rest) = Self::unpack_u64(rest)?;
+        let (fee_side, rest) = Self::unpack_u8(rest)?;
+        let fee_side = FeeSide::from_u8(fee_side)?;
+        let (valid_until_slot, rest) = Self::unpack_option_u64(rest)?;
+        let (adapter, _rest) = Self::unpack_adapter(rest)?;
+        Ok(SwapInstruction{amount_in, minimum_amount_out, fee_side, valid_until_slot, adapter})
+    }
+
+    fn unpack_deposit(input: &[u8]) -> Result<DepositInstruction, ProgramError> {
+        let (max_coin_amount, rest) = Self::unpack_u64(input)?;
+        let (max_pc_amount, rest) = Self::unpack_u64(rest)?;
+        let (base_side, rest) = Self::unpack_u64(rest)?;
+        let (valid_until_slot, rest) = Self::unpack_option_u64(rest)?;
+        let (adapter, _rest) = Self::unpack_adapter(rest)?;
+        Ok(DepositInstruction{ max_coin_amount, max_pc_amount, base_side, valid_until_slot, adapter })
+    }
+
+    /// Optional trailing adapter tag, Raydium AMM v4 if missing
+    fn unpack_adapter(input: &[u8]) -> Result<(AdapterKind, &[u8]), ProgramError> {
+        if input.is_empty() {
+            return Ok((AdapterKind::RaydiumV4, input));
+        }
+        AdapterKind::unpack(input)
+    }
+
+    /// Optional trailing u64, a missing value or a 0 flag is `None`, a 1 flag is followed by the value
+    fn unpack_option_u64(input: &[u8]) -> Result<(Option<u64>, &[u8]), ProgramError> {
+        if input.is_empty() {
+            return Ok((None, input));
+        }
+        match Self::unpack_u8(input)? {
+            (0, rest) => Ok((None, rest)),
+            (1, rest) => {
+                let (value, rest) = Self::unpack_u64(rest)?;
+                Ok((Some(value), rest))
             }
+            _ => Err(AmmError::InvalidInstruction.into()),
         }
-        Ok(buf)
     }
-}
 
-/// Creates a 'deposit' instruction.
-pub fn deposit(
-    program_id: &Pubkey,
-    amm_id: &Pubkey,
-    amm_authority: &Pubkey,
-    amm_open_orders: &Pubkey,
-    amm_target_orders: &Pubkey,
-    lp_mint_address: &Pubkey,
-    pool_coin_token_account: &Pubkey,
-    pool_pc_token_account: &Pubkey,
-    serum_market: &Pubkey,
-    user_coin_token_account: &Pubkey,
-    user_pc_token_account: &Pubkey,
-    user_lp_token_account: &Pubkey,
-    user_owner: &Pubkey,
+    fn pack_swap(swap: &SwapInstruction, buf: &mut Vec<u8>) {
+        buf.extend_from_slice(&swap.amount_in.to_le_bytes());
+        buf.extend_from_slice(&swap.minimum_amount_out.to_le_bytes());
+        buf.push(swap.fee_side as u8);
+        Self::pack_option_u64(swap.valid_until_slot, buf);
+        swap.adapter.pack(buf);
+    }
 
-    max_coin_amount: u64,
-    max_pc_amount: u64,
-    base_side: u64,
-) -> Result<Instruction, ProgramError> {
-    let data = AmmInstruction::Deposit(DepositInstruction{ max_coin_amount, max_pc_amount, base_side }).pack()?;
+    fn pack_deposit(deposit: &DepositInstruction, buf: &mut Vec<u8>) {
+        buf.extend_from_slice(&deposit.max_coin_amount.to_le_bytes());
+        buf.extend_from_slice(&deposit.max_pc_amount.to_le_bytes());
+        buf.extend_from_slice(&deposit.base_side.to_le_bytes());
+        Self::pack_option_u64(deposit.valid_until_slot, buf);
+        deposit.adapter.pack(buf);
+    }
 
-    let accounts = vec![
-        // spl token
-        AccountMeta::new_readonly(spl_token::id(), false),
-        // amm
-        AccountMeta::new(*amm_id, false),
-        AccountMeta::new_readonly(*amm_authority, false),
-        AccountMeta::new_readonly(*amm_open_orders, false),
-        AccountMeta::new(*amm_target_orders, false),
-        AccountMeta::new(*lp_mint_address, false),
-        AccountMeta::new(*pool_coin_token_account, false),
-        AccountMeta::new(*pool_pc_token_account, false),
-        // serum
-        AccountMeta::new_readonly(*serum_market, false),
-        // user
-        AccountMeta::new(*user_coin_token_account, false),
-        AccountMeta::new(*user_pc_token_account, false),
-        AccountMeta::new(*user_lp_token_account, false),
-        AccountMeta::new_readonly(*user_owner, true),
-    ];
+    fn pack_option_u64(value: Option<u64>, buf: &mut Vec<u8>) {
+        match value {
+            Some(value) => {
+                buf.push(1);
+                buf.extend_from_slice(&value.to_le_bytes());
+            }
+            None => buf.push(0),
+        }
+    }
+
+    /// Packs a [ProviderInstruction](enum.ProviderInstruction.html) into a byte buffer.
+    pub fn pack(&self) -> Result<Vec<u8>, ProgramError> {
+        let mut buf = Vec::with_capacity(size_of::<Self>());
+        match self {
+            Self::Swap(swap) => {
+                buf.push(0);
+                Self::pack_swap(swap, &mut buf);
+            }
+            Self::Deposit(deposit) => {
+                buf.push(1);
+                Self::pack_deposit(deposit, &mut buf);
+            }
+            Self::InitializeConfig { guardian } => {
+                buf.push(2);
+                buf.extend_from_slice(guardian.as_ref());
+            }
+            Self::SetFeeReceivers(SetFeeReceiversInstruction{ receiver_count, receivers, accrue_to_vault }) => {
+                buf.push(3);
+                buf.push(*receiver_count);
+                for receiver in receivers.iter().take(*receiver_count as usize) {
+                    buf.extend_from_slice(receiver.token_account.as_ref());
+                    buf.extend_from_slice(&receiver.weight_bps.to_le_bytes());
+                }
+                buf.push(*accrue_to_vault as u8);
+            }
+            Self::InitializeFeeVault => {
+                buf.push(4);
+            }
+            Self::SweepFees => {
+                buf.push(5);
+            }
+            Self::SetFlatFee(SetFlatFeeInstruction{ fee_model, flat_fee_lamports, treasury }) => {
+                buf.push(6);
+                buf.push(*fee_model as u8);
+                buf.extend_from_slice(&flat_fee_lamports.to_le_bytes());
+                buf.extend_from_slice(treasury.as_ref());
+            }
+            Self::Withdraw(WithdrawInstruction{ amount, valid_until_slot, adapter }) => {
+                buf.push(7);
+                buf.extend_from_slice(&amount.to_le_bytes());
+                Self::pack_option_u64(*valid_until_slot, &mut buf);
+                adapter.pack(&mut buf);
+            }
+            Self::SetGuardian { guardian } => {
+                buf.push(8);
+                buf.extend_from_slice(guardian.as_ref());
+            }
+            Self::SetPause(SetPauseInstruction{ paused, swap_paused, deposit_paused, withdraw_paused }) => {
+                buf.push(9);
+                buf.push(*paused as u8);
+                buf.push(*swap_paused as u8);
+                buf.push(*deposit_paused as u8);
+                buf.push(*withdraw_paused as u8);
+            }
+            Self::SetPoolRegistry(SetPoolRegistryInstruction{ coin_mint, pc_mint, enabled, kind }) => {
+                buf.push(10);
+                buf.extend_from_slice(coin_mint.as_ref());
+                buf.extend_from_slice(pc_mint.as_ref());
+                buf.push(*enabled as u8);
+                buf.push(*kind as u8);
+            }
+            Self::SwapNative { swap, native_side } => {
+                buf.push(11);
+                buf.push(*native_side as u8);
+                Self::pack_swap(swap, &mut buf);
+            }
+            Self::DepositNative(deposit) => {
+                buf.push(12);
+                Self::pack_deposit(deposit, &mut buf);
+            }
+            Self::ZapIn(ZapInInstruction{ amount_in, minimum_lp_amount, valid_until_slot }) => {
+                buf.push(13);
+                buf.extend_from_slice(&amount_in.to_le_bytes());
+                buf.extend_from_slice(&minimum_lp_amount.to_le_bytes());
+                Self::pack_option_u64(*valid_until_slot, &mut buf);
+            }
+            Self::ZapOut(ZapOutInstruction{ amount, minimum_amount_out, valid_until_slot }) => {
+                buf.push(14);
+                buf.extend_from_slice(&amount.to_le_bytes());
+                buf.extend_from_slice(&minimum_amount_out.to_le_bytes());
+                Self::pack_option_u64(*valid_until_slot, &mut buf);
+            }
+            Self::StakeLp(FarmInstruction{ amount }) => {
+                buf.push(15);
+                buf.extend_from_slice(&amount.to_le_bytes());
+            }
+            Self::UnstakeLp(FarmInstruction{ amount }) => {
+                buf.push(16);
+                buf.extend_from_slice(&amount.to_le_bytes());
+            }
+            Self::HarvestRewards => {
+                buf.push(17);
+            }
+            Self::SetRewardFee { reward_fee_bps } => {
+                buf.push(18);
+                buf.extend_from_slice(&reward_fee_bps.to_le_bytes());
+            }
+            Self::InitializeLpVault { performance_fee_bps, max_slippage_bps } => {
+                buf.push(19);
+                buf.extend_from_slice(&performance_fee_bps.to_le_bytes());
+                buf.extend_from_slice(&max_slippage_bps.to_le_bytes());
+            }
+            Self::DepositLpVault(LpVaultInstruction{ amount }) => {
+                buf.push(20);
+                buf.extend_from_slice(&amount.to_le_bytes());
+            }
+            Self::WithdrawLpVault(LpVaultInstruction{ amount }) => {
+                buf.push(21);
+                buf.extend_from_slice(&amount.to_le_bytes());
+            }
+            Self::Compound { minimum_lp_amount } => {
+                buf.push(22);
+                buf.extend_from_slice(&minimum_lp_amount.to_le_bytes());
+            }
+            Self::PlaceLimitOrder(PlaceLimitOrderInstruction{
+                order_id,
+                amount_in,
+                minimum_amount_out,
+                expiry_slot,
+                keeper_tip_lamports,
+            }) => {
+                buf.push(23);
+                buf.extend_from_slice(&order_id.to_le_bytes());
+                buf.extend_from_slice(&amount_in.to_le_bytes());
+                buf.extend_from_slice(&minimum_amount_out.to_le_bytes());
+                buf.extend_from_slice(&expiry_slot.to_le_bytes());
+                buf.extend_from_slice(&keeper_tip_lamports.to_le_bytes());
+            }
+            Self::ExecuteLimitOrder => {
+                buf.push(24);
+            }
+            Self::CancelLimitOrder => {
+                buf.push(25);
+            }
+            Self::CreateDca(CreateDcaInstruction{
+                schedule_id,
+                total_amount,
+                amount_per_period,
+                interval_slots,
+                minimum_amount_out,
+            }) => {
+                buf.push(26);
+                buf.extend_from_slice(&schedule_id.to_le_bytes());
+                buf.extend_from_slice(&total_amount.to_le_bytes());
+                buf.extend_from_slice(&amount_per_period.to_le_bytes());
+                buf.extend_from_slice(&interval_slots.to_le_bytes());
+                buf.extend_from_slice(&minimum_amount_out.to_le_bytes());
+            }
+            Self::ExecuteDca => {
+                buf.push(27);
+            }
+            Self::CloseDca => {
+                buf.push(28);
+            }
+            Self::PlaceTriggerOrder(PlaceTriggerOrderInstruction{
+                order_id,
+                amount_in,
+                stop_amount_out,
+                take_profit_amount_out,
+                max_slippage_bps,
+                expiry_slot,
+                keeper_tip_lamports,
+            }) => {
+                buf.push(29);
+                buf.extend_from_slice(&order_id.to_le_bytes());
+                buf.extend_from_slice(&amount_in.to_le_bytes());
+                buf.extend_from_slice(&stop_amount_out.to_le_bytes());
+                buf.extend_from_slice(&take_profit_amount_out.to_le_bytes());
+                buf.extend_from_slice(&max_slippage_bps.to_le_bytes());
+                buf.extend_from_slice(&expiry_slot.to_le_bytes());
+                buf.extend_from_slice(&keeper_tip_lamports.to_le_bytes());
+            }
+            Self::ExecuteTriggerOrder => {
+                buf.push(30);
+            }
+            Self::CancelTriggerOrder => {
+                buf.push(31);
+            }
+            Self::SerumSwap(SerumSwapInstruction{
+                amount_in,
+                minimum_amount_out,
+                limit_price,
+                valid_until_slot,
+            }) => {
+                buf.push(32);
+                buf.extend_from_slice(&amount_in.to_le_bytes());
+                buf.extend_from_slice(&minimum_amount_out.to_le_bytes());
+                buf.extend_from_slice(&limit_price.to_le_bytes());
+                Self::pack_option_u64(*valid_until_slot, &mut buf);
+            }
+            Self::CreatePool(CreatePoolInstruction{ nonce, init_coin_amount, init_pc_amount, open_time }) => {
+                buf.push(33);
+                buf.push(*nonce);
+                buf.extend_from_slice(&init_coin_amount.to_le_bytes());
+                buf.extend_from_slice(&init_pc_amount.to_le_bytes());
+                buf.extend_from_slice(&open_time.to_le_bytes());
+            }
+            Self::SetListingFee { listing_fee_lamports, treasury } => {
+                buf.push(34);
+                buf.extend_from_slice(&listing_fee_lamports.to_le_bytes());
+                buf.extend_from_slice(treasury.as_ref());
+            }
+            Self::SetFarmProgram { farm_program_id } => {
+                buf.push(35);
+                buf.extend_from_slice(farm_program_id.as_ref());
+            }
+        }
+        Ok(buf)
+    }
+}
+
+/// Instructions of the Raydium AMM v4 and stable programs the provider sends through CPI
+#[repr(C)]
+#[derive(Clone, Debug, PartialEq)]
+pub enum RaydiumInstruction {
+    /// Creates the pool accounts and moves the initial liquidity from the user
+    Initialize2(Initialize2Instruction),
+    /// Crank placing the orders of a pool on its market
+    MonitorStep(MonitorStepInstruction),
+    /// Deposits up to `max_coin_amount` and `max_pc_amount` at the pool ratio, `base_side`
+    /// 0 fixes the coin amount and 1 the pc amount
+    Deposit { max_coin_amount: u64, max_pc_amount: u64, base_side: u64 },
+    /// Burns `amount` lp tokens for the coin and pc they own
+    Withdraw { amount: u64 },
+    /// Swaps exactly `amount_in` for at least `minimum_amount_out`
+    Swap { amount_in: u64, minimum_amount_out: u64 },
+}
+
+impl RaydiumInstruction {
+    /// Unpacks a byte buffer into a [RaydiumInstruction](enum.RaydiumInstruction.html).
+    pub fn unpack(input: &[u8]) -> Result<Self, ProgramError> {
+        let (&tag, rest) = input.split_first().ok_or(AmmError::InvalidInstruction)?;
+        Ok(match tag {
+            1 => {
+                let (nonce, rest) = ProviderInstruction::unpack_u8(rest)?;
+                let (open_time, rest) = ProviderInstruction::unpack_u64(rest)?;
+                let (init_pc_amount, rest) = ProviderInstruction::unpack_u64(rest)?;
+                let (init_coin_amount, _rest) = ProviderInstruction::unpack_u64(rest)?;
+                Self::Initialize2(Initialize2Instruction{ nonce, open_time, init_pc_amount, init_coin_amount })
+            }
+            2 => {
+                let (plan_order_limit, rest) = ProviderInstruction::unpack_u16(rest)?;
+                let (place_order_limit, rest) = ProviderInstruction::unpack_u16(rest)?;
+                let (cancel_order_limit, _rest) = ProviderInstruction::unpack_u16(rest)?;
+                Self::MonitorStep(MonitorStepInstruction{ plan_order_limit, place_order_limit, cancel_order_limit })
+            }
+            3 => {
+                let (max_coin_amount, rest) = ProviderInstruction::unpack_u64(rest)?;
+                let (max_pc_amount, rest) = ProviderInstruction::unpack_u64(rest)?;
+                let (base_side, _rest) = ProviderInstruction::unpack_u64(rest)?;
+                Self::Deposit { max_coin_amount, max_pc_amount, base_side }
+            }
+            4 => {
+                let (amount, _rest) = ProviderInstruction::unpack_u64(rest)?;
+                Self::Withdraw { amount }
+            }
+            9 => {
+                let (amount_in, rest) = ProviderInstruction::unpack_u64(rest)?;
+                let (minimum_amount_out, _rest) = ProviderInstruction::unpack_u64(rest)?;
+                Self::Swap { amount_in, minimum_amount_out }
+            }
+            _ => return Err(AmmError::InvalidInstruction.into()),
+        })
+    }
+
+    /// Packs a [RaydiumInstruction](enum.RaydiumInstruction.html) into a byte buffer.
+    pub fn pack(&self) -> Vec<u8> {
+        let mut buf = Vec::with_capacity(size_of::<Self>());
+        match self {
+            Self::Initialize2(Initialize2Instruction{ nonce, open_time, init_pc_amount, init_coin_amount }) => {
+                buf.push(1);
+                buf.push(*nonce);
+                buf.extend_from_slice(&open_time.to_le_bytes());
+                buf.extend_from_slice(&init_pc_amount.to_le_bytes());
+                buf.extend_from_slice(&init_coin_amount.to_le_bytes());
+            }
+            Self::MonitorStep(MonitorStepInstruction{ plan_order_limit, place_order_limit, cancel_order_limit }) => {
+                buf.push(2);
+                buf.extend_from_slice(&plan_order_limit.to_le_bytes());
+                buf.extend_from_slice(&place_order_limit.to_le_bytes());
+                buf.extend_from_slice(&cancel_order_limit.to_le_bytes());
+            }
+            Self::Deposit { max_coin_amount, max_pc_amount, base_side } => {
+                buf.push(3);
+                buf.extend_from_slice(&max_coin_amount.to_le_bytes());
+                buf.extend_from_slice(&max_pc_amount.to_le_bytes());
+                buf.extend_from_slice(&base_side.to_le_bytes());
+            }
+            Self::Withdraw { amount } => {
+                buf.push(4);
+                buf.extend_from_slice(&amount.to_le_bytes());
+            }
+            Self::Swap { amount_in, minimum_amount_out } => {
+                buf.push(9);
+                buf.extend_from_slice(&amount_in.to_le_bytes());
+                buf.extend_from_slice(&minimum_amount_out.to_le_bytes());
+            }
+        }
+        buf
+    }
+}
+
+/// Seed of the authority of the Raydium AMM v4 pools, with the nonce of the pool
+pub const AUTHORITY_AMM: &[u8] = b"amm authority";
+
+/// Raydium authority of the pools with `nonce`
+pub fn raydium_authority(program_id: &Pubkey, nonce: u8) -> Result<Pubkey, ProgramError> {
+    Pubkey::create_program_address(&[AUTHORITY_AMM, &[nonce]], program_id)
+        .map_err(|_| AmmError::InvalidInput.into())
+}
+
+/// Creates an 'initialize2' instruction. Raydium transfers the initial liquidity from the
+/// user token accounts and mints the lp tokens to the associated lp token account of the user.
+pub fn initialize2(
+    program_id: &Pubkey,
+    amm_id: &Pubkey,
+    amm_open_orders: &Pubkey,
+    lp_mint_address: &Pubkey,
+    coin_mint_address: &Pubkey,
+    pc_mint_address: &Pubkey,
+    pool_coin_token_account: &Pubkey,
+    pool_pc_token_account: &Pubkey,
+    pool_target_orders_account: &Pubkey,
+    amm_config: &Pubkey,
+    create_fee_destination: &Pubkey,
+    serum_program_id: &Pubkey,
+    serum_market: &Pubkey,
+    user_wallet: &Pubkey,
+    user_token_coin: &Pubkey,
+    user_token_pc: &Pubkey,
+
+    nonce: u8,
+    open_time: u64,
+    init_pc_amount: u64,
+    init_coin_amount: u64,
+) -> Result<Instruction, ProgramError> {
+    let data = RaydiumInstruction::Initialize2(Initialize2Instruction{ nonce, open_time, init_pc_amount, init_coin_amount }).pack();
+    let amm_authority = raydium_authority(program_id, nonce)?;
+    let user_lp_token_account = get_associated_token_address(user_wallet, lp_mint_address);
+
+    let accounts = vec![
+        // spl token
+        AccountMeta::new_readonly(spl_token::id(), false),
+        AccountMeta::new_readonly(spl_associated_token_account::id(), false),
+        AccountMeta::new_readonly(system_program::id(), false),
+        AccountMeta::new_readonly(sysvar::rent::id(), false),
+        // amm
+        AccountMeta::new(*amm_id, false),
+        AccountMeta::new_readonly(amm_authority, false),
+        AccountMeta::new(*amm_open_orders, false),
+        AccountMeta::new(*lp_mint_address, false),
+        AccountMeta::new_readonly(*coin_mint_address, false),
+        AccountMeta::new_readonly(*pc_mint_address, false),
+        AccountMeta::new(*pool_coin_token_account, false),
+        AccountMeta::new(*pool_pc_token_account, false),
+        AccountMeta::new(*pool_target_orders_account, false),
+        AccountMeta::new_readonly(*amm_config, false),
+        AccountMeta::new(*create_fee_destination, false),
+        // serum
+        AccountMeta::new_readonly(*serum_program_id, false),
+        AccountMeta::new_readonly(*serum_market, false),
+        // user wallet
+        AccountMeta::new(*user_wallet, true),
+        AccountMeta::new(*user_token_coin, false),
+        AccountMeta::new(*user_token_pc, false),
+        AccountMeta::new(user_lp_token_account, false),
+    ];
+
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data,
+    })
+}
+
+/// Creates a 'monitor_step' instruction, the crank placing the orders of a pool on its market.
+pub fn monitor_step(
+    program_id: &Pubkey,
+    amm_id: &Pubkey,
+    amm_nonce: u8,
+    amm_open_orders: &Pubkey,
+    amm_target_orders: &Pubkey,
+    pool_coin_token_account: &Pubkey,
+    pool_pc_token_account: &Pubkey,
+    pool_withdraw_queue: &Pubkey,
+    serum_program_id: &Pubkey,
+    serum_market: &Pubkey,
+    serum_coin_vault_account: &Pubkey,
+    serum_pc_vault_account: &Pubkey,
+    serum_vault_signer_nonce: u64,
+    serum_req_q: &Pubkey,
+    serum_event_q: &Pubkey,
+    serum_bids: &Pubkey,
+    serum_asks: &Pubkey,
+
+    plan_order_limit: u16,
+    place_order_limit: u16,
+    cancel_order_limit: u16,
+) -> Result<Instruction, ProgramError> {
+    let data = RaydiumInstruction::MonitorStep(MonitorStepInstruction{
+        plan_order_limit,
+        place_order_limit,
+        cancel_order_limit,
+    }).pack();
+    let amm_authority = raydium_authority(program_id, amm_nonce)?;
+    let vault_signer = serum_vault_signer(serum_program_id, serum_market, serum_vault_signer_nonce)?;
+
+    let accounts = vec![
+        // spl token
+        AccountMeta::new_readonly(spl_token::id(), false),
+        AccountMeta::new_readonly(sysvar::rent::id(), false),
+        AccountMeta::new_readonly(sysvar::clock::id(), false),
+        // amm
+        AccountMeta::new(*amm_id, false),
+        AccountMeta::new_readonly(amm_authority, false),
+        AccountMeta::new(*amm_open_orders, false),
+        AccountMeta::new(*amm_target_orders, false),
+        AccountMeta::new(*pool_coin_token_account, false),
+        AccountMeta::new(*pool_pc_token_account, false),
+        AccountMeta::new(*pool_withdraw_queue, false),
+        // serum
+        AccountMeta::new_readonly(*serum_program_id, false),
+        AccountMeta::new(*serum_market, false),
+        AccountMeta::new(*serum_coin_vault_account, false),
+        AccountMeta::new(*serum_pc_vault_account, false),
+        AccountMeta::new_readonly(vault_signer, false),
+        AccountMeta::new(*serum_req_q, false),
+        AccountMeta::new(*serum_event_q, false),
+        AccountMeta::new(*serum_bids, false),
+        AccountMeta::new(*serum_asks, false),
+    ];
+
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data,
+    })
+}
+
+/// Creates a 'deposit' instruction.
+pub fn deposit(
+    program_id: &Pubkey,
+    amm_id: &Pubkey,
+    amm_nonce: u8,
+    amm_open_orders: &Pubkey,
+    amm_target_orders: &Pubkey,
+    lp_mint_address: &Pubkey,
+    pool_coin_token_account: &Pubkey,
+    pool_pc_token_account: &Pubkey,
+    serum_market: &Pubkey,
+    user_coin_token_account: &Pubkey,
+    user_pc_token_account: &Pubkey,
+    user_lp_token_account: &Pubkey,
+    user_owner: &Pubkey,
+
+    max_coin_amount: u64,
+    max_pc_amount: u64,
+    base_side: u64,
+) -> Result<Instruction, ProgramError> {
+    let data = RaydiumInstruction::Deposit { max_coin_amount, max_pc_amount, base_side }.pack();
+    let amm_authority = raydium_authority(program_id, amm_nonce)?;
+
+    let accounts = vec![
+        // spl token
+        AccountMeta::new_readonly(spl_token::id(), false),
+        // amm
+        AccountMeta::new(*amm_id, false),
+        AccountMeta::new_readonly(amm_authority, false),
+        AccountMeta::new_readonly(*amm_open_orders, false),
+        AccountMeta::new(*amm_target_orders, false),
+        AccountMeta::new(*lp_mint_address, false),
+        AccountMeta::new(*pool_coin_token_account, false),
+        AccountMeta::new(*pool_pc_token_account, false),
+        // serum
+        AccountMeta::new_readonly(*serum_market, false),
+        // user
+        AccountMeta::new(*user_coin_token_account, false),
+        AccountMeta::new(*user_pc_token_account, false),
+        AccountMeta::new(*user_lp_token_account, false),
+        AccountMeta::new_readonly(*user_owner, true),
+    ];
+
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data,
+    })
+}
+
+/// Creates a 'withdraw' instruction.
+pub fn withdraw(
+    program_id: &Pubkey,
+    amm_id: &Pubkey,
+    amm_nonce: u8,
+    amm_open_orders: &Pubkey,
+    amm_target_orders: &Pubkey,
+    lp_mint_address: &Pubkey,
+    pool_coin_token_account: &Pubkey,
+    pool_pc_token_account: &Pubkey,
+    pool_withdraw_queue: &Pubkey,
+    pool_temp_lp_token_account: &Pubkey,
+    serum_program_id: &Pubkey,
+    serum_market: &Pubkey,
+    serum_coin_vault_account: &Pubkey,
+    serum_pc_vault_account: &Pubkey,
+    serum_vault_signer_nonce: u64,
+    user_lp_token_account: &Pubkey,
+    user_coin_token_account: &Pubkey,
+    user_pc_token_account: &Pubkey,
+    user_owner: &Pubkey,
+    serum_event_queue: &Pubkey,
+    serum_bids: &Pubkey,
+    serum_asks: &Pubkey,
+
+    amount: u64,
+) -> Result<Instruction, ProgramError> {
+    let data = RaydiumInstruction::Withdraw { amount }.pack();
+    let amm_authority = raydium_authority(program_id, amm_nonce)?;
+    let vault_signer = serum_vault_signer(serum_program_id, serum_market, serum_vault_signer_nonce)?;
+
+    let accounts = vec![
+        // spl token
+        AccountMeta::new_readonly(spl_token::id(), false),
+        // amm
+        AccountMeta::new(*amm_id, false),
+        AccountMeta::new_readonly(amm_authority, false),
+        AccountMeta::new(*amm_open_orders, false),
+        AccountMeta::new(*amm_target_orders, false),
+        AccountMeta::new(*lp_mint_address, false),
+        AccountMeta::new(*pool_coin_token_account, false),
+        AccountMeta::new(*pool_pc_token_account, false),
+        AccountMeta::new(*pool_withdraw_queue, false),
+        AccountMeta::new(*pool_temp_lp_token_account, false),
+        // serum
+        AccountMeta::new_readonly(*serum_program_id, false),
+        AccountMeta::new(*serum_market, false),
+        AccountMeta::new(*serum_coin_vault_account, false),
+        AccountMeta::new(*serum_pc_vault_account, false),
+        AccountMeta::new_readonly(vault_signer, false),
+        // user
+        AccountMeta::new(*user_lp_token_account, false),
+        AccountMeta::new(*user_coin_token_account, false),
+        AccountMeta::new(*user_pc_token_account, false),
+        AccountMeta::new_readonly(*user_owner, true),
+        // serum orderbook
+        AccountMeta::new(*serum_event_queue, false),
+        AccountMeta::new(*serum_bids, false),
+        AccountMeta::new(*serum_asks, false),
+    ];
 
     Ok(Instruction {
         program_id: *program_id,
@@ -264,7 +1790,7 @@ pub fn deposit(
 pub fn swap(
     program_id: &Pubkey,
     amm_id: &Pubkey,
-    amm_authority: &Pubkey,
+    amm_nonce: u8,
     amm_open_orders: &Pubkey,
     amm_target_orders: &Pubkey,
     pool_coin_token_account: &Pubkey,
@@ -276,7 +1802,7 @@ pub fn swap(
     serum_event_queue: &Pubkey,
     serum_coin_vault_account: &Pubkey,
     serum_pc_vault_account: &Pubkey,
-    serum_vault_signer: &Pubkey,
+    serum_vault_signer_nonce: u64,
     uer_source_token_account: &Pubkey,
     uer_destination_token_account: &Pubkey,
     user_source_owner: &Pubkey,
@@ -284,14 +1810,16 @@ pub fn swap(
     amount_in: u64,
     minimum_amount_out: u64,
 ) -> Result<Instruction, ProgramError> {
-    let data = AmmInstruction::Swap(SwapInstruction{ amount_in, minimum_amount_out }).pack()?;
+    let data = RaydiumInstruction::Swap { amount_in, minimum_amount_out }.pack();
+    let amm_authority = raydium_authority(program_id, amm_nonce)?;
+    let vault_signer = serum_vault_signer(serum_program_id, serum_market, serum_vault_signer_nonce)?;
 
     let accounts = vec![
         // spl token
         AccountMeta::new_readonly(spl_token::id(), false),
         // amm
         AccountMeta::new(*amm_id, false),
-        AccountMeta::new_readonly(*amm_authority, false),
+        AccountMeta::new_readonly(amm_authority, false),
         AccountMeta::new(*amm_open_orders, false),
         AccountMeta::new(*amm_target_orders, false),
         AccountMeta::new(*pool_coin_token_account, false),
@@ -304,7 +1832,7 @@ pub fn swap(
         AccountMeta::new(*serum_event_queue, false),
         AccountMeta::new(*serum_coin_vault_account, false),
         AccountMeta::new(*serum_pc_vault_account, false),
-        AccountMeta::new_readonly(*serum_vault_signer, false),
+        AccountMeta::new_readonly(vault_signer, false),
         // user
         AccountMeta::new(*uer_source_token_account, false),
         AccountMeta::new(*uer_destination_token_account, false),
@@ -318,13 +1846,1429 @@ pub fn swap(
     })
 }
 
-/// Unpacks a reference from a bytes buffer.
-/// TODO actually pack / unpack instead of relying on normal memory layout.
-pub fn unpack<T>(input: &[u8]) -> Result<&T, ProgramError> {
+/// Creates a 'stable_deposit' instruction.
+pub fn stable_deposit(
+    program_id: &Pubkey,
+    amm_id: &Pubkey,
+    amm_nonce: u8,
+    amm_open_orders: &Pubkey,
+    lp_mint_address: &Pubkey,
+    pool_coin_token_account: &Pubkey,
+    pool_pc_token_account: &Pubkey,
+    model_data_account: &Pubkey,
+    serum_market: &Pubkey,
+    user_coin_token_account: &Pubkey,
+    user_pc_token_account: &Pubkey,
+    user_lp_token_account: &Pubkey,
+    user_owner: &Pubkey,
+
+    max_coin_amount: u64,
+    max_pc_amount: u64,
+    base_side: u64,
+) -> Result<Instruction, ProgramError> {
+    let data = RaydiumInstruction::Deposit { max_coin_amount, max_pc_amount, base_side }.pack();
+    let amm_authority = raydium_authority(program_id, amm_nonce)?;
+
+    let accounts = vec![
+        // spl token
+        AccountMeta::new_readonly(spl_token::id(), false),
+        // amm
+        AccountMeta::new(*amm_id, false),
+        AccountMeta::new_readonly(amm_authority, false),
+        AccountMeta::new_readonly(*amm_open_orders, false),
+        AccountMeta::new(*lp_mint_address, false),
+        AccountMeta::new(*pool_coin_token_account, false),
+        AccountMeta::new(*pool_pc_token_account, false),
+        AccountMeta::new_readonly(*model_data_account, false),
+        // serum
+        AccountMeta::new_readonly(*serum_market, false),
+        // user
+        AccountMeta::new(*user_coin_token_account, false),
+        AccountMeta::new(*user_pc_token_account, false),
+        AccountMeta::new(*user_lp_token_account, false),
+        AccountMeta::new_readonly(*user_owner, true),
+    ];
+
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data,
+    })
+}
+
+/// Creates a 'stable_withdraw' instruction.
+pub fn stable_withdraw(
+    program_id: &Pubkey,
+    amm_id: &Pubkey,
+    amm_nonce: u8,
+    amm_open_orders: &Pubkey,
+    lp_mint_address: &Pubkey,
+    pool_coin_token_account: &Pubkey,
+    pool_pc_token_account: &Pubkey,
+    model_data_account: &Pubkey,
+    serum_program_id: &Pubkey,
+    serum_market: &Pubkey,
+    serum_coin_vault_account: &Pubkey,
+    serum_pc_vault_account: &Pubkey,
+    serum_vault_signer_nonce: u64,
+    user_lp_token_account: &Pubkey,
+    user_coin_token_account: &Pubkey,
+    user_pc_token_account: &Pubkey,
+    user_owner: &Pubkey,
+    serum_event_queue: &Pubkey,
+    serum_bids: &Pubkey,
+    serum_asks: &Pubkey,
+
+    amount: u64,
+) -> Result<Instruction, ProgramError> {
+    let data = RaydiumInstruction::Withdraw { amount }.pack();
+    let amm_authority = raydium_authority(program_id, amm_nonce)?;
+    let vault_signer = serum_vault_signer(serum_program_id, serum_market, serum_vault_signer_nonce)?;
+
+    let accounts = vec![
+        // spl token
+        AccountMeta::new_readonly(spl_token::id(), false),
+        // amm
+        AccountMeta::new(*amm_id, false),
+        AccountMeta::new_readonly(amm_authority, false),
+        AccountMeta::new(*amm_open_orders, false),
+        AccountMeta::new(*lp_mint_address, false),
+        AccountMeta::new(*pool_coin_token_account, false),
+        AccountMeta::new(*pool_pc_token_account, false),
+        AccountMeta::new_readonly(*model_data_account, false),
+        // serum
+        AccountMeta::new_readonly(*serum_program_id, false),
+        AccountMeta::new(*serum_market, false),
+        AccountMeta::new(*serum_coin_vault_account, false),
+        AccountMeta::new(*serum_pc_vault_account, false),
+        AccountMeta::new_readonly(vault_signer, false),
+        // user
+        AccountMeta::new(*user_lp_token_account, false),
+        AccountMeta::new(*user_coin_token_account, false),
+        AccountMeta::new(*user_pc_token_account, false),
+        AccountMeta::new_readonly(*user_owner, true),
+        // serum orderbook
+        AccountMeta::new(*serum_event_queue, false),
+        AccountMeta::new(*serum_bids, false),
+        AccountMeta::new(*serum_asks, false),
+    ];
+
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data,
+    })
+}
+
+/// Creates a 'stable_swap' instruction.
+pub fn stable_swap(
+    program_id: &Pubkey,
+    amm_id: &Pubkey,
+    amm_nonce: u8,
+    amm_open_orders: &Pubkey,
+    pool_coin_token_account: &Pubkey,
+    pool_pc_token_account: &Pubkey,
+    model_data_account: &Pubkey,
+    serum_program_id: &Pubkey,
+    serum_market: &Pubkey,
+    serum_bids: &Pubkey,
+    serum_asks: &Pubkey,
+    serum_event_queue: &Pubkey,
+    serum_coin_vault_account: &Pubkey,
+    serum_pc_vault_account: &Pubkey,
+    serum_vault_signer_nonce: u64,
+    user_source_token_account: &Pubkey,
+    user_destination_token_account: &Pubkey,
+    user_source_owner: &Pubkey,
+
+    amount_in: u64,
+    minimum_amount_out: u64,
+) -> Result<Instruction, ProgramError> {
+    let data = RaydiumInstruction::Swap { amount_in, minimum_amount_out }.pack();
+    let amm_authority = raydium_authority(program_id, amm_nonce)?;
+    let vault_signer = serum_vault_signer(serum_program_id, serum_market, serum_vault_signer_nonce)?;
+
+    let accounts = vec![
+        // spl token
+        AccountMeta::new_readonly(spl_token::id(), false),
+        // amm
+        AccountMeta::new(*amm_id, false),
+        AccountMeta::new_readonly(amm_authority, false),
+        AccountMeta::new(*amm_open_orders, false),
+        AccountMeta::new(*pool_coin_token_account, false),
+        AccountMeta::new(*pool_pc_token_account, false),
+        AccountMeta::new_readonly(*model_data_account, false),
+        // serum
+        AccountMeta::new_readonly(*serum_program_id, false),
+        AccountMeta::new(*serum_market, false),
+        AccountMeta::new(*serum_bids, false),
+        AccountMeta::new(*serum_asks, false),
+        AccountMeta::new(*serum_event_queue, false),
+        AccountMeta::new(*serum_coin_vault_account, false),
+        AccountMeta::new(*serum_pc_vault_account, false),
+        AccountMeta::new_readonly(vault_signer, false),
+        // user
+        AccountMeta::new(*user_source_token_account, false),
+        AccountMeta::new(*user_destination_token_account, false),
+        AccountMeta::new_readonly(*user_source_owner, true),
+    ];
+
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data,
+    })
+}
+
+/// Anchor discriminator of the Raydium CLMM `swap` instruction
+pub const CLMM_SWAP_DISCRIMINATOR: [u8; 8] = [248, 198, 158, 145, 225, 117, 135, 200];
+
+/// Creates a Raydium CLMM 'swap' instruction of an exact `amount_in`.
+/// `tick_arrays` are the arrays the swap may cross, the one of the current tick first.
+pub fn clmm_swap(
+    program_id: &Pubkey,
+    amm_config: &Pubkey,
+    pool_state: &Pubkey,
+    input_vault: &Pubkey,
+    output_vault: &Pubkey,
+    observation_state: &Pubkey,
+    tick_arrays: &[Pubkey],
+    user_source_token_account: &Pubkey,
+    user_destination_token_account: &Pubkey,
+    user_source_owner: &Pubkey,
+
+    amount_in: u64,
+    minimum_amount_out: u64,
+) -> Result<Instruction, ProgramError> {
+    if tick_arrays.is_empty() {
+        return Err(AmmError::InvalidInput.into());
+    }
+    let mut data = Vec::with_capacity(8 + 8 + 8 + 16 + 1);
+    data.extend_from_slice(&CLMM_SWAP_DISCRIMINATOR);
+    data.extend_from_slice(&amount_in.to_le_bytes());
+    // other_amount_threshold, the minimum output of an exact input swap
+    data.extend_from_slice(&minimum_amount_out.to_le_bytes());
+    // no sqrt_price_limit_x64, the threshold bounds the price
+    data.extend_from_slice(&0u128.to_le_bytes());
+    // is_base_input
+    data.push(1);
+
+    let mut accounts = vec![
+        // user
+        AccountMeta::new_readonly(*user_source_owner, true),
+        // pool
+        AccountMeta::new_readonly(*amm_config, false),
+        AccountMeta::new(*pool_state, false),
+        // user
+        AccountMeta::new(*user_source_token_account, false),
+        AccountMeta::new(*user_destination_token_account, false),
+        // pool
+        AccountMeta::new(*input_vault, false),
+        AccountMeta::new(*output_vault, false),
+        AccountMeta::new(*observation_state, false),
+        // spl token
+        AccountMeta::new_readonly(spl_token::id(), false),
+    ];
+    accounts.extend(tick_arrays.iter().map(|tick_array| AccountMeta::new(*tick_array, false)));
+
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data,
+    })
+}
+
+/// Tick arrays a Raydium CLMM swap of `pool` walks through, `count` of them starting with
+/// the array of the current tick
+pub fn clmm_swap_tick_arrays(
+    program_id: &Pubkey,
+    pool: &Pubkey,
+    pool_state: &ClmmPoolState,
+    zero_for_one: bool,
+    count: u8,
+) -> Vec<Pubkey> {
+    pool_state
+        .swap_tick_array_start_indexes(zero_for_one, count)
+        .into_iter()
+        .map(|start_tick_index| ClmmTickArrayState::find_address(program_id, pool, start_tick_index).0)
+        .collect()
+}
+
+/// Creates an 'initialize_config' instruction.
+pub fn initialize_config(
+    program_id: &Pubkey,
+    admin: &Pubkey,
+    guardian: &Pubkey,
+) -> Result<Instruction, ProgramError> {
+    let data = ProviderInstruction::InitializeConfig { guardian: *guardian }.pack()?;
+    let (config, _) = ProviderConfig::find_address(program_id);
+
+    let accounts = vec![
+        AccountMeta::new(config, false),
+        AccountMeta::new(*admin, true),
+        AccountMeta::new_readonly(system_program::id(), false),
+    ];
+
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data,
+    })
+}
+
+/// Creates a 'set_fee_receivers' instruction.
+pub fn set_fee_receivers(
+    program_id: &Pubkey,
+    admin: &Pubkey,
+    mint: &Pubkey,
+    fee_receivers: &[FeeReceiver],
+    accrue_to_vault: bool,
+) -> Result<Instruction, ProgramError> {
+    if fee_receivers.len() > MAX_FEE_RECEIVERS {
+        return Err(AmmError::InvalidInput.into());
+    }
+    let mut receivers = [FeeReceiver::default(); MAX_FEE_RECEIVERS];
+    receivers[..fee_receivers.len()].copy_from_slice(fee_receivers);
+    let data = ProviderInstruction::SetFeeReceivers(SetFeeReceiversInstruction{
+        receiver_count: fee_receivers.len() as u8,
+        receivers,
+        accrue_to_vault,
+    }).pack()?;
+    let (config, _) = ProviderConfig::find_address(program_id);
+    let (fee_config, _) = FeeConfig::find_address(program_id, mint);
+
+    let accounts = vec![
+        AccountMeta::new_readonly(config, false),
+        AccountMeta::new(*admin, true),
+        AccountMeta::new(fee_config, false),
+        AccountMeta::new_readonly(*mint, false),
+        AccountMeta::new_readonly(system_program::id(), false),
+    ];
+
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data,
+    })
+}
+
+/// Creates an 'initialize_fee_vault' instruction.
+pub fn initialize_fee_vault(
+    program_id: &Pubkey,
+    admin: &Pubkey,
+    mint: &Pubkey,
+) -> Result<Instruction, ProgramError> {
+    let data = ProviderInstruction::InitializeFeeVault.pack()?;
+    let (config, _) = ProviderConfig::find_address(program_id);
+    let (fee_config, _) = FeeConfig::find_address(program_id, mint);
+    let (fee_vault, _) = FeeConfig::find_vault_address(program_id, mint);
+
+    let accounts = vec![
+        AccountMeta::new_readonly(config, false),
+        AccountMeta::new(*admin, true),
+        AccountMeta::new(fee_config, false),
+        AccountMeta::new(fee_vault, false),
+        AccountMeta::new_readonly(*mint, false),
+        AccountMeta::new_readonly(spl_token::id(), false),
+        AccountMeta::new_readonly(system_program::id(), false),
+        AccountMeta::new_readonly(sysvar::rent::id(), false),
+    ];
+
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data,
+    })
+}
+
+/// Creates a 'sweep_fees' instruction.
+pub fn sweep_fees(
+    program_id: &Pubkey,
+    mint: &Pubkey,
+    fee_receivers: &[Pubkey],
+) -> Result<Instruction, ProgramError> {
+    let data = ProviderInstruction::SweepFees.pack()?;
+    let (config, _) = ProviderConfig::find_address(program_id);
+    let (fee_config, _) = FeeConfig::find_address(program_id, mint);
+    let (fee_vault, _) = FeeConfig::find_vault_address(program_id, mint);
+
+    let mut accounts = vec![
+        AccountMeta::new_readonly(config, false),
+        AccountMeta::new_readonly(fee_config, false),
+        AccountMeta::new(fee_vault, false),
+        AccountMeta::new_readonly(spl_token::id(), false),
+    ];
+    accounts.extend(fee_receivers.iter().map(|receiver| AccountMeta::new(*receiver, false)));
+
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data,
+    })
+}
+
+/// Creates a 'set_flat_fee' instruction.
+pub fn set_flat_fee(
+    program_id: &Pubkey,
+    admin: &Pubkey,
+    mint: &Pubkey,
+    fee_model: FeeModel,
+    flat_fee_lamports: u64,
+    treasury: &Pubkey,
+) -> Result<Instruction, ProgramError> {
+    let data = ProviderInstruction::SetFlatFee(SetFlatFeeInstruction{
+        fee_model,
+        flat_fee_lamports,
+        treasury: *treasury,
+    }).pack()?;
+    let (config, _) = ProviderConfig::find_address(program_id);
+    let (fee_config, _) = FeeConfig::find_address(program_id, mint);
+
+    let accounts = vec![
+        AccountMeta::new_readonly(config, false),
+        AccountMeta::new_readonly(*admin, true),
+        AccountMeta::new(fee_config, false),
+        AccountMeta::new_readonly(*mint, false),
+    ];
+
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data,
+    })
+}
+
+/// Creates a 'create_pool' instruction. The Raydium authority is derived from `nonce`,
+/// `listing_treasury` is only passed with a listing fee.
+pub fn create_pool(
+    program_id: &Pubkey,
+    raydium_program_id: &Pubkey,
+    amm_id: &Pubkey,
+    amm_open_orders: &Pubkey,
+    lp_mint_address: &Pubkey,
+    coin_mint_address: &Pubkey,
+    pc_mint_address: &Pubkey,
+    pool_coin_token_account: &Pubkey,
+    pool_pc_token_account: &Pubkey,
+    pool_target_orders_account: &Pubkey,
+    amm_config: &Pubkey,
+    create_fee_destination: &Pubkey,
+    serum_program_id: &Pubkey,
+    serum_market: &Pubkey,
+    creator: &Pubkey,
+    creator_coin_token_account: &Pubkey,
+    creator_pc_token_account: &Pubkey,
+    listing_treasury: Option<&Pubkey>,
+
+    nonce: u8,
+    init_coin_amount: u64,
+    init_pc_amount: u64,
+    open_time: u64,
+) -> Result<Instruction, ProgramError> {
+    let data = ProviderInstruction::CreatePool(CreatePoolInstruction{ nonce, init_coin_amount, init_pc_amount, open_time }).pack()?;
+    let (config, _) = ProviderConfig::find_address(program_id);
+    let amm_authority = raydium_authority(raydium_program_id, nonce)?;
+    let creator_lp_token_account = get_associated_token_address(creator, lp_mint_address);
+    let (coin_fee_config, _) = FeeConfig::find_address(program_id, coin_mint_address);
+    let (pc_fee_config, _) = FeeConfig::find_address(program_id, pc_mint_address);
+    let (pool_registry, _) = PoolRegistry::find_address(program_id, amm_id);
+
+    let mut accounts = vec![
+        AccountMeta::new_readonly(config, false),
+        AccountMeta::new_readonly(*raydium_program_id, false),
+        AccountMeta::new_readonly(spl_token::id(), false),
+        AccountMeta::new_readonly(spl_associated_token_account::id(), false),
+        AccountMeta::new_readonly(system_program::id(), false),
+        AccountMeta::new_readonly(sysvar::rent::id(), false),
+        // amm
+        AccountMeta::new(*amm_id, false),
+        AccountMeta::new_readonly(amm_authority, false),
+        AccountMeta::new(*amm_open_orders, false),
+        AccountMeta::new(*lp_mint_address, false),
+        AccountMeta::new_readonly(*coin_mint_address, false),
+        AccountMeta::new_readonly(*pc_mint_address, false),
+        AccountMeta::new(*pool_coin_token_account, false),
+        AccountMeta::new(*pool_pc_token_account, false),
+        AccountMeta::new(*pool_target_orders_account, false),
+        AccountMeta::new_readonly(*amm_config, false),
+        AccountMeta::new(*create_fee_destination, false),
+        // serum
+        AccountMeta::new_readonly(*serum_program_id, false),
+        AccountMeta::new_readonly(*serum_market, false),
+        // creator
+        AccountMeta::new(*creator, true),
+        AccountMeta::new(*creator_coin_token_account, false),
+        AccountMeta::new(*creator_pc_token_account, false),
+        AccountMeta::new(creator_lp_token_account, false),
+        AccountMeta::new_readonly(coin_fee_config, false),
+        AccountMeta::new_readonly(pc_fee_config, false),
+        AccountMeta::new(pool_registry, false),
+    ];
+    if let Some(listing_treasury) = listing_treasury {
+        accounts.push(AccountMeta::new(*listing_treasury, false));
+    }
+
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data,
+    })
+}
+
+/// Creates a 'set_listing_fee' instruction.
+pub fn set_listing_fee(
+    program_id: &Pubkey,
+    admin: &Pubkey,
+    listing_fee_lamports: u64,
+    treasury: &Pubkey,
+) -> Result<Instruction, ProgramError> {
+    let data = ProviderInstruction::SetListingFee { listing_fee_lamports, treasury: *treasury }.pack()?;
+    let (config, _) = ProviderConfig::find_address(program_id);
+
+    let accounts = vec![
+        AccountMeta::new(config, false),
+        AccountMeta::new_readonly(*admin, true),
+    ];
+
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data,
+    })
+}
+
+/// Creates a 'set_farm_program' instruction.
+pub fn set_farm_program(
+    program_id: &Pubkey,
+    admin: &Pubkey,
+    farm_program_id: &Pubkey,
+) -> Result<Instruction, ProgramError> {
+    let data = ProviderInstruction::SetFarmProgram { farm_program_id: *farm_program_id }.pack()?;
+    let (config, _) = ProviderConfig::find_address(program_id);
+
+    let accounts = vec![
+        AccountMeta::new(config, false),
+        AccountMeta::new_readonly(*admin, true),
+    ];
+
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data,
+    })
+}
+
+/// Creates a 'set_guardian' instruction.
+pub fn set_guardian(
+    program_id: &Pubkey,
+    admin: &Pubkey,
+    guardian: &Pubkey,
+) -> Result<Instruction, ProgramError> {
+    let data = ProviderInstruction::SetGuardian { guardian: *guardian }.pack()?;
+    let (config, _) = ProviderConfig::find_address(program_id);
+
+    let accounts = vec![
+        AccountMeta::new(config, false),
+        AccountMeta::new_readonly(*admin, true),
+    ];
+
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data,
+    })
+}
+
+/// Creates a 'set_pause' instruction.
+pub fn set_pause(
+    program_id: &Pubkey,
+    guardian: &Pubkey,
+    pause: SetPauseInstruction,
+) -> Result<Instruction, ProgramError> {
+    let data = ProviderInstruction::SetPause(pause).pack()?;
+    let (config, _) = ProviderConfig::find_address(program_id);
+
+    let accounts = vec![
+        AccountMeta::new(config, false),
+        AccountMeta::new_readonly(*guardian, true),
+    ];
+
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data,
+    })
+}
+
+/// Creates a 'set_pool_registry' instruction.
+pub fn set_pool_registry(
+    program_id: &Pubkey,
+    admin: &Pubkey,
+    amm_id: &Pubkey,
+    coin_mint: &Pubkey,
+    pc_mint: &Pubkey,
+    enabled: bool,
+    kind: PoolKind,
+) -> Result<Instruction, ProgramError> {
+    let data = ProviderInstruction::SetPoolRegistry(SetPoolRegistryInstruction{
+        coin_mint: *coin_mint,
+        pc_mint: *pc_mint,
+        enabled,
+        kind,
+    }).pack()?;
+    let (config, _) = ProviderConfig::find_address(program_id);
+    let (pool_registry, _) = PoolRegistry::find_address(program_id, amm_id);
+
+    let accounts = vec![
+        AccountMeta::new_readonly(config, false),
+        AccountMeta::new(*admin, true),
+        AccountMeta::new(pool_registry, false),
+        AccountMeta::new_readonly(*amm_id, false),
+        AccountMeta::new_readonly(system_program::id(), false),
+    ];
+
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data,
+    })
+}
+
+/// Creates a 'set_reward_fee' instruction.
+pub fn set_reward_fee(
+    program_id: &Pubkey,
+    admin: &Pubkey,
+    mint: &Pubkey,
+    reward_fee_bps: u16,
+) -> Result<Instruction, ProgramError> {
+    let data = ProviderInstruction::SetRewardFee { reward_fee_bps }.pack()?;
+    let (config, _) = ProviderConfig::find_address(program_id);
+    let (fee_config, _) = FeeConfig::find_address(program_id, mint);
+
+    let accounts = vec![
+        AccountMeta::new_readonly(config, false),
+        AccountMeta::new_readonly(*admin, true),
+        AccountMeta::new(fee_config, false),
+        AccountMeta::new_readonly(*mint, false),
+    ];
+
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data,
+    })
+}
+
+/// Creates a 'initialize_lp_vault' instruction.
+pub fn initialize_lp_vault(
+    program_id: &Pubkey,
+    admin: &Pubkey,
+    amm_id: &Pubkey,
+    lp_mint: &Pubkey,
+    coin_mint: &Pubkey,
+    pc_mint: &Pubkey,
+    farm_id: &Pubkey,
+    farm_user_info: &Pubkey,
+    treasury: &Pubkey,
+    performance_fee_bps: u16,
+    max_slippage_bps: u16,
+) -> Result<Instruction, ProgramError> {
+    let data = ProviderInstruction::InitializeLpVault { performance_fee_bps, max_slippage_bps }.pack()?;
+    let (config, _) = ProviderConfig::find_address(program_id);
+    let (pool_registry, _) = PoolRegistry::find_address(program_id, amm_id);
+    let (lp_vault, _) = LpVault::find_address(program_id, amm_id);
+    let (share_mint, _) = LpVault::find_share_mint_address(program_id, &lp_vault);
+
+    let accounts = vec![
+        AccountMeta::new_readonly(config, false),
+        AccountMeta::new(*admin, true),
+        AccountMeta::new_readonly(pool_registry, false),
+        AccountMeta::new_readonly(*amm_id, false),
+        AccountMeta::new(lp_vault, false),
+        AccountMeta::new(share_mint, false),
+        AccountMeta::new(get_associated_token_address(&lp_vault, lp_mint), false),
+        AccountMeta::new(get_associated_token_address(&lp_vault, coin_mint), false),
+        AccountMeta::new(get_associated_token_address(&lp_vault, pc_mint), false),
+        AccountMeta::new_readonly(*lp_mint, false),
+        AccountMeta::new_readonly(*coin_mint, false),
+        AccountMeta::new_readonly(*pc_mint, false),
+        AccountMeta::new_readonly(*farm_id, false),
+        AccountMeta::new_readonly(*farm_user_info, false),
+        AccountMeta::new_readonly(*treasury, false),
+        AccountMeta::new_readonly(system_program::id(), false),
+        AccountMeta::new_readonly(spl_token::id(), false),
+        AccountMeta::new_readonly(spl_associated_token_account::id(), false),
+        AccountMeta::new_readonly(sysvar::rent::id(), false),
+    ];
+
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data,
+    })
+}
+
+/// Creates a 'deposit_lp_vault' instruction. Once the vault holds shares, deposits fail
+/// with `VaultNotCompounded` unless a 'compound' instruction runs earlier in the same slot,
+/// put it first in the transaction.
+pub fn deposit_lp_vault(
+    program_id: &Pubkey,
+    amm_id: &Pubkey,
+    lp_mint: &Pubkey,
+    reward_mint: &Pubkey,
+    user_owner: &Pubkey,
+    farm_program_id: &Pubkey,
+    farm_id: &Pubkey,
+    farm_authority: &Pubkey,
+    farm_user_info: &Pubkey,
+    farm_lp_vault: &Pubkey,
+    farm_reward_vault: &Pubkey,
+    amount: u64,
+) -> Result<Instruction, ProgramError> {
+    let data = ProviderInstruction::DepositLpVault(LpVaultInstruction { amount }).pack()?;
+    lp_vault_instruction(
+        program_id,
+        data,
+        amm_id,
+        lp_mint,
+        reward_mint,
+        user_owner,
+        farm_program_id,
+        farm_id,
+        farm_authority,
+        farm_user_info,
+        farm_lp_vault,
+        farm_reward_vault,
+    )
+}
+
+/// Creates a 'withdraw_lp_vault' instruction.
+pub fn withdraw_lp_vault(
+    program_id: &Pubkey,
+    amm_id: &Pubkey,
+    lp_mint: &Pubkey,
+    reward_mint: &Pubkey,
+    user_owner: &Pubkey,
+    farm_program_id: &Pubkey,
+    farm_id: &Pubkey,
+    farm_authority: &Pubkey,
+    farm_user_info: &Pubkey,
+    farm_lp_vault: &Pubkey,
+    farm_reward_vault: &Pubkey,
+    amount: u64,
+) -> Result<Instruction, ProgramError> {
+    let data = ProviderInstruction::WithdrawLpVault(LpVaultInstruction { amount }).pack()?;
+    lp_vault_instruction(
+        program_id,
+        data,
+        amm_id,
+        lp_mint,
+        reward_mint,
+        user_owner,
+        farm_program_id,
+        farm_id,
+        farm_authority,
+        farm_user_info,
+        farm_lp_vault,
+        farm_reward_vault,
+    )
+}
+
+fn lp_vault_instruction(
+    program_id: &Pubkey,
+    data: Vec<u8>,
+    amm_id: &Pubkey,
+    lp_mint: &Pubkey,
+    reward_mint: &Pubkey,
+    user_owner: &Pubkey,
+    farm_program_id: &Pubkey,
+    farm_id: &Pubkey,
+    farm_authority: &Pubkey,
+    farm_user_info: &Pubkey,
+    farm_lp_vault: &Pubkey,
+    farm_reward_vault: &Pubkey,
+) -> Result<Instruction, ProgramError> {
+    let (config, _) = ProviderConfig::find_address(program_id);
+    let (lp_vault, _) = LpVault::find_address(program_id, amm_id);
+    let (share_mint, _) = LpVault::find_share_mint_address(program_id, &lp_vault);
+
+    let accounts = vec![
+        AccountMeta::new_readonly(config, false),
+        AccountMeta::new(lp_vault, false),
+        AccountMeta::new(share_mint, false),
+        AccountMeta::new(get_associated_token_address(&lp_vault, lp_mint), false),
+        AccountMeta::new(get_associated_token_address(user_owner, lp_mint), false),
+        AccountMeta::new(get_associated_token_address(user_owner, &share_mint), false),
+        AccountMeta::new_readonly(*user_owner, true),
+        AccountMeta::new_readonly(*farm_program_id, false),
+        AccountMeta::new(*farm_id, false),
+        AccountMeta::new_readonly(*farm_authority, false),
+        AccountMeta::new(*farm_user_info, false),
+        AccountMeta::new(*farm_lp_vault, false),
+        AccountMeta::new(*farm_reward_vault, false),
+        AccountMeta::new_readonly(sysvar::clock::id(), false),
+        AccountMeta::new(get_associated_token_address(&lp_vault, reward_mint), false),
+        AccountMeta::new_readonly(spl_token::id(), false),
+    ];
+
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data,
+    })
+}
+
+/// Raydium farm instruction tags
+const FARM_DEPOSIT: u8 = 1;
+const FARM_WITHDRAW: u8 = 2;
+
+/// Creates a Raydium farm 'deposit' instruction. A zero amount only harvests the rewards.
+pub fn farm_deposit(
+    program_id: &Pubkey,
+    farm_id: &Pubkey,
+    farm_authority: &Pubkey,
+    user_info: &Pubkey,
+    user_owner: &Pubkey,
+    user_lp_token_account: &Pubkey,
+    farm_lp_vault: &Pubkey,
+    user_reward_token_account: &Pubkey,
+    farm_reward_vault: &Pubkey,
+    amount: u64,
+) -> Result<Instruction, ProgramError> {
+    farm_instruction(
+        program_id,
+        FARM_DEPOSIT,
+        farm_id,
+        farm_authority,
+        user_info,
+        user_owner,
+        user_lp_token_account,
+        farm_lp_vault,
+        user_reward_token_account,
+        farm_reward_vault,
+        amount,
+    )
+}
+
+/// Creates a Raydium farm 'withdraw' instruction.
+pub fn farm_withdraw(
+    program_id: &Pubkey,
+    farm_id: &Pubkey,
+    farm_authority: &Pubkey,
+    user_info: &Pubkey,
+    user_owner: &Pubkey,
+    user_lp_token_account: &Pubkey,
+    farm_lp_vault: &Pubkey,
+    user_reward_token_account: &Pubkey,
+    farm_reward_vault: &Pubkey,
+    amount: u64,
+) -> Result<Instruction, ProgramError> {
+    farm_instruction(
+        program_id,
+        FARM_WITHDRAW,
+        farm_id,
+        farm_authority,
+        user_info,
+        user_owner,
+        user_lp_token_account,
+        farm_lp_vault,
+        user_reward_token_account,
+        farm_reward_vault,
+        amount,
+    )
+}
+
+fn farm_instruction(
+    program_id: &Pubkey,
+    tag: u8,
+    farm_id: &Pubkey,
+    farm_authority: &Pubkey,
+    user_info: &Pubkey,
+    user_owner: &Pubkey,
+    user_lp_token_account: &Pubkey,
+    farm_lp_vault: &Pubkey,
+    user_reward_token_account: &Pubkey,
+    farm_reward_vault: &Pubkey,
+    amount: u64,
+) -> Result<Instruction, ProgramError> {
+    let mut data = Vec::with_capacity(9);
+    data.push(tag);
+    data.extend_from_slice(&amount.to_le_bytes());
+
+    let accounts = vec![
+        AccountMeta::new(*farm_id, false),
+        AccountMeta::new_readonly(*farm_authority, false),
+        AccountMeta::new(*user_info, false),
+        AccountMeta::new_readonly(*user_owner, true),
+        AccountMeta::new(*user_lp_token_account, false),
+        AccountMeta::new(*farm_lp_vault, false),
+        AccountMeta::new(*user_reward_token_account, false),
+        AccountMeta::new(*farm_reward_vault, false),
+        AccountMeta::new_readonly(sysvar::clock::id(), false),
+        AccountMeta::new_readonly(spl_token::id(), false),
+    ];
+
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data,
+    })
+}
+
+/// Creates a provider 'swap' instruction through a Raydium AMM v4 pool, reading its accounts
+/// from the raw `amm_data` and `market_data`. Swaps coin for pc if `source_mint` is the coin
+/// mint of the pool, pc for coin otherwise. The user token accounts are the associated token
+/// accounts of `user_owner`, `fee_config` is the fee config of the `fee_side` mint.
+pub fn provider_swap(
+    program_id: &Pubkey,
+    raydium_program_id: &Pubkey,
+    amm_id: &Pubkey,
+    amm_data: &[u8],
+    market_data: &[u8],
+    user_owner: &Pubkey,
+    source_mint: &Pubkey,
+    fee_config: &FeeConfig,
+    amount_in: u64,
+    minimum_amount_out: u64,
+    fee_side: FeeSide,
+    valid_until_slot: Option<u64>,
+) -> Result<Instruction, ProgramError> {
+    let amm = AmmInfo::unpack_amm(amm_data)?;
+    let market_state = MarketState::unpack(market_data)?;
+    if market_state.own_address != amm.market {
+        return Err(AmmError::InvalidInput.into());
+    }
+    let destination_mint = if *source_mint == amm.coin_mint {
+        amm.pc_mint
+    } else if *source_mint == amm.pc_mint {
+        amm.coin_mint
+    } else {
+        return Err(AmmError::InvalidMint.into());
+    };
+    let fee_mint = match fee_side {
+        FeeSide::Input => source_mint,
+        FeeSide::Output => &destination_mint,
+    };
+    if fee_config.mint != *fee_mint {
+        return Err(AmmError::InvalidMint.into());
+    }
+    let data = ProviderInstruction::Swap(SwapInstruction{
+        amount_in,
+        minimum_amount_out,
+        fee_side,
+        valid_until_slot,
+        adapter: AdapterKind::RaydiumV4,
+    }).pack()?;
+    let (config, _) = ProviderConfig::find_address(program_id);
+    let (pool_registry, _) = PoolRegistry::find_address(program_id, amm_id);
+    let (fee_config_address, _) = FeeConfig::find_address(program_id, fee_mint);
+    let amm_authority = raydium_authority(raydium_program_id, amm.nonce.try_into().map_err(|_| AmmError::InvalidAuthority)?)?;
+    let vault_signer = market_state.vault_signer(&amm.serum_dex, &amm.market)?;
+
+    let mut accounts = vec![
+        AccountMeta::new_readonly(config, false),
+        AccountMeta::new_readonly(pool_registry, false),
+        // amm
+        AccountMeta::new_readonly(*raydium_program_id, false),
+        AccountMeta::new_readonly(spl_token::id(), false),
+        AccountMeta::new(*amm_id, false),
+        AccountMeta::new_readonly(amm_authority, false),
+        AccountMeta::new(amm.open_orders, false),
+        AccountMeta::new(amm.target_orders, false),
+        AccountMeta::new(amm.token_coin, false),
+        AccountMeta::new(amm.token_pc, false),
+        // serum
+        AccountMeta::new_readonly(amm.serum_dex, false),
+        AccountMeta::new(amm.market, false),
+        AccountMeta::new(market_state.bids, false),
+        AccountMeta::new(market_state.asks, false),
+        AccountMeta::new(market_state.event_q, false),
+        AccountMeta::new(market_state.coin_vault, false),
+        AccountMeta::new(market_state.pc_vault, false),
+        AccountMeta::new_readonly(vault_signer, false),
+        // user
+        AccountMeta::new(get_associated_token_address(user_owner, source_mint), false),
+        AccountMeta::new(get_associated_token_address(user_owner, &destination_mint), false),
+        user_owner_meta(user_owner, fee_config.charges_flat_fee()),
+        // fees
+        AccountMeta::new_readonly(fee_config_address, false),
+    ];
+    accounts.extend(fee_config_accounts(fee_config));
+
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data,
+    })
+}
+
+/// Creates a provider 'deposit' instruction into a Raydium AMM v4 pool, reading its accounts
+/// from the raw `amm_data`. The user token accounts are the associated token accounts of
+/// `user_owner`, the fee configs those of the coin and pc mints of the pool.
+pub fn provider_deposit(
+    program_id: &Pubkey,
+    raydium_program_id: &Pubkey,
+    amm_id: &Pubkey,
+    amm_data: &[u8],
+    user_owner: &Pubkey,
+    coin_fee_config: &FeeConfig,
+    pc_fee_config: &FeeConfig,
+    max_coin_amount: u64,
+    max_pc_amount: u64,
+    base_side: u64,
+    valid_until_slot: Option<u64>,
+) -> Result<Instruction, ProgramError> {
+    let amm = AmmInfo::unpack_amm(amm_data)?;
+    if coin_fee_config.mint != amm.coin_mint || pc_fee_config.mint != amm.pc_mint {
+        return Err(AmmError::InvalidMint.into());
+    }
+    let data = ProviderInstruction::Deposit(DepositInstruction{
+        max_coin_amount,
+        max_pc_amount,
+        base_side,
+        valid_until_slot,
+        adapter: AdapterKind::RaydiumV4,
+    }).pack()?;
+    let (config, _) = ProviderConfig::find_address(program_id);
+    let (pool_registry, _) = PoolRegistry::find_address(program_id, amm_id);
+    let (coin_fee_config_address, _) = FeeConfig::find_address(program_id, &amm.coin_mint);
+    let (pc_fee_config_address, _) = FeeConfig::find_address(program_id, &amm.pc_mint);
+    let amm_authority = raydium_authority(raydium_program_id, amm.nonce.try_into().map_err(|_| AmmError::InvalidAuthority)?)?;
+
+    let mut accounts = vec![
+        AccountMeta::new_readonly(config, false),
+        AccountMeta::new_readonly(pool_registry, false),
+        // amm
+        AccountMeta::new_readonly(*raydium_program_id, false),
+        AccountMeta::new_readonly(spl_token::id(), false),
+        AccountMeta::new(*amm_id, false),
+        AccountMeta::new_readonly(amm_authority, false),
+        AccountMeta::new_readonly(amm.open_orders, false),
+        AccountMeta::new(amm.target_orders, false),
+        AccountMeta::new(amm.lp_mint, false),
+        AccountMeta::new(amm.token_coin, false),
+        AccountMeta::new(amm.token_pc, false),
+        // serum
+        AccountMeta::new_readonly(amm.market, false),
+        // user
+        AccountMeta::new(get_associated_token_address(user_owner, &amm.coin_mint), false),
+        AccountMeta::new(get_associated_token_address(user_owner, &amm.pc_mint), false),
+        AccountMeta::new(get_associated_token_address(user_owner, &amm.lp_mint), false),
+        user_owner_meta(user_owner, coin_fee_config.charges_flat_fee() || pc_fee_config.charges_flat_fee()),
+        // fees
+        AccountMeta::new_readonly(coin_fee_config_address, false),
+    ];
+    accounts.extend(fee_config_accounts(coin_fee_config));
+    accounts.push(AccountMeta::new_readonly(pc_fee_config_address, false));
+    accounts.extend(fee_config_accounts(pc_fee_config));
+
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data,
+    })
+}
+
+/// User owner signing a trade, writable if it pays a flat fee
+fn user_owner_meta(user_owner: &Pubkey, pays_flat_fee: bool) -> AccountMeta {
+    if pays_flat_fee {
+        AccountMeta::new(*user_owner, true)
+    } else {
+        AccountMeta::new_readonly(*user_owner, true)
+    }
+}
+
+/// Accounts following `fee_config`: its receivers or fee vault for the commission, then the
+/// treasury and System program for the flat fee
+fn fee_config_accounts(fee_config: &FeeConfig) -> Vec<AccountMeta> {
+    let mut accounts = Vec::new();
+    if fee_config.charges_commission() {
+        if fee_config.accrue_to_vault {
+            accounts.push(AccountMeta::new(fee_config.vault, false));
+        } else {
+            accounts.extend(fee_config.receivers().iter().map(|receiver| AccountMeta::new(receiver.token_account, false)));
+        }
+    }
+    if fee_config.charges_flat_fee() {
+        accounts.push(AccountMeta::new(fee_config.treasury, false));
+        accounts.push(AccountMeta::new_readonly(system_program::id(), false));
+    }
+    accounts
+}
+
+/// Creates a 'serum_swap' instruction. The market accounts are read from `market_state`,
+/// `fee_receivers` are the accounts following the fee config of the destination mint.
+pub fn serum_swap(
+    program_id: &Pubkey,
+    dex_program_id: &Pubkey,
+    market_state: &MarketState,
+    user_open_orders: &Pubkey,
+    user_source_token_account: &Pubkey,
+    user_destination_token_account: &Pubkey,
+    user_owner: &Pubkey,
+    destination_mint: &Pubkey,
+    fee_receivers: &[Pubkey],
+    amount_in: u64,
+    minimum_amount_out: u64,
+    limit_price: u64,
+    valid_until_slot: Option<u64>,
+) -> Result<Instruction, ProgramError> {
+    let data = ProviderInstruction::SerumSwap(SerumSwapInstruction{
+        amount_in,
+        minimum_amount_out,
+        limit_price,
+        valid_until_slot,
+    }).pack()?;
+    let market = market_state.own_address;
+    let (config, _) = ProviderConfig::find_address(program_id);
+    let (pool_registry, _) = PoolRegistry::find_address(program_id, &market);
+    let (fee_config, _) = FeeConfig::find_address(program_id, destination_mint);
+    let vault_signer = serum_vault_signer(dex_program_id, &market, market_state.vault_signer_nonce)?;
+
+    let mut accounts = vec![
+        AccountMeta::new_readonly(config, false),
+        AccountMeta::new_readonly(pool_registry, false),
+        // serum
+        AccountMeta::new_readonly(*dex_program_id, false),
+        AccountMeta::new(market, false),
+        AccountMeta::new(*user_open_orders, false),
+        AccountMeta::new(market_state.req_q, false),
+        AccountMeta::new(market_state.event_q, false),
+        AccountMeta::new(market_state.bids, false),
+        AccountMeta::new(market_state.asks, false),
+        AccountMeta::new(market_state.coin_vault, false),
+        AccountMeta::new(market_state.pc_vault, false),
+        AccountMeta::new_readonly(vault_signer, false),
+        // user
+        AccountMeta::new(*user_source_token_account, false),
+        AccountMeta::new(*user_destination_token_account, false),
+        AccountMeta::new(*user_owner, true),
+        AccountMeta::new_readonly(spl_token::id(), false),
+        AccountMeta::new_readonly(sysvar::rent::id(), false),
+        AccountMeta::new_readonly(fee_config, false),
+    ];
+    accounts.extend(fee_receivers.iter().map(|receiver| AccountMeta::new(*receiver, false)));
+
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data,
+    })
+}
+
+/// Vault signer of a serum market, PDA of `[market, nonce]` under the dex program
+pub fn serum_vault_signer(dex_program_id: &Pubkey, market: &Pubkey, nonce: u64) -> Result<Pubkey, ProgramError> {
+    Pubkey::create_program_address(&[market.as_ref(), &nonce.to_le_bytes()], dex_program_id)
+        .map_err(|_| AmmError::InvalidInput.into())
+}
+
+/// Serum dex instruction tags, after the version byte
+const SERUM_SETTLE_FUNDS: u32 = 5;
+const SERUM_NEW_ORDER_V3: u32 = 10;
+
+/// Side of a serum order
+#[repr(u32)]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SerumSide {
+    Bid = 0,
+    Ask = 1,
+}
+
+/// Serum `self_trade_behavior` decrementing the taker, and `order_type` immediate-or-cancel
+const SERUM_DECREMENT_TAKE: u32 = 0;
+const SERUM_IMMEDIATE_OR_CANCEL: u32 = 1;
+
+/// Creates a serum dex 'new_order_v3' immediate-or-cancel instruction. The quantities and
+/// the limit price must not be zero.
+pub fn serum_new_order_v3(
+    dex_program_id: &Pubkey,
+    market: &Pubkey,
+    open_orders: &Pubkey,
+    request_queue: &Pubkey,
+    event_queue: &Pubkey,
+    bids: &Pubkey,
+    asks: &Pubkey,
+    order_payer: &Pubkey,
+    open_orders_owner: &Pubkey,
+    coin_vault: &Pubkey,
+    pc_vault: &Pubkey,
+    side: SerumSide,
+    limit_price: u64,
+    max_coin_qty: u64,
+    max_native_pc_qty_including_fees: u64,
+) -> Result<Instruction, ProgramError> {
+    if limit_price == 0 || max_coin_qty == 0 || max_native_pc_qty_including_fees == 0 {
+        return Err(AmmError::InvalidInput.into());
+    }
+    let mut data = Vec::with_capacity(51);
+    data.push(0);
+    data.extend_from_slice(&SERUM_NEW_ORDER_V3.to_le_bytes());
+    data.extend_from_slice(&(side as u32).to_le_bytes());
+    data.extend_from_slice(&limit_price.to_le_bytes());
+    data.extend_from_slice(&max_coin_qty.to_le_bytes());
+    data.extend_from_slice(&max_native_pc_qty_including_fees.to_le_bytes());
+    data.extend_from_slice(&SERUM_DECREMENT_TAKE.to_le_bytes());
+    data.extend_from_slice(&SERUM_IMMEDIATE_OR_CANCEL.to_le_bytes());
+    // client_order_id
+    data.extend_from_slice(&0u64.to_le_bytes());
+    // limit on the orders matched
+    data.extend_from_slice(&u16::MAX.to_le_bytes());
+
+    let accounts = vec![
+        AccountMeta::new(*market, false),
+        AccountMeta::new(*open_orders, false),
+        AccountMeta::new(*request_queue, false),
+        AccountMeta::new(*event_queue, false),
+        AccountMeta::new(*bids, false),
+        AccountMeta::new(*asks, false),
+        AccountMeta::new(*order_payer, false),
+        AccountMeta::new_readonly(*open_orders_owner, true),
+        AccountMeta::new(*coin_vault, false),
+        AccountMeta::new(*pc_vault, false),
+        AccountMeta::new_readonly(spl_token::id(), false),
+        AccountMeta::new_readonly(sysvar::rent::id(), false),
+    ];
+
+    Ok(Instruction {
+        program_id: *dex_program_id,
+        accounts,
+        data,
+    })
+}
+
+/// Creates a serum dex 'settle_funds' instruction.
+pub fn serum_settle_funds(
+    dex_program_id: &Pubkey,
+    market: &Pubkey,
+    open_orders: &Pubkey,
+    open_orders_owner: &Pubkey,
+    coin_vault: &Pubkey,
+    pc_vault: &Pubkey,
+    coin_wallet: &Pubkey,
+    pc_wallet: &Pubkey,
+    vault_signer: &Pubkey,
+) -> Result<Instruction, ProgramError> {
+    let mut data = Vec::with_capacity(5);
+    data.push(0);
+    data.extend_from_slice(&SERUM_SETTLE_FUNDS.to_le_bytes());
+
+    let accounts = vec![
+        AccountMeta::new(*market, false),
+        AccountMeta::new(*open_orders, false),
+        AccountMeta::new_readonly(*open_orders_owner, true),
+        AccountMeta::new(*coin_vault, false),
+        AccountMeta::new(*pc_vault, false),
+        AccountMeta::new(*coin_wallet, false),
+        AccountMeta::new(*pc_wallet, false),
+        AccountMeta::new_readonly(*vault_signer, false),
+        AccountMeta::new_readonly(spl_token::id(), false),
+    ];
+
+    Ok(Instruction {
+        program_id: *dex_program_id,
+        accounts,
+        data,
+    })
+}
+
+/// Unpacks a reference from a bytes buffer.
+/// TODO actually pack / unpack instead of relying on normal memory layout.
+pub fn unpack<T>(input: &[u8]) -> Result<&T, ProgramError> {
     if input.len() < size_of::<u8>() + size_of::<T>() {
         return Err(ProgramError::InvalidAccountData);
     }
     #[allow(clippy::cast_ptr_alignment)]
     let val: &T = unsafe { &*(&input[1] as *const u8 as *const T) };
     Ok(val)
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_provider_swap_golden() {
+        let swap = ProviderInstruction::Swap(SwapInstruction{
+            amount_in: 1_000,
+            minimum_amount_out: 900,
+            fee_side: FeeSide::Input,
+            valid_until_slot: Some(42),
+            adapter: AdapterKind::RaydiumClmm { tick_array_count: 3 },
+        });
+        let expected = [
+            0,
+            0xe8, 0x03, 0, 0, 0, 0, 0, 0,
+            0x84, 0x03, 0, 0, 0, 0, 0, 0,
+            1,
+            1, 42, 0, 0, 0, 0, 0, 0, 0,
+            2, 3,
+        ];
+        assert_eq!(swap.pack().unwrap(), expected);
+        assert_eq!(ProviderInstruction::unpack(&expected).unwrap(), swap);
+    }
+
+    #[test]
+    fn test_provider_deposit_withdraw_golden() {
+        let deposit = ProviderInstruction::Deposit(DepositInstruction{
+            max_coin_amount: 5,
+            max_pc_amount: 0x0102,
+            base_side: 1,
+            valid_until_slot: None,
+            adapter: AdapterKind::RaydiumStable,
+        });
+        let expected = [
+            1,
+            5, 0, 0, 0, 0, 0, 0, 0,
+            0x02, 0x01, 0, 0, 0, 0, 0, 0,
+            1, 0, 0, 0, 0, 0, 0, 0,
+            0,
+            1,
+        ];
+        assert_eq!(deposit.pack().unwrap(), expected);
+        assert_eq!(ProviderInstruction::unpack(&expected).unwrap(), deposit);
+
+        let withdraw = ProviderInstruction::Withdraw(WithdrawInstruction{
+            amount: 7,
+            valid_until_slot: Some(1),
+            adapter: AdapterKind::RaydiumV4,
+        });
+        let expected = [7, 7, 0, 0, 0, 0, 0, 0, 0, 1, 1, 0, 0, 0, 0, 0, 0, 0, 0];
+        assert_eq!(withdraw.pack().unwrap(), expected);
+        assert_eq!(ProviderInstruction::unpack(&expected).unwrap(), withdraw);
+    }
+
+    #[test]
+    fn test_provider_legacy_swap() {
+        // swaps packed before the deadline and adapter existed end after the fee side
+        let input = [0, 10, 0, 0, 0, 0, 0, 0, 0, 9, 0, 0, 0, 0, 0, 0, 0, 0];
+        let expected = ProviderInstruction::Swap(SwapInstruction{
+            amount_in: 10,
+            minimum_amount_out: 9,
+            ..Default::default()
+        });
+        assert_eq!(ProviderInstruction::unpack(&input).unwrap(), expected);
+    }
+
+    #[test]
+    fn test_provider_round_trip() {
+        let key = Pubkey::new_from_array([7; 32]);
+        let mut receivers = [FeeReceiver::default(); MAX_FEE_RECEIVERS];
+        receivers[0] = FeeReceiver { token_account: key, weight_bps: 10_000 };
+        let swap = SwapInstruction{ amount_in: 3, minimum_amount_out: 2, valid_until_slot: Some(9), ..Default::default() };
+        let deposit = DepositInstruction{ max_coin_amount: 4, max_pc_amount: 5, base_side: 0, valid_until_slot: Some(9), ..Default::default() };
+        let instructions = vec![
+            ProviderInstruction::Swap(swap),
+            ProviderInstruction::Deposit(deposit),
+            ProviderInstruction::InitializeConfig { guardian: key },
+            ProviderInstruction::SetFeeReceivers(SetFeeReceiversInstruction{ receiver_count: 1, receivers, accrue_to_vault: true }),
+            ProviderInstruction::InitializeFeeVault,
+            ProviderInstruction::SweepFees,
+            ProviderInstruction::SetFlatFee(SetFlatFeeInstruction{ fee_model: FeeModel::PercentageAndFlat, flat_fee_lamports: 5_000, treasury: key }),
+            ProviderInstruction::Withdraw(WithdrawInstruction{ amount: 6, valid_until_slot: None, adapter: AdapterKind::RaydiumStable }),
+            ProviderInstruction::SetGuardian { guardian: key },
+            ProviderInstruction::SetPause(SetPauseInstruction{ paused: false, swap_paused: true, deposit_paused: false, withdraw_paused: true }),
+            ProviderInstruction::SetPoolRegistry(SetPoolRegistryInstruction{ coin_mint: key, pc_mint: Pubkey::default(), enabled: true, kind: PoolKind::RaydiumClmm }),
+            ProviderInstruction::SwapNative { swap, native_side: NativeSide::Output },
+            ProviderInstruction::DepositNative(deposit),
+            ProviderInstruction::ZapIn(ZapInInstruction{ amount_in: 8, minimum_lp_amount: 7, valid_until_slot: None }),
+            ProviderInstruction::ZapOut(ZapOutInstruction{ amount: 8, minimum_amount_out: 7, valid_until_slot: Some(6) }),
+            ProviderInstruction::StakeLp(FarmInstruction{ amount: 1 }),
+            ProviderInstruction::UnstakeLp(FarmInstruction{ amount: 2 }),
+            ProviderInstruction::HarvestRewards,
+            ProviderInstruction::SetRewardFee { reward_fee_bps: 250 },
+            ProviderInstruction::InitializeLpVault { performance_fee_bps: 1_000, max_slippage_bps: 100 },
+            ProviderInstruction::DepositLpVault(LpVaultInstruction{ amount: 3 }),
+            ProviderInstruction::WithdrawLpVault(LpVaultInstruction{ amount: 4 }),
+            ProviderInstruction::Compound { minimum_lp_amount: 5 },
+            ProviderInstruction::PlaceLimitOrder(PlaceLimitOrderInstruction{
+                order_id: 1,
+                amount_in: 2,
+                minimum_amount_out: 3,
+                expiry_slot: 4,
+                keeper_tip_lamports: 5,
+            }),
+            ProviderInstruction::ExecuteLimitOrder,
+            ProviderInstruction::CancelLimitOrder,
+            ProviderInstruction::CreateDca(CreateDcaInstruction{
+                schedule_id: 1,
+                total_amount: 2,
+                amount_per_period: 3,
+                interval_slots: 4,
+                minimum_amount_out: 5,
+            }),
+            ProviderInstruction::ExecuteDca,
+            ProviderInstruction::CloseDca,
+            ProviderInstruction::PlaceTriggerOrder(PlaceTriggerOrderInstruction{
+                order_id: 1,
+                amount_in: 2,
+                stop_amount_out: 3,
+                take_profit_amount_out: 4,
+                max_slippage_bps: 5,
+                expiry_slot: 6,
+                keeper_tip_lamports: 7,
+            }),
+            ProviderInstruction::ExecuteTriggerOrder,
+            ProviderInstruction::CancelTriggerOrder,
+            ProviderInstruction::SerumSwap(SerumSwapInstruction{
+                amount_in: 1,
+                minimum_amount_out: 2,
+                limit_price: 3,
+                valid_until_slot: Some(4),
+            }),
+            ProviderInstruction::CreatePool(CreatePoolInstruction{ nonce: 254, init_coin_amount: 1, init_pc_amount: 2, open_time: 3 }),
+            ProviderInstruction::SetListingFee { listing_fee_lamports: 1_000_000, treasury: key },
+            ProviderInstruction::SetFarmProgram { farm_program_id: key },
+        ];
+        for (tag, instruction) in instructions.into_iter().enumerate() {
+            let packed = instruction.pack().unwrap();
+            assert_eq!(packed[0] as usize, tag);
+            assert_eq!(ProviderInstruction::unpack(&packed).unwrap(), instruction);
+        }
+    }
+
+    #[test]
+    fn test_raydium_golden() {
+        let cases = vec![
+            (
+                RaydiumInstruction::Initialize2(Initialize2Instruction{
+                    nonce: 254,
+                    open_time: 1,
+                    init_pc_amount: 2,
+                    init_coin_amount: 0x0103,
+                }),
+                vec![1, 254, 1, 0, 0, 0, 0, 0, 0, 0, 2, 0, 0, 0, 0, 0, 0, 0, 3, 1, 0, 0, 0, 0, 0, 0],
+            ),
+            (
+                RaydiumInstruction::MonitorStep(MonitorStepInstruction{
+                    plan_order_limit: 1,
+                    place_order_limit: 0x0102,
+                    cancel_order_limit: 3,
+                }),
+                vec![2, 1, 0, 0x02, 0x01, 3, 0],
+            ),
+            (
+                RaydiumInstruction::Deposit { max_coin_amount: 1, max_pc_amount: 2, base_side: 1 },
+                vec![3, 1, 0, 0, 0, 0, 0, 0, 0, 2, 0, 0, 0, 0, 0, 0, 0, 1, 0, 0, 0, 0, 0, 0, 0],
+            ),
+            (RaydiumInstruction::Withdraw { amount: 0x0100 }, vec![4, 0, 1, 0, 0, 0, 0, 0, 0]),
+            (
+                RaydiumInstruction::Swap { amount_in: 1_000, minimum_amount_out: 900 },
+                vec![9, 0xe8, 0x03, 0, 0, 0, 0, 0, 0, 0x84, 0x03, 0, 0, 0, 0, 0, 0],
+            ),
+        ];
+        for (instruction, expected) in cases {
+            assert_eq!(instruction.pack(), expected);
+            assert_eq!(RaydiumInstruction::unpack(&expected).unwrap(), instruction);
+        }
+    }
+
+    #[test]
+    fn test_raydium_builders_payload() {
+        let program_id = Pubkey::new_unique();
+        let key = Pubkey::new_unique();
+        let deposit = deposit(&program_id, &key, 254, &key, &key, &key, &key, &key, &key, &key, &key, &key, &key, 1, 2, 0).unwrap();
+        assert_eq!(
+            RaydiumInstruction::unpack(&deposit.data).unwrap(),
+            RaydiumInstruction::Deposit { max_coin_amount: 1, max_pc_amount: 2, base_side: 0 },
+        );
+    }
+}
@@ -1,6 +1,9 @@
+pub mod adapter;
 pub mod error;
 pub mod instruction;
 pub mod processor;
+pub mod serum;
+pub mod stable_swap;
 pub mod state;
 
 #[cfg(not(feature = "no-entrypoint"))]
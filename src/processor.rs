@@ -1,250 +1,3540 @@
+#![allow(clippy::too_many_arguments)]
+
 use solana_program::{
     account_info::{next_account_info, AccountInfo},
     entrypoint::ProgramResult,
     msg,
     program::{invoke, invoke_signed},
     program_error::ProgramError,
-    program_pack::{IsInitialized, Pack},
+    program_pack::Pack,
     pubkey::Pubkey,
-    sysvar::{rent::Rent, Sysvar},
-    instruction::{AccountMeta},
+    system_instruction,
+    sysvar::{self, clock::Clock, rent::Rent, Sysvar},
+};
+use crate::adapter::{
+    check_raydium_authority, check_serum_vault_signer, Adapter, AdapterKind, DepositAccounts, DexAdapter, RaydiumV4,
+    SerumAccounts, SwapAccounts,
 };
 use crate::error::AmmError;
-use crate::instruction::{AmmInstruction, SwapInstruction, DepositInstruction};
-use std::convert::TryInto;
+use crate::serum::{MarketState, OpenOrders};
+use crate::instruction::{
+    ProviderInstruction, SwapInstruction, DepositInstruction, WithdrawInstruction, FeeSide, NativeSide,
+    SetFeeReceiversInstruction, SetFlatFeeInstruction, SetPauseInstruction, SetPoolRegistryInstruction,
+    ZapInInstruction, ZapOutInstruction, FarmInstruction, LpVaultInstruction, PlaceLimitOrderInstruction,
+    CreateDcaInstruction, PlaceTriggerOrderInstruction, SerumSwapInstruction, SerumSide, CreatePoolInstruction,
+};
+use crate::state::{
+    AmmInfo, ProviderConfig, FeeConfig, FeeModel, Operation, PoolKind, PoolRegistry, LpVault, LimitOrder, DcaSchedule, TriggerOrder,
+    CONFIG_SEED, FEE_CONFIG_SEED, FEE_VAULT_SEED, POOL_REGISTRY_SEED, WSOL_SEED, LP_VAULT_SEED, SHARE_MINT_SEED,
+    LIMIT_ORDER_SEED, DCA_SEED, TRIGGER_ORDER_SEED, ESCROW_SEED, TOTAL_WEIGHT_BPS,
+};
+use spl_associated_token_account::{create_associated_token_account, get_associated_token_address};
+use spl_math::uint::U256;
+use spl_token::native_mint;
+use std::convert::{TryFrom, TryInto};
+
+/// Provider commission, charged as `amount * FEE_NUMERATOR / FEE_DENOMINATOR`
+pub const FEE_NUMERATOR: u64 = 1;
+pub const FEE_DENOMINATOR: u64 = 10;
+
+/// Fee config of one mint and the accounts its fees are paid to
+struct FeeAccounts<'a, 'b> {
+    config: FeeConfig,
+    /// token receivers, or only the fee vault. Empty without a commission
+    receivers: Vec<&'a AccountInfo<'b>>,
+    /// flat fee treasury, if the config charges one
+    treasury: Option<&'a AccountInfo<'b>>,
+    system_program: Option<&'a AccountInfo<'b>>,
+}
+
+impl FeeAccounts<'_, '_> {
+    /// Commission on `amount`, zero if the config only charges a flat fee
+    fn commission(&self, amount: u64) -> Result<u64, ProgramError> {
+        if self.config.charges_commission() {
+            Processor::commission(amount)
+        } else {
+            Ok(0)
+        }
+    }
+}
+
+/// Temporary wrapped SOL account standing in for a user token account
+struct WrappedSol<'a, 'b> {
+    account: &'a AccountInfo<'b>,
+    bump_seed: u8,
+    system_program: &'a AccountInfo<'b>,
+    rent_sysvar: &'a AccountInfo<'b>,
+    native_mint: &'a AccountInfo<'b>,
+}
+
+/// Optional accounts needed to create missing associated token accounts
+struct AssociatedAccounts<'a, 'b> {
+    associated_token_program: &'a AccountInfo<'b>,
+    system_program: &'a AccountInfo<'b>,
+    rent_sysvar: &'a AccountInfo<'b>,
+}
+
+/// Raydium pool and serum market accounts of the zap instructions, in instruction order
+struct ZapPool<'a, 'b> {
+    raydium_program_id: &'a AccountInfo<'b>,
+    spl_token_program_id: &'a AccountInfo<'b>,
+    amm_account: &'a AccountInfo<'b>,
+    authority: &'a AccountInfo<'b>,
+    amm_open_orders: &'a AccountInfo<'b>,
+    amm_target_orders: &'a AccountInfo<'b>,
+    pool_lp_mint: &'a AccountInfo<'b>,
+    pool_token_coin: &'a AccountInfo<'b>,
+    pool_token_pc: &'a AccountInfo<'b>,
+    serum_dex_program_id: &'a AccountInfo<'b>,
+    serum_market: &'a AccountInfo<'b>,
+    serum_bids: &'a AccountInfo<'b>,
+    serum_asks: &'a AccountInfo<'b>,
+    serum_event_queue: &'a AccountInfo<'b>,
+    serum_coin_vault_account: &'a AccountInfo<'b>,
+    serum_pc_vault_account: &'a AccountInfo<'b>,
+    serum_vault_signer: &'a AccountInfo<'b>,
+}
+
+impl<'a, 'b> ZapPool<'a, 'b> {
+    /// The pool as a Raydium v4 adapter
+    fn adapter(&self) -> RaydiumV4<'a, 'b> {
+        RaydiumV4 {
+            program_id: self.raydium_program_id,
+            spl_token_program_id: self.spl_token_program_id,
+            amm_account: self.amm_account,
+            authority: self.authority,
+            amm_open_orders: self.amm_open_orders,
+            amm_target_orders: self.amm_target_orders,
+            pool_lp_mint: Some(self.pool_lp_mint),
+            pool_token_coin: self.pool_token_coin,
+            pool_token_pc: self.pool_token_pc,
+            pool_withdraw_queue: None,
+            serum_market: self.serum_market,
+            serum: Some(SerumAccounts {
+                dex_program_id: self.serum_dex_program_id,
+                bids: self.serum_bids,
+                asks: self.serum_asks,
+                event_queue: self.serum_event_queue,
+                coin_vault: self.serum_coin_vault_account,
+                pc_vault: self.serum_pc_vault_account,
+                vault_signer: self.serum_vault_signer,
+            }),
+        }
+    }
+}
+
+/// Raydium farm accounts of an lp vault, in instruction order
+struct VaultFarm<'a, 'b> {
+    farm_program_id: &'a AccountInfo<'b>,
+    farm_account: &'a AccountInfo<'b>,
+    farm_authority: &'a AccountInfo<'b>,
+    farm_user_info: &'a AccountInfo<'b>,
+    farm_lp_vault: &'a AccountInfo<'b>,
+    farm_reward_vault: &'a AccountInfo<'b>,
+    clock_sysvar: &'a AccountInfo<'b>,
+}
+
 pub struct Processor;
 impl Processor {
     pub fn process(program_id: &Pubkey, accounts: &[AccountInfo], instruction_data: &[u8]) -> ProgramResult {
-        let instruction = AmmInstruction::unpack(instruction_data)?;
+        let instruction = ProviderInstruction::unpack(instruction_data)?;
+        // user facing instructions lead with the config account, consumed by the pause check
+        let (config, accounts) = match Self::operation(&instruction) {
+            Some(operation) => {
+                let (config, accounts) = Self::check_not_paused(program_id, accounts, operation)?;
+                (Some(config), accounts)
+            }
+            None => (None, accounts),
+        };
+        // staking and lp vaults only go to the farm program the admin set
+        let farm_program_id = config.map(|config| config.farm_program_id).unwrap_or_default();
+
+        match instruction {
+            ProviderInstruction::Swap(SwapInstruction {amount_in, minimum_amount_out, fee_side, valid_until_slot, adapter}) => {
+                msg!("Instruction: SwapInstruction");
+                Self::check_deadline(valid_until_slot)?;
+                Self::swap(accounts, amount_in, minimum_amount_out, fee_side, None, adapter, program_id)
+            },
+            ProviderInstruction::Deposit(DepositInstruction {max_coin_amount, max_pc_amount, base_side, valid_until_slot, adapter}) => {
+                msg!("Instruction: DepositInstruction");
+                Self::check_deadline(valid_until_slot)?;
+                Self::deposit(accounts, max_coin_amount, max_pc_amount, base_side, false, adapter, program_id)
+            },
+            ProviderInstruction::SwapNative {swap: SwapInstruction {amount_in, minimum_amount_out, fee_side, valid_until_slot, adapter}, native_side} => {
+                msg!("Instruction: SwapNative");
+                Self::check_deadline(valid_until_slot)?;
+                Self::swap(accounts, amount_in, minimum_amount_out, fee_side, Some(native_side), adapter, program_id)
+            },
+            ProviderInstruction::DepositNative(DepositInstruction {max_coin_amount, max_pc_amount, base_side, valid_until_slot, adapter}) => {
+                msg!("Instruction: DepositNative");
+                Self::check_deadline(valid_until_slot)?;
+                Self::deposit(accounts, max_coin_amount, max_pc_amount, base_side, true, adapter, program_id)
+            },
+            ProviderInstruction::ZapIn(ZapInInstruction {amount_in, minimum_lp_amount, valid_until_slot}) => {
+                msg!("Instruction: ZapIn");
+                Self::check_deadline(valid_until_slot)?;
+                Self::zap_in(accounts, amount_in, minimum_lp_amount, program_id)
+            },
+            ProviderInstruction::ZapOut(ZapOutInstruction {amount, minimum_amount_out, valid_until_slot}) => {
+                msg!("Instruction: ZapOut");
+                Self::check_deadline(valid_until_slot)?;
+                Self::zap_out(accounts, amount, minimum_amount_out, program_id)
+            },
+            ProviderInstruction::StakeLp(FarmInstruction {amount}) => {
+                msg!("Instruction: StakeLp");
+                Self::farm(accounts, false, amount, &farm_program_id, program_id)
+            },
+            ProviderInstruction::UnstakeLp(FarmInstruction {amount}) => {
+                msg!("Instruction: UnstakeLp");
+                Self::farm(accounts, true, amount, &farm_program_id, program_id)
+            },
+            ProviderInstruction::HarvestRewards => {
+                msg!("Instruction: HarvestRewards");
+                Self::farm(accounts, false, 0, &farm_program_id, program_id)
+            },
+            ProviderInstruction::DepositLpVault(LpVaultInstruction {amount}) => {
+                msg!("Instruction: DepositLpVault");
+                Self::deposit_lp_vault(accounts, amount, &farm_program_id, program_id)
+            },
+            ProviderInstruction::WithdrawLpVault(LpVaultInstruction {amount}) => {
+                msg!("Instruction: WithdrawLpVault");
+                Self::withdraw_lp_vault(accounts, amount, &farm_program_id, program_id)
+            },
+            ProviderInstruction::Compound {minimum_lp_amount} => {
+                msg!("Instruction: Compound");
+                Self::compound(accounts, minimum_lp_amount, &farm_program_id, program_id)
+            },
+            ProviderInstruction::PlaceLimitOrder(order) => {
+                msg!("Instruction: PlaceLimitOrder");
+                Self::place_limit_order(accounts, order, program_id)
+            },
+            ProviderInstruction::ExecuteLimitOrder => {
+                msg!("Instruction: ExecuteLimitOrder");
+                Self::execute_limit_order(accounts, program_id)
+            },
+            ProviderInstruction::CancelLimitOrder => {
+                msg!("Instruction: CancelLimitOrder");
+                Self::cancel_limit_order(accounts, program_id)
+            },
+            ProviderInstruction::CreateDca(schedule) => {
+                msg!("Instruction: CreateDca");
+                Self::create_dca(accounts, schedule, program_id)
+            },
+            ProviderInstruction::ExecuteDca => {
+                msg!("Instruction: ExecuteDca");
+                Self::execute_dca(accounts, program_id)
+            },
+            ProviderInstruction::CloseDca => {
+                msg!("Instruction: CloseDca");
+                Self::close_dca(accounts, program_id)
+            },
+            ProviderInstruction::PlaceTriggerOrder(order) => {
+                msg!("Instruction: PlaceTriggerOrder");
+                Self::place_trigger_order(accounts, order, program_id)
+            },
+            ProviderInstruction::ExecuteTriggerOrder => {
+                msg!("Instruction: ExecuteTriggerOrder");
+                Self::execute_trigger_order(accounts, program_id)
+            },
+            ProviderInstruction::CancelTriggerOrder => {
+                msg!("Instruction: CancelTriggerOrder");
+                Self::cancel_trigger_order(accounts, program_id)
+            },
+            ProviderInstruction::SerumSwap(swap) => {
+                msg!("Instruction: SerumSwap");
+                Self::check_deadline(swap.valid_until_slot)?;
+                Self::serum_swap(accounts, swap, program_id)
+            },
+            ProviderInstruction::CreatePool(pool) => {
+                msg!("Instruction: CreatePool");
+                Self::create_pool(accounts, pool, program_id)
+            },
+            ProviderInstruction::SetListingFee {listing_fee_lamports, treasury} => {
+                msg!("Instruction: SetListingFee");
+                Self::set_listing_fee(accounts, listing_fee_lamports, treasury, program_id)
+            },
+            ProviderInstruction::SetFarmProgram {farm_program_id} => {
+                msg!("Instruction: SetFarmProgram");
+                Self::set_farm_program(accounts, farm_program_id, program_id)
+            },
+            ProviderInstruction::Withdraw(WithdrawInstruction {amount, valid_until_slot, adapter}) => {
+                msg!("Instruction: WithdrawInstruction");
+                Self::check_deadline(valid_until_slot)?;
+                Self::withdraw(accounts, amount, adapter, program_id)
+            },
+            ProviderInstruction::InitializeConfig {guardian} => {
+                msg!("Instruction: InitializeConfig");
+                Self::initialize_config(accounts, guardian, program_id)
+            },
+            ProviderInstruction::SetGuardian {guardian} => {
+                msg!("Instruction: SetGuardian");
+                Self::set_guardian(accounts, guardian, program_id)
+            },
+            ProviderInstruction::SetPause(pause) => {
+                msg!("Instruction: SetPause");
+                Self::set_pause(accounts, pause, program_id)
+            },
+            ProviderInstruction::SetPoolRegistry(SetPoolRegistryInstruction {coin_mint, pc_mint, enabled, kind}) => {
+                msg!("Instruction: SetPoolRegistry");
+                Self::set_pool_registry(accounts, coin_mint, pc_mint, enabled, kind, program_id)
+            },
+            ProviderInstruction::SetFeeReceivers(SetFeeReceiversInstruction {receiver_count, receivers, accrue_to_vault}) => {
+                msg!("Instruction: SetFeeReceivers");
+                Self::set_fee_receivers(accounts, FeeConfig {
+                    receiver_count,
+                    receivers,
+                    accrue_to_vault,
+                    ..FeeConfig::default()
+                }, program_id)
+            },
+            ProviderInstruction::InitializeFeeVault => {
+                msg!("Instruction: InitializeFeeVault");
+                Self::initialize_fee_vault(accounts, program_id)
+            },
+            ProviderInstruction::SweepFees => {
+                msg!("Instruction: SweepFees");
+                Self::sweep_fees(accounts, program_id)
+            },
+            ProviderInstruction::SetFlatFee(SetFlatFeeInstruction {fee_model, flat_fee_lamports, treasury}) => {
+                msg!("Instruction: SetFlatFee");
+                Self::set_flat_fee(accounts, fee_model, flat_fee_lamports, treasury, program_id)
+            },
+            ProviderInstruction::SetRewardFee {reward_fee_bps} => {
+                msg!("Instruction: SetRewardFee");
+                Self::set_reward_fee(accounts, reward_fee_bps, program_id)
+            },
+            ProviderInstruction::InitializeLpVault {performance_fee_bps, max_slippage_bps} => {
+                msg!("Instruction: InitializeLpVault");
+                Self::initialize_lp_vault(accounts, performance_fee_bps, max_slippage_bps, program_id)
+            },
+        }
+    }
+
+    /// Operation a user facing instruction performs, `None` for admin instructions
+    fn operation(instruction: &ProviderInstruction) -> Option<Operation> {
+        match instruction {
+            ProviderInstruction::Swap(_)
+            | ProviderInstruction::SwapNative {..}
+            | ProviderInstruction::PlaceLimitOrder(_)
+            | ProviderInstruction::ExecuteLimitOrder
+            | ProviderInstruction::CreateDca(_)
+            | ProviderInstruction::ExecuteDca
+            | ProviderInstruction::PlaceTriggerOrder(_)
+            | ProviderInstruction::ExecuteTriggerOrder
+            | ProviderInstruction::SerumSwap(_) => Some(Operation::Swap),
+            ProviderInstruction::Deposit(_)
+            | ProviderInstruction::DepositNative(_)
+            | ProviderInstruction::ZapIn(_)
+            | ProviderInstruction::StakeLp(_)
+            | ProviderInstruction::DepositLpVault(_) => Some(Operation::Deposit),
+            ProviderInstruction::Withdraw(_)
+            | ProviderInstruction::ZapOut(_)
+            | ProviderInstruction::UnstakeLp(_)
+            | ProviderInstruction::HarvestRewards
+            | ProviderInstruction::WithdrawLpVault(_) => Some(Operation::Withdraw),
+            ProviderInstruction::Compound {..} => Some(Operation::Compound),
+            ProviderInstruction::SweepFees => Some(Operation::SweepFees),
+            _ => None,
+        }
+    }
+
+    /// Checks the config in the first account lets `operation` through, returns it and the other accounts
+    fn check_not_paused<'a, 'b>(
+        program_id: &Pubkey,
+        accounts: &'a [AccountInfo<'b>],
+        operation: Operation,
+    ) -> Result<(ProviderConfig, &'a [AccountInfo<'b>]), ProgramError> {
+        let (config_account, accounts) = accounts.split_first().ok_or(ProgramError::NotEnoughAccountKeys)?;
+        let config = ProviderConfig::load(program_id, config_account)?;
+        config.check_not_paused(operation)?;
+        Ok((config, accounts))
+    }
+
+    fn initialize_config(
+        accounts: &[AccountInfo],
+        guardian: Pubkey,
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let config_account = next_account_info(account_info_iter)?;
+        let admin = next_account_info(account_info_iter)?;
+        let system_program = next_account_info(account_info_iter)?;
+        if !admin.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+
+        let (address, bump_seed) = ProviderConfig::find_address(program_id);
+        if address != *config_account.key {
+            return Err(AmmError::InvalidConfig.into());
+        }
+        Self::create_pda_account(
+            admin,
+            config_account,
+            system_program,
+            ProviderConfig::LEN,
+            program_id,
+            &[CONFIG_SEED, &[bump_seed]],
+        )?;
+        let config = ProviderConfig {
+            is_initialized: true,
+            bump_seed,
+            admin: *admin.key,
+            guardian,
+            ..ProviderConfig::default()
+        };
+        ProviderConfig::pack(config, &mut config_account.data.borrow_mut())
+    }
+
+    fn set_guardian(
+        accounts: &[AccountInfo],
+        guardian: Pubkey,
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let config_account = next_account_info(account_info_iter)?;
+        let admin = next_account_info(account_info_iter)?;
+
+        let mut config = ProviderConfig::load(program_id, config_account)?;
+        Self::check_admin(&config, admin)?;
+        config.guardian = guardian;
+        ProviderConfig::pack(config, &mut config_account.data.borrow_mut())
+    }
+
+    fn set_pause(
+        accounts: &[AccountInfo],
+        pause: SetPauseInstruction,
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let config_account = next_account_info(account_info_iter)?;
+        let guardian = next_account_info(account_info_iter)?;
+
+        let mut config = ProviderConfig::load(program_id, config_account)?;
+        if !guardian.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+        if config.guardian != *guardian.key {
+            return Err(AmmError::InvalidGuardian.into());
+        }
+        config.paused = pause.paused;
+        config.swap_paused = pause.swap_paused;
+        config.deposit_paused = pause.deposit_paused;
+        config.withdraw_paused = pause.withdraw_paused;
+        ProviderConfig::pack(config, &mut config_account.data.borrow_mut())
+    }
+
+    fn set_fee_receivers(
+        accounts: &[AccountInfo],
+        mut fee_config: FeeConfig,
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let config_account = next_account_info(account_info_iter)?;
+        let admin = next_account_info(account_info_iter)?;
+        let fee_config_account = next_account_info(account_info_iter)?;
+        let mint = next_account_info(account_info_iter)?;
+        let system_program = next_account_info(account_info_iter)?;
+
+        let config = ProviderConfig::load(program_id, config_account)?;
+        Self::check_admin(&config, admin)?;
+
+        let (address, bump_seed) = FeeConfig::find_address(program_id, mint.key);
+        if address != *fee_config_account.key {
+            return Err(AmmError::InvalidConfig.into());
+        }
+        if !fee_config_account.data_is_empty() {
+            let current = FeeConfig::load(program_id, fee_config_account, mint.key)?;
+            fee_config.vault = current.vault;
+            fee_config.fee_model = current.fee_model;
+            fee_config.flat_fee_lamports = current.flat_fee_lamports;
+            fee_config.treasury = current.treasury;
+            fee_config.reward_fee_bps = current.reward_fee_bps;
+        }
+        fee_config.is_initialized = true;
+        fee_config.bump_seed = bump_seed;
+        fee_config.mint = *mint.key;
+        fee_config.validate()?;
+
+        if fee_config_account.data_is_empty() {
+            Self::create_pda_account(
+                admin,
+                fee_config_account,
+                system_program,
+                FeeConfig::LEN,
+                program_id,
+                &[FEE_CONFIG_SEED, mint.key.as_ref(), &[bump_seed]],
+            )?;
+        }
+        FeeConfig::pack(fee_config, &mut fee_config_account.data.borrow_mut())
+    }
+
+    fn set_pool_registry(
+        accounts: &[AccountInfo],
+        coin_mint: Pubkey,
+        pc_mint: Pubkey,
+        enabled: bool,
+        kind: PoolKind,
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let config_account = next_account_info(account_info_iter)?;
+        let admin = next_account_info(account_info_iter)?;
+        let registry_account = next_account_info(account_info_iter)?;
+        let amm_account = next_account_info(account_info_iter)?;
+        let system_program = next_account_info(account_info_iter)?;
+
+        let config = ProviderConfig::load(program_id, config_account)?;
+        Self::check_admin(&config, admin)?;
+        if coin_mint == pc_mint {
+            return Err(AmmError::InvalidInput.into());
+        }
+
+        let (address, bump_seed) = PoolRegistry::find_address(program_id, amm_account.key);
+        if address != *registry_account.key {
+            return Err(AmmError::InvalidConfig.into());
+        }
+        if registry_account.data_is_empty() {
+            Self::create_pda_account(
+                admin,
+                registry_account,
+                system_program,
+                PoolRegistry::LEN,
+                program_id,
+                &[POOL_REGISTRY_SEED, amm_account.key.as_ref(), &[bump_seed]],
+            )?;
+        }
+        let registry = PoolRegistry {
+            is_initialized: true,
+            bump_seed,
+            amm: *amm_account.key,
+            coin_mint,
+            pc_mint,
+            enabled,
+            kind,
+        };
+        PoolRegistry::pack(registry, &mut registry_account.data.borrow_mut())
+    }
+
+    fn set_flat_fee(
+        accounts: &[AccountInfo],
+        fee_model: FeeModel,
+        flat_fee_lamports: u64,
+        treasury: Pubkey,
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let config_account = next_account_info(account_info_iter)?;
+        let admin = next_account_info(account_info_iter)?;
+        let fee_config_account = next_account_info(account_info_iter)?;
+        let mint = next_account_info(account_info_iter)?;
+
+        let config = ProviderConfig::load(program_id, config_account)?;
+        Self::check_admin(&config, admin)?;
+        let mut fee_config = FeeConfig::load(program_id, fee_config_account, mint.key)?;
+        fee_config.fee_model = fee_model;
+        fee_config.flat_fee_lamports = flat_fee_lamports;
+        fee_config.treasury = treasury;
+        fee_config.validate()?;
+        FeeConfig::pack(fee_config, &mut fee_config_account.data.borrow_mut())
+    }
+
+    fn set_reward_fee(
+        accounts: &[AccountInfo],
+        reward_fee_bps: u16,
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let config_account = next_account_info(account_info_iter)?;
+        let admin = next_account_info(account_info_iter)?;
+        let fee_config_account = next_account_info(account_info_iter)?;
+        let mint = next_account_info(account_info_iter)?;
+
+        let config = ProviderConfig::load(program_id, config_account)?;
+        Self::check_admin(&config, admin)?;
+        let mut fee_config = FeeConfig::load(program_id, fee_config_account, mint.key)?;
+        fee_config.reward_fee_bps = reward_fee_bps;
+        fee_config.validate()?;
+        FeeConfig::pack(fee_config, &mut fee_config_account.data.borrow_mut())
+    }
+
+    fn initialize_fee_vault(
+        accounts: &[AccountInfo],
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let config_account = next_account_info(account_info_iter)?;
+        let admin = next_account_info(account_info_iter)?;
+        let fee_config_account = next_account_info(account_info_iter)?;
+        let fee_vault = next_account_info(account_info_iter)?;
+        let mint = next_account_info(account_info_iter)?;
+        let spl_token_program_id = next_account_info(account_info_iter)?;
+        let system_program = next_account_info(account_info_iter)?;
+        let rent_sysvar = next_account_info(account_info_iter)?;
+
+        let config = ProviderConfig::load(program_id, config_account)?;
+        Self::check_admin(&config, admin)?;
+        let mut fee_config = FeeConfig::load(program_id, fee_config_account, mint.key)?;
+        if fee_config.vault != Pubkey::default() {
+            return Err(AmmError::InvalidConfig.into());
+        }
+        if *spl_token_program_id.key != spl_token::id() {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+
+        let (address, bump_seed) = FeeConfig::find_vault_address(program_id, mint.key);
+        if address != *fee_vault.key {
+            return Err(AmmError::InvalidConfig.into());
+        }
+        Self::create_pda_account(
+            admin,
+            fee_vault,
+            system_program,
+            spl_token::state::Account::LEN,
+            spl_token_program_id.key,
+            &[FEE_VAULT_SEED, mint.key.as_ref(), &[bump_seed]],
+        )?;
+        invoke(
+            &spl_token::instruction::initialize_account(
+                spl_token_program_id.key,
+                fee_vault.key,
+                mint.key,
+                fee_config_account.key,
+            )?,
+            &[
+                fee_vault.clone(),
+                mint.clone(),
+                fee_config_account.clone(),
+                rent_sysvar.clone(),
+            ],
+        )?;
+
+        fee_config.vault = *fee_vault.key;
+        FeeConfig::pack(fee_config, &mut fee_config_account.data.borrow_mut())
+    }
+
+    fn sweep_fees(
+        accounts: &[AccountInfo],
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let fee_config_account = next_account_info(account_info_iter)?;
+        let fee_vault = next_account_info(account_info_iter)?;
+        let spl_token_program_id = next_account_info(account_info_iter)?;
+        // the fee config signs for the vault, never hand that to another program
+        if *spl_token_program_id.key != spl_token::id() {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+
+        let vault = spl_token::state::Account::unpack(&fee_vault.data.borrow())?;
+        let fee_config = FeeConfig::load(program_id, fee_config_account, &vault.mint)?;
+        if fee_config.vault != *fee_vault.key {
+            return Err(AmmError::InvalidFeeReceiver.into());
+        }
+        let fee_receivers = Self::next_fee_receivers(&fee_config, account_info_iter)?;
+
+        let bump_seed = [fee_config.bump_seed];
+        let signer_seeds = fee_config.signer_seeds(&bump_seed);
+        let shares = fee_config.split(vault.amount)?;
+        for (fee_receiver, share) in fee_receivers.iter().zip(shares.iter()) {
+            if *share == 0 {
+                continue;
+            }
+            invoke_signed(
+                &spl_token::instruction::transfer(
+                    spl_token_program_id.key,
+                    fee_vault.key,
+                    fee_receiver.key,
+                    fee_config_account.key,
+                    &[],
+                    *share,
+                )?,
+                &[
+                    fee_vault.clone(),
+                    (*fee_receiver).clone(),
+                    fee_config_account.clone(),
+                ],
+                &[&signer_seeds],
+            )?;
+        }
+        Ok(())
+    }
+
+    /// Provider commission on `amount`
+    fn commission(amount: u64) -> Result<u64, ProgramError> {
+        let fee = to_u128(amount)?
+            .checked_mul(to_u128(FEE_NUMERATOR)?)
+            .ok_or(AmmError::ConversionFailure)?
+            .checked_div(to_u128(FEE_DENOMINATOR)?)
+            .ok_or(AmmError::ConversionFailure)?;
+        Ok(to_u64(fee)?)
+    }
+
+    fn deposit(
+        accounts: &[AccountInfo],
+        max_coin_amount: u64, 
+        max_pc_amount: u64, 
+        base_side: u64,
+        native: bool,
+        adapter: AdapterKind,
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let registry_account = next_account_info(account_info_iter)?;
+        let (pool, user) = Adapter::next_deposit(adapter, account_info_iter)?;
+        let spl_token_program_id = pool.spl_token_program_id();
+        let user_coin_token_base_account = user.coin;
+        let user_pc_token_base_account = user.pc;
+        let user_owner_account = user.owner;
+        let registry = Self::check_adapter_pool(program_id, registry_account, &pool)?;
+        // the SOL side of a native deposit has no token account yet
+        let native_coin = native && registry.coin_mint == native_mint::id();
+        let native_pc = native && registry.pc_mint == native_mint::id();
+        if native && !native_coin && !native_pc {
+            return Err(AmmError::InvalidMint.into());
+        }
+        let coin_mint = if native_coin { native_mint::id() } else { Self::token_mint(user_coin_token_base_account)? };
+        let pc_mint = if native_pc { native_mint::id() } else { Self::token_mint(user_pc_token_base_account)? };
+        if coin_mint != registry.coin_mint || pc_mint != registry.pc_mint {
+            return Err(AmmError::InvalidMint.into());
+        }
+        let coin_fees = Self::next_fee_accounts(program_id, &coin_mint, account_info_iter)?;
+        let pc_fees = Self::next_fee_accounts(program_id, &pc_mint, account_info_iter)?;
+        let fee_first = coin_fees.commission(max_coin_amount)?;
+        let fee_second = pc_fees.commission(max_pc_amount)?;
+
+        let wrapped_sol = if native_coin {
+            let wrapped_sol = Self::next_wrapped_sol(program_id, user_coin_token_base_account, user_owner_account, account_info_iter)?;
+            Self::open_wrapped_sol(&wrapped_sol, spl_token_program_id, user_owner_account, max_coin_amount)?;
+            Some(wrapped_sol)
+        } else if native_pc {
+            let wrapped_sol = Self::next_wrapped_sol(program_id, user_pc_token_base_account, user_owner_account, account_info_iter)?;
+            Self::open_wrapped_sol(&wrapped_sol, spl_token_program_id, user_owner_account, max_pc_amount)?;
+            Some(wrapped_sol)
+        } else {
+            None
+        };
+
+        let final_amount_coin = max_coin_amount.checked_sub(fee_first).unwrap();
+        let final_amount_pc = max_pc_amount.checked_sub(fee_second).unwrap();
+
+        let deposit_tx = pool.deposit_instruction(&user, final_amount_coin, final_amount_pc, base_side)?;
+        invoke(&deposit_tx, accounts)?;
+
+        Self::distribute_fee(spl_token_program_id, user_coin_token_base_account, user_owner_account, &coin_fees, fee_first)?;
+        Self::distribute_fee(spl_token_program_id, user_pc_token_base_account, user_owner_account, &pc_fees, fee_second)?;
+        Self::collect_flat_fee(user_owner_account, &coin_fees)?;
+        Self::collect_flat_fee(user_owner_account, &pc_fees)?;
+        if let Some(wrapped_sol) = wrapped_sol {
+            Self::close_wrapped_sol(&wrapped_sol, spl_token_program_id, user_owner_account)?;
+        }
+        Ok(())
+    }
+
+    fn withdraw(
+        accounts: &[AccountInfo],
+        amount: u64,
+        adapter: AdapterKind,
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let registry_account = next_account_info(account_info_iter)?;
+        let (pool, user) = Adapter::next_withdraw(adapter, account_info_iter)?;
+        let spl_token_program_id = pool.spl_token_program_id();
+        let user_coin_token_account = user.coin;
+        let user_pc_token_account = user.pc;
+        let user_owner_account = user.owner;
+        let registry = Self::check_adapter_pool(program_id, registry_account, &pool)?;
+        if let Some(associated) = Self::next_associated_accounts(account_info_iter)? {
+            let coin_mint = next_account_info(account_info_iter)?;
+            let pc_mint = next_account_info(account_info_iter)?;
+            if *coin_mint.key != registry.coin_mint || *pc_mint.key != registry.pc_mint {
+                return Err(AmmError::InvalidMint.into());
+            }
+            Self::create_associated_account(&associated, spl_token_program_id, user_owner_account, user_coin_token_account, user_owner_account, coin_mint)?;
+            Self::create_associated_account(&associated, spl_token_program_id, user_owner_account, user_pc_token_account, user_owner_account, pc_mint)?;
+        }
+        if Self::token_mint(user_coin_token_account)? != registry.coin_mint
+            || Self::token_mint(user_pc_token_account)? != registry.pc_mint {
+            return Err(AmmError::InvalidMint.into());
+        }
+
+        let withdraw_tx = pool.withdraw_instruction(&user, amount)?;
+        invoke(&withdraw_tx, accounts)
+    }
+
+    fn swap(
+        accounts: &[AccountInfo],
+        amount_in: u64, 
+        minimum_amount_out: u64,
+        fee_side: FeeSide,
+        native_side: Option<NativeSide>,
+        adapter: AdapterKind,
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        msg!("Swap initialized");
+        let account_info_iter = &mut accounts.iter();
+        let registry_account = next_account_info(account_info_iter)?;
+        let (pool, user) = Adapter::next_swap(adapter, account_info_iter)?;
+        let spl_token_program_id = pool.spl_token_program_id();
+        let user_source_token_account = user.source;
+        let user_destination_token_account = user.destination;
+        let user_owner_account = user.owner;
+        let registry = Self::check_adapter_pool(program_id, registry_account, &pool)?;
+        // the SOL side of a native swap has no token account yet
+        let source_mint = match native_side {
+            Some(NativeSide::Input) => native_mint::id(),
+            _ => Self::token_mint(user_source_token_account)?,
+        };
+        // the destination may not exist yet, its mint is the other side of the pool
+        let destination_mint = registry.other_mint(&source_mint).ok_or(AmmError::InvalidMint)?;
+        if native_side == Some(NativeSide::Output) && destination_mint != native_mint::id() {
+            return Err(AmmError::InvalidMint.into());
+        }
+        let fee_source = match fee_side {
+            FeeSide::Input => user_source_token_account,
+            FeeSide::Output => user_destination_token_account,
+        };
+        let fee_mint = match fee_side {
+            FeeSide::Input => source_mint,
+            FeeSide::Output => destination_mint,
+        };
+        let fees = Self::next_fee_accounts(program_id, &fee_mint, account_info_iter)?;
+        let wrapped_sol = match native_side {
+            Some(NativeSide::Input) => {
+                let wrapped_sol = Self::next_wrapped_sol(program_id, user_source_token_account, user_owner_account, account_info_iter)?;
+                Self::open_wrapped_sol(&wrapped_sol, spl_token_program_id, user_owner_account, amount_in)?;
+                Some(wrapped_sol)
+            },
+            Some(NativeSide::Output) => {
+                let wrapped_sol = Self::next_wrapped_sol(program_id, user_destination_token_account, user_owner_account, account_info_iter)?;
+                Self::open_wrapped_sol(&wrapped_sol, spl_token_program_id, user_owner_account, 0)?;
+                Some(wrapped_sol)
+            },
+            None => None,
+        };
+        if let Some(associated) = Self::next_associated_accounts(account_info_iter)? {
+            let source_mint_account = next_account_info(account_info_iter)?;
+            let destination_mint_account = next_account_info(account_info_iter)?;
+            if *source_mint_account.key != source_mint || *destination_mint_account.key != destination_mint {
+                return Err(AmmError::InvalidMint.into());
+            }
+            if native_side != Some(NativeSide::Output) {
+                Self::create_associated_account(
+                    &associated,
+                    spl_token_program_id,
+                    user_owner_account,
+                    user_destination_token_account,
+                    user_owner_account,
+                    destination_mint_account,
+                )?;
+            }
+            let fee_mint_account = match fee_side {
+                FeeSide::Input => source_mint_account,
+                FeeSide::Output => destination_mint_account,
+            };
+            Self::create_fee_receiver_accounts(
+                &associated,
+                spl_token_program_id,
+                user_owner_account,
+                &fees,
+                fee_mint_account,
+                account_info_iter,
+            )?;
+        }
+        if native_side != Some(NativeSide::Output) && Self::token_mint(user_destination_token_account)? != destination_mint {
+            return Err(AmmError::InvalidMint.into());
+        }
+
+        // an input side fee is collected up front so Raydium only sees the net amount
+        let (fee, amount_in) = Self::swap_commission(&fees, fee_side, amount_in, minimum_amount_out)?;
+        if fee_side == FeeSide::Input {
+            Self::distribute_fee(spl_token_program_id, fee_source, user_owner_account, &fees, fee)?;
+        }
+        msg!("Swap Instruction");
+        let instruction = pool.swap_instruction(&user, amount_in, minimum_amount_out)?;
+        msg!("Swap Invoke");
+        invoke(&instruction, accounts)?;
+        if fee_side == FeeSide::Output {
+            Self::distribute_fee(spl_token_program_id, fee_source, user_owner_account, &fees, fee)?;
+        }
+        Self::collect_flat_fee(user_owner_account, &fees)?;
+        if let Some(wrapped_sol) = wrapped_sol {
+            Self::close_wrapped_sol(&wrapped_sol, spl_token_program_id, user_owner_account)?;
+        }
+
+        msg!("OKOKOKO");
+        Ok(())
+    }
+
+    /// Commission of a swap and the amount swapped. An input side fee comes out of `amount_in`,
+    /// an output side fee is charged on the guaranteed `minimum_amount_out`
+    fn swap_commission(
+        fees: &FeeAccounts,
+        fee_side: FeeSide,
+        amount_in: u64,
+        minimum_amount_out: u64,
+    ) -> Result<(u64, u64), ProgramError> {
+        match fee_side {
+            FeeSide::Input => {
+                let fee = fees.commission(amount_in)?;
+                Ok((fee, amount_in.checked_sub(fee).ok_or(AmmError::InvalidInput)?))
+            },
+            FeeSide::Output => Ok((fees.commission(minimum_amount_out)?, amount_in)),
+        }
+    }
+
+    /// Commission on the `amount_out` of an order and what is left of it for the user,
+    /// which is what the order minimum is checked against
+    fn order_commission(fees: &FeeAccounts, amount_out: u64) -> Result<(u64, u64), ProgramError> {
+        let fee = fees.commission(amount_out)?;
+        Ok((fee, amount_out.checked_sub(fee).ok_or(AmmError::InvalidInput)?))
+    }
+
+    /// Stakes or unstakes `amount` in a Raydium farm, a zero amount deposit only harvests.
+    /// Every call pays out the pending rewards, which are charged the reward commission.
+    fn farm(
+        accounts: &[AccountInfo],
+        unstake: bool,
+        amount: u64,
+        expected_farm_program_id: &Pubkey,
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let farm_program_id = next_account_info(account_info_iter)?;
+        let farm_account = next_account_info(account_info_iter)?;
+        let farm_authority = next_account_info(account_info_iter)?;
+        let user_info_account = next_account_info(account_info_iter)?;
+        let user_owner_account = next_account_info(account_info_iter)?;
+        let user_lp_token_account = next_account_info(account_info_iter)?;
+        let farm_lp_vault = next_account_info(account_info_iter)?;
+        let user_reward_token_account = next_account_info(account_info_iter)?;
+        let farm_reward_vault = next_account_info(account_info_iter)?;
+        let clock_sysvar = next_account_info(account_info_iter)?;
+        let spl_token_program_id = next_account_info(account_info_iter)?;
+        if farm_program_id.key != expected_farm_program_id || farm_account.owner != farm_program_id.key {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        let reward_mint = Self::token_mint(user_reward_token_account)?;
+        let fee_config_account = next_account_info(account_info_iter)?;
+        // a reward mint without a fee config is not charged
+        let fees = if fee_config_account.data_is_empty() {
+            let (address, _) = FeeConfig::find_address(program_id, &reward_mint);
+            if address != *fee_config_account.key {
+                return Err(AmmError::InvalidConfig.into());
+            }
+            None
+        } else {
+            let fee_config = FeeConfig::load(program_id, fee_config_account, &reward_mint)?;
+            if fee_config.charges_reward_fee() {
+                let receivers = Self::next_fee_destinations(&fee_config, account_info_iter)?;
+                Some(FeeAccounts { config: fee_config, receivers, treasury: None, system_program: None })
+            } else {
+                None
+            }
+        };
+
+        let reward_before = Self::token_amount(user_reward_token_account)?;
+        let farm_tx = if unstake {
+            crate::instruction::farm_withdraw(
+                farm_program_id.key,
+                farm_account.key,
+                farm_authority.key,
+                user_info_account.key,
+                user_owner_account.key,
+                user_lp_token_account.key,
+                farm_lp_vault.key,
+                user_reward_token_account.key,
+                farm_reward_vault.key,
+                amount,
+            )?
+        } else {
+            crate::instruction::farm_deposit(
+                farm_program_id.key,
+                farm_account.key,
+                farm_authority.key,
+                user_info_account.key,
+                user_owner_account.key,
+                user_lp_token_account.key,
+                farm_lp_vault.key,
+                user_reward_token_account.key,
+                farm_reward_vault.key,
+                amount,
+            )?
+        };
+        invoke(
+            &farm_tx,
+            &[
+                farm_account.clone(),
+                farm_authority.clone(),
+                user_info_account.clone(),
+                user_owner_account.clone(),
+                user_lp_token_account.clone(),
+                farm_lp_vault.clone(),
+                user_reward_token_account.clone(),
+                farm_reward_vault.clone(),
+                clock_sysvar.clone(),
+                spl_token_program_id.clone(),
+            ],
+        )?;
+        let rewards = Self::token_amount(user_reward_token_account)?
+            .checked_sub(reward_before)
+            .ok_or(AmmError::InvalidInput)?;
+        match fees {
+            Some(fees) => {
+                let fee = fees.config.reward_commission(rewards)?;
+                Self::distribute_fee(spl_token_program_id, user_reward_token_account, user_owner_account, &fees, fee)
+            }
+            None => Ok(()),
+        }
+    }
+
+    fn initialize_lp_vault(
+        accounts: &[AccountInfo],
+        performance_fee_bps: u16,
+        max_slippage_bps: u16,
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let config_account = next_account_info(account_info_iter)?;
+        let admin = next_account_info(account_info_iter)?;
+        let registry_account = next_account_info(account_info_iter)?;
+        let amm_account = next_account_info(account_info_iter)?;
+        let vault_account = next_account_info(account_info_iter)?;
+        let share_mint = next_account_info(account_info_iter)?;
+        let vault_lp_token_account = next_account_info(account_info_iter)?;
+        let vault_coin_token_account = next_account_info(account_info_iter)?;
+        let vault_pc_token_account = next_account_info(account_info_iter)?;
+        let lp_mint = next_account_info(account_info_iter)?;
+        let coin_mint = next_account_info(account_info_iter)?;
+        let pc_mint = next_account_info(account_info_iter)?;
+        let farm_account = next_account_info(account_info_iter)?;
+        let farm_user_info = next_account_info(account_info_iter)?;
+        let treasury = next_account_info(account_info_iter)?;
+        let system_program = next_account_info(account_info_iter)?;
+        let spl_token_program_id = next_account_info(account_info_iter)?;
+        let associated_token_program = next_account_info(account_info_iter)?;
+        let rent_sysvar = next_account_info(account_info_iter)?;
+
+        let config = ProviderConfig::load(program_id, config_account)?;
+        Self::check_admin(&config, admin)?;
+        let registry = PoolRegistry::load(program_id, registry_account, amm_account.key)?;
+        registry.check_kind(PoolKind::RaydiumV4)?;
+        let amm_lp_mint = AmmInfo::load_amm_mut(amm_account, true)?.lp_mint;
+        if *lp_mint.key != amm_lp_mint || *coin_mint.key != registry.coin_mint || *pc_mint.key != registry.pc_mint {
+            return Err(AmmError::InvalidMint.into());
+        }
+        // compounding swaps the rewards in the pool itself, so the farm must reward one of its mints
+        let reward_mint = Self::token_mint(treasury)?;
+        if !registry.has_mint(&reward_mint) {
+            return Err(AmmError::RewardMintNotInPool.into());
+        }
+        if *farm_account.owner != config.farm_program_id {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        if farm_user_info.owner != farm_account.owner {
+            return Err(AmmError::InvalidVault.into());
+        }
+        if performance_fee_bps > TOTAL_WEIGHT_BPS || max_slippage_bps > TOTAL_WEIGHT_BPS {
+            return Err(AmmError::InvalidFee.into());
+        }
+        if *spl_token_program_id.key != spl_token::id() || *associated_token_program.key != spl_associated_token_account::id() {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+
+        let (address, bump_seed) = LpVault::find_address(program_id, amm_account.key);
+        if address != *vault_account.key || !vault_account.data_is_empty() {
+            return Err(AmmError::InvalidVault.into());
+        }
+        let (share_mint_address, share_mint_bump_seed) = LpVault::find_share_mint_address(program_id, vault_account.key);
+        if share_mint_address != *share_mint.key {
+            return Err(AmmError::InvalidVault.into());
+        }
+        Self::create_pda_account(
+            admin,
+            vault_account,
+            system_program,
+            LpVault::LEN,
+            program_id,
+            &[LP_VAULT_SEED, amm_account.key.as_ref(), &[bump_seed]],
+        )?;
+        Self::create_pda_account(
+            admin,
+            share_mint,
+            system_program,
+            spl_token::state::Mint::LEN,
+            spl_token_program_id.key,
+            &[SHARE_MINT_SEED, vault_account.key.as_ref(), &[share_mint_bump_seed]],
+        )?;
+        // shares start at one per lp token, so they share its decimals
+        let decimals = spl_token::state::Mint::unpack(&lp_mint.data.borrow())?.decimals;
+        invoke(
+            &spl_token::instruction::initialize_mint(
+                spl_token_program_id.key,
+                share_mint.key,
+                vault_account.key,
+                None,
+                decimals,
+            )?,
+            &[share_mint.clone(), rent_sysvar.clone()],
+        )?;
+        let associated = AssociatedAccounts { associated_token_program, system_program, rent_sysvar };
+        for (token_account, mint) in [
+            (vault_lp_token_account, lp_mint),
+            (vault_coin_token_account, coin_mint),
+            (vault_pc_token_account, pc_mint),
+        ] {
+            Self::create_associated_account(&associated, spl_token_program_id, admin, token_account, vault_account, mint)?;
+        }
+
+        let vault = LpVault {
+            is_initialized: true,
+            bump_seed,
+            amm: *amm_account.key,
+            farm: *farm_account.key,
+            farm_user_info: *farm_user_info.key,
+            share_mint: *share_mint.key,
+            lp_token: *vault_lp_token_account.key,
+            coin_token: *vault_coin_token_account.key,
+            pc_token: *vault_pc_token_account.key,
+            reward_mint,
+            treasury: *treasury.key,
+            staked_lp: 0,
+            performance_fee_bps,
+            reward_balance: 0,
+            last_compound_slot: 0,
+            max_slippage_bps,
+        };
+        LpVault::pack(vault, &mut vault_account.data.borrow_mut())
+    }
+
+    fn deposit_lp_vault(
+        accounts: &[AccountInfo],
+        amount: u64,
+        farm_program_id: &Pubkey,
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let vault_account = next_account_info(account_info_iter)?;
+        let share_mint = next_account_info(account_info_iter)?;
+        let vault_lp_token_account = next_account_info(account_info_iter)?;
+        let user_lp_token_account = next_account_info(account_info_iter)?;
+        let user_share_token_account = next_account_info(account_info_iter)?;
+        let user_owner_account = next_account_info(account_info_iter)?;
+        let farm = Self::next_vault_farm(account_info_iter)?;
+        let vault_reward_token_account = next_account_info(account_info_iter)?;
+        let spl_token_program_id = next_account_info(account_info_iter)?;
+
+        let mut vault = LpVault::load(program_id, vault_account)?;
+        Self::check_vault_accounts(
+            &vault,
+            share_mint,
+            vault_lp_token_account,
+            vault_reward_token_account,
+            &farm,
+            farm_program_id,
+            spl_token_program_id,
+        )?;
+        let share_supply = spl_token::state::Mint::unpack(&share_mint.data.borrow())?.supply;
+        vault.check_compounded(share_supply, Clock::get()?.slot)?;
+        let shares = vault.shares_for_lp(amount, share_supply)?;
+        if shares == 0 {
+            return Err(AmmError::InvalidInput.into());
+        }
+
+        invoke(
+            &spl_token::instruction::transfer(
+                spl_token_program_id.key,
+                user_lp_token_account.key,
+                vault_lp_token_account.key,
+                user_owner_account.key,
+                &[],
+                amount,
+            )?,
+            &[user_lp_token_account.clone(), vault_lp_token_account.clone(), user_owner_account.clone()],
+        )?;
+        let bump_seed = [vault.bump_seed];
+        let signer_seeds = vault.signer_seeds(&bump_seed);
+        Self::invoke_vault_farm(
+            &farm,
+            vault_account,
+            vault_lp_token_account,
+            vault_reward_token_account,
+            spl_token_program_id,
+            false,
+            amount,
+            &signer_seeds,
+        )?;
+        invoke_signed(
+            &spl_token::instruction::mint_to(
+                spl_token_program_id.key,
+                share_mint.key,
+                user_share_token_account.key,
+                vault_account.key,
+                &[],
+                shares,
+            )?,
+            &[share_mint.clone(), user_share_token_account.clone(), vault_account.clone()],
+            &[&signer_seeds],
+        )?;
+
+        vault.staked_lp = vault.staked_lp.checked_add(amount).ok_or(AmmError::InvalidInput)?;
+        LpVault::pack(vault, &mut vault_account.data.borrow_mut())
+    }
+
+    fn withdraw_lp_vault(
+        accounts: &[AccountInfo],
+        shares: u64,
+        farm_program_id: &Pubkey,
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let vault_account = next_account_info(account_info_iter)?;
+        let share_mint = next_account_info(account_info_iter)?;
+        let vault_lp_token_account = next_account_info(account_info_iter)?;
+        let user_lp_token_account = next_account_info(account_info_iter)?;
+        let user_share_token_account = next_account_info(account_info_iter)?;
+        let user_owner_account = next_account_info(account_info_iter)?;
+        let farm = Self::next_vault_farm(account_info_iter)?;
+        let vault_reward_token_account = next_account_info(account_info_iter)?;
+        let spl_token_program_id = next_account_info(account_info_iter)?;
+
+        let mut vault = LpVault::load(program_id, vault_account)?;
+        Self::check_vault_accounts(
+            &vault,
+            share_mint,
+            vault_lp_token_account,
+            vault_reward_token_account,
+            &farm,
+            farm_program_id,
+            spl_token_program_id,
+        )?;
+        let share_supply = spl_token::state::Mint::unpack(&share_mint.data.borrow())?.supply;
+        let amount = vault.lp_for_shares(shares, share_supply)?;
+
+        invoke(
+            &spl_token::instruction::burn(
+                spl_token_program_id.key,
+                user_share_token_account.key,
+                share_mint.key,
+                user_owner_account.key,
+                &[],
+                shares,
+            )?,
+            &[user_share_token_account.clone(), share_mint.clone(), user_owner_account.clone()],
+        )?;
+        let bump_seed = [vault.bump_seed];
+        let signer_seeds = vault.signer_seeds(&bump_seed);
+        Self::invoke_vault_farm(
+            &farm,
+            vault_account,
+            vault_lp_token_account,
+            vault_reward_token_account,
+            spl_token_program_id,
+            true,
+            amount,
+            &signer_seeds,
+        )?;
+        invoke_signed(
+            &spl_token::instruction::transfer(
+                spl_token_program_id.key,
+                vault_lp_token_account.key,
+                user_lp_token_account.key,
+                vault_account.key,
+                &[],
+                amount,
+            )?,
+            &[vault_lp_token_account.clone(), user_lp_token_account.clone(), vault_account.clone()],
+            &[&signer_seeds],
+        )?;
+
+        vault.staked_lp = vault.staked_lp.checked_sub(amount).ok_or(AmmError::InvalidInput)?;
+        LpVault::pack(vault, &mut vault_account.data.borrow_mut())
+    }
+
+    fn compound(
+        accounts: &[AccountInfo],
+        minimum_lp_amount: u64,
+        farm_program_id: &Pubkey,
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let registry_account = next_account_info(account_info_iter)?;
+        let vault_account = next_account_info(account_info_iter)?;
+        let pool = Self::next_zap_pool(account_info_iter)?;
+        let vault_coin_token_account = next_account_info(account_info_iter)?;
+        let vault_pc_token_account = next_account_info(account_info_iter)?;
+        let vault_lp_token_account = next_account_info(account_info_iter)?;
+        let farm = Self::next_vault_farm(account_info_iter)?;
+        let treasury = next_account_info(account_info_iter)?;
+
+        let mut vault = LpVault::load(program_id, vault_account)?;
+        if vault.amm != *pool.amm_account.key
+            || vault.coin_token != *vault_coin_token_account.key
+            || vault.pc_token != *vault_pc_token_account.key
+            || vault.treasury != *treasury.key {
+            return Err(AmmError::InvalidVault.into());
+        }
+        let registry = Self::check_pool(program_id, registry_account, &pool)?;
+        let reward_is_coin = vault.reward_mint == registry.coin_mint;
+        let (reward_token_account, other_token_account) = if reward_is_coin {
+            (vault_coin_token_account, vault_pc_token_account)
+        } else {
+            (vault_pc_token_account, vault_coin_token_account)
+        };
+        Self::check_vault_farm(&vault, vault_lp_token_account, &farm, farm_program_id, pool.spl_token_program_id)?;
+        let bump_seed = [vault.bump_seed];
+        let signer_seeds = vault.signer_seeds(&bump_seed);
+
+        // a zero deposit harvests the pending rewards
+        Self::invoke_vault_farm(
+            &farm,
+            vault_account,
+            vault_lp_token_account,
+            reward_token_account,
+            pool.spl_token_program_id,
+            false,
+            0,
+            &signer_seeds,
+        )?;
+        // rewards harvested by deposits and withdrawals since the last compound are charged now,
+        // the leftovers of the last compound were charged then
+        let reward_amount = Self::token_amount(reward_token_account)?;
+        let performance_fee = vault.performance_fee(vault.harvested_rewards(reward_amount))?;
+        if performance_fee > 0 {
+            invoke_signed(
+                &spl_token::instruction::transfer(
+                    pool.spl_token_program_id.key,
+                    reward_token_account.key,
+                    treasury.key,
+                    vault_account.key,
+                    &[],
+                    performance_fee,
+                )?,
+                &[reward_token_account.clone(), treasury.clone(), vault_account.clone()],
+                &[&signer_seeds],
+            )?;
+        }
+        let rewards = reward_amount.checked_sub(performance_fee).ok_or(AmmError::InvalidInput)?;
+
+        let (coin_reserve, pc_reserve) = pool.adapter().reserves()?;
+        let (swap_fee_numerator, swap_fee_denominator) = {
+            let amm = AmmInfo::load_amm_mut(pool.amm_account, true)?;
+            (amm.fees.swap_fee_numerator, amm.fees.swap_fee_denominator)
+        };
+        let reserve_in = if reward_is_coin { coin_reserve } else { pc_reserve };
+        let swap_amount = Self::zap_swap_amount(reserve_in, rewards, swap_fee_numerator, swap_fee_denominator)?;
+        if swap_amount > 0 {
+            // bounded by the quote at the reserves the crank found, the crank itself is permissionless
+            let minimum_amount_out = vault.minimum_swap_out(pool.adapter().quote(swap_amount, reward_is_coin)?)?;
+            Self::invoke_zap_swap(
+                &pool,
+                reward_token_account,
+                other_token_account,
+                vault_account,
+                swap_amount,
+                minimum_amount_out,
+                &[&signer_seeds],
+            )?;
+        }
+        // leftovers of earlier compounds are deposited along
+        let coin_amount = Self::token_amount(vault_coin_token_account)?;
+        let pc_amount = Self::token_amount(vault_pc_token_account)?;
+        if coin_amount > 0 && pc_amount > 0 {
+            Self::invoke_zap_deposit(
+                &pool,
+                vault_coin_token_account,
+                vault_pc_token_account,
+                vault_lp_token_account,
+                vault_account,
+                coin_amount,
+                pc_amount,
+                &[&signer_seeds],
+            )?;
+        }
+        let lp_amount = Self::token_amount(vault_lp_token_account)?;
+        if lp_amount < minimum_lp_amount {
+            return Err(AmmError::ExceededSlippage.into());
+        }
+        // read before staking, rewards the stake harvests are charged by the next compound
+        let reward_balance = Self::token_amount(reward_token_account)?;
+        if lp_amount > 0 {
+            Self::invoke_vault_farm(
+                &farm,
+                vault_account,
+                vault_lp_token_account,
+                reward_token_account,
+                pool.spl_token_program_id,
+                false,
+                lp_amount,
+                &signer_seeds,
+            )?;
+        }
+
+        vault.staked_lp = vault.staked_lp.checked_add(lp_amount).ok_or(AmmError::InvalidInput)?;
+        vault.reward_balance = reward_balance;
+        vault.last_compound_slot = Clock::get()?.slot;
+        LpVault::pack(vault, &mut vault_account.data.borrow_mut())
+    }
+
+    /// Reads the Raydium farm accounts of an lp vault
+    fn next_vault_farm<'a, 'b, I: Iterator<Item = &'a AccountInfo<'b>>>(
+        account_info_iter: &mut I,
+    ) -> Result<VaultFarm<'a, 'b>, ProgramError> {
+        Ok(VaultFarm {
+            farm_program_id: next_account_info(account_info_iter)?,
+            farm_account: next_account_info(account_info_iter)?,
+            farm_authority: next_account_info(account_info_iter)?,
+            farm_user_info: next_account_info(account_info_iter)?,
+            farm_lp_vault: next_account_info(account_info_iter)?,
+            farm_reward_vault: next_account_info(account_info_iter)?,
+            clock_sysvar: next_account_info(account_info_iter)?,
+        })
+    }
+
+    /// Checks the share mint and the vault token accounts of a deposit or withdraw
+    fn check_vault_accounts(
+        vault: &LpVault,
+        share_mint: &AccountInfo,
+        vault_lp_token_account: &AccountInfo,
+        vault_reward_token_account: &AccountInfo,
+        farm: &VaultFarm,
+        farm_program_id: &Pubkey,
+        spl_token_program_id: &AccountInfo,
+    ) -> ProgramResult {
+        let is_vault_token = *vault_reward_token_account.key == vault.coin_token
+            || *vault_reward_token_account.key == vault.pc_token;
+        if vault.share_mint != *share_mint.key
+            || !is_vault_token
+            || Self::token_mint(vault_reward_token_account)? != vault.reward_mint {
+            return Err(AmmError::InvalidVault.into());
+        }
+        Self::check_vault_farm(vault, vault_lp_token_account, farm, farm_program_id, spl_token_program_id)
+    }
+
+    /// Checks the farm and lp token account of the vault, and the token program it signs for
+    fn check_vault_farm(
+        vault: &LpVault,
+        vault_lp_token_account: &AccountInfo,
+        farm: &VaultFarm,
+        farm_program_id: &Pubkey,
+        spl_token_program_id: &AccountInfo,
+    ) -> ProgramResult {
+        if *spl_token_program_id.key != spl_token::id()
+            || farm.farm_program_id.key != farm_program_id
+            || farm.farm_account.owner != farm_program_id {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        if vault.lp_token != *vault_lp_token_account.key
+            || vault.farm != *farm.farm_account.key
+            || vault.farm_user_info != *farm.farm_user_info.key {
+            return Err(AmmError::InvalidVault.into());
+        }
+        Ok(())
+    }
+
+    /// Stakes or unstakes `amount` lp tokens of the vault in its farm, signed by the vault
+    fn invoke_vault_farm<'a>(
+        farm: &VaultFarm<'_, 'a>,
+        vault_account: &AccountInfo<'a>,
+        vault_lp_token_account: &AccountInfo<'a>,
+        vault_reward_token_account: &AccountInfo<'a>,
+        spl_token_program_id: &AccountInfo<'a>,
+        unstake: bool,
+        amount: u64,
+        signer_seeds: &[&[u8]],
+    ) -> ProgramResult {
+        let build = if unstake { crate::instruction::farm_withdraw } else { crate::instruction::farm_deposit };
+        let farm_tx = build(
+            farm.farm_program_id.key,
+            farm.farm_account.key,
+            farm.farm_authority.key,
+            farm.farm_user_info.key,
+            vault_account.key,
+            vault_lp_token_account.key,
+            farm.farm_lp_vault.key,
+            vault_reward_token_account.key,
+            farm.farm_reward_vault.key,
+            amount,
+        )?;
+        invoke_signed(
+            &farm_tx,
+            &[
+                farm.farm_account.clone(),
+                farm.farm_authority.clone(),
+                farm.farm_user_info.clone(),
+                vault_account.clone(),
+                vault_lp_token_account.clone(),
+                farm.farm_lp_vault.clone(),
+                vault_reward_token_account.clone(),
+                farm.farm_reward_vault.clone(),
+                farm.clock_sysvar.clone(),
+                spl_token_program_id.clone(),
+            ],
+            &[signer_seeds],
+        )
+    }
+
+    fn place_limit_order(
+        accounts: &[AccountInfo],
+        order: PlaceLimitOrderInstruction,
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let registry_account = next_account_info(account_info_iter)?;
+        let amm_account = next_account_info(account_info_iter)?;
+        let order_account = next_account_info(account_info_iter)?;
+        let input_escrow = next_account_info(account_info_iter)?;
+        let output_escrow = next_account_info(account_info_iter)?;
+        let source_mint = next_account_info(account_info_iter)?;
+        let destination_mint = next_account_info(account_info_iter)?;
+        let user_source_token_account = next_account_info(account_info_iter)?;
+        let user_destination_token_account = next_account_info(account_info_iter)?;
+        let user_owner_account = next_account_info(account_info_iter)?;
+        let system_program = next_account_info(account_info_iter)?;
+        let spl_token_program_id = next_account_info(account_info_iter)?;
+        let rent_sysvar = next_account_info(account_info_iter)?;
+
+        let registry = PoolRegistry::load(program_id, registry_account, amm_account.key)?;
+        registry.check_kind(PoolKind::RaydiumV4)?;
+        if registry.other_mint(source_mint.key) != Some(*destination_mint.key)
+            || Self::token_mint(user_destination_token_account)? != *destination_mint.key {
+            return Err(AmmError::InvalidMint.into());
+        }
+        if *spl_token_program_id.key != spl_token::id() {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        if order.amount_in == 0 || order.expiry_slot < Clock::get()?.slot {
+            return Err(AmmError::InvalidInput.into());
+        }
+
+        let (address, bump_seed) = LimitOrder::find_address(program_id, user_owner_account.key, order.order_id);
+        if address != *order_account.key {
+            return Err(AmmError::InvalidOrder.into());
+        }
+        let order_id = order.order_id.to_le_bytes();
+        Self::create_pda_account(
+            user_owner_account,
+            order_account,
+            system_program,
+            LimitOrder::LEN,
+            program_id,
+            &[LIMIT_ORDER_SEED, user_owner_account.key.as_ref(), &order_id, &[bump_seed]],
+        )?;
+        if order.keeper_tip_lamports > 0 {
+            invoke(
+                &system_instruction::transfer(user_owner_account.key, order_account.key, order.keeper_tip_lamports),
+                &[user_owner_account.clone(), order_account.clone(), system_program.clone()],
+            )?;
+        }
+        for (escrow, mint) in [(input_escrow, source_mint), (output_escrow, destination_mint)] {
+            Self::create_escrow(program_id, user_owner_account, escrow, mint, order_account, system_program, spl_token_program_id, rent_sysvar)?;
+        }
+        invoke(
+            &spl_token::instruction::transfer(
+                spl_token_program_id.key,
+                user_source_token_account.key,
+                input_escrow.key,
+                user_owner_account.key,
+                &[],
+                order.amount_in,
+            )?,
+            &[user_source_token_account.clone(), input_escrow.clone(), user_owner_account.clone()],
+        )?;
+
+        let limit_order = LimitOrder {
+            is_initialized: true,
+            bump_seed,
+            owner: *user_owner_account.key,
+            amm: *amm_account.key,
+            order_id: order.order_id,
+            input_escrow: *input_escrow.key,
+            output_escrow: *output_escrow.key,
+            destination: *user_destination_token_account.key,
+            amount_in: order.amount_in,
+            minimum_amount_out: order.minimum_amount_out,
+            expiry_slot: order.expiry_slot,
+            keeper_tip_lamports: order.keeper_tip_lamports,
+        };
+        LimitOrder::pack(limit_order, &mut order_account.data.borrow_mut())
+    }
+
+    fn execute_limit_order(
+        accounts: &[AccountInfo],
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let registry_account = next_account_info(account_info_iter)?;
+        let order_account = next_account_info(account_info_iter)?;
+        let input_escrow = next_account_info(account_info_iter)?;
+        let output_escrow = next_account_info(account_info_iter)?;
+        let pool = Self::next_zap_pool(account_info_iter)?;
+        let user_source_token_account = next_account_info(account_info_iter)?;
+        let user_destination_token_account = next_account_info(account_info_iter)?;
+        let user_owner_account = next_account_info(account_info_iter)?;
+        let keeper_account = next_account_info(account_info_iter)?;
+
+        let order = LimitOrder::load(program_id, order_account)?;
+        Self::check_order_accounts(&order, input_escrow, output_escrow, user_destination_token_account, user_owner_account)?;
+        if order.amm != *pool.amm_account.key {
+            return Err(AmmError::InvalidOrder.into());
+        }
+        // strays left in the input escrow are refunded there
+        if spl_token::state::Account::unpack(&user_source_token_account.data.borrow())?.owner != order.owner {
+            return Err(AmmError::InvalidOrder.into());
+        }
+        if !keeper_account.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+        if Clock::get()?.slot > order.expiry_slot {
+            return Err(AmmError::Expired.into());
+        }
+        if *pool.spl_token_program_id.key != spl_token::id() {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        Self::check_pool(program_id, registry_account, &pool)?;
+        let destination_mint = Self::token_mint(output_escrow)?;
+        let fees = Self::next_commission_accounts(program_id, &destination_mint, account_info_iter)?;
+
+        let order_id = order.order_id.to_le_bytes();
+        let bump_seed = [order.bump_seed];
+        let signer_seeds = order.signer_seeds(&order_id, &bump_seed);
+        // the minimum holds after the commission, Raydium can only check it before
+        Self::invoke_zap_swap(&pool, input_escrow, output_escrow, order_account, order.amount_in, order.minimum_amount_out, &[&signer_seeds])?;
+        let (fee, amount_out) = Self::order_commission(&fees, Self::token_amount(output_escrow)?)?;
+        if amount_out < order.minimum_amount_out {
+            return Err(AmmError::PriceNotReached.into());
+        }
+        Self::distribute_fee_signed(pool.spl_token_program_id, output_escrow, order_account, &fees, fee, &[&signer_seeds])?;
+
+        Self::close_escrow(pool.spl_token_program_id, input_escrow, user_source_token_account, user_owner_account, order_account, &signer_seeds)?;
+        Self::close_escrow(pool.spl_token_program_id, output_escrow, user_destination_token_account, user_owner_account, order_account, &signer_seeds)?;
+        Self::move_lamports(order_account, keeper_account, order.keeper_tip_lamports)?;
+        Self::close_program_account(order_account, user_owner_account)
+    }
+
+    fn cancel_limit_order(
+        accounts: &[AccountInfo],
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let order_account = next_account_info(account_info_iter)?;
+        let input_escrow = next_account_info(account_info_iter)?;
+        let output_escrow = next_account_info(account_info_iter)?;
+        let user_source_token_account = next_account_info(account_info_iter)?;
+        let user_destination_token_account = next_account_info(account_info_iter)?;
+        let user_owner_account = next_account_info(account_info_iter)?;
+        let spl_token_program_id = next_account_info(account_info_iter)?;
+
+        let order = LimitOrder::load(program_id, order_account)?;
+        Self::check_order_accounts(&order, input_escrow, output_escrow, user_destination_token_account, user_owner_account)?;
+        if !user_owner_account.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+        if *spl_token_program_id.key != spl_token::id() {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+
+        let order_id = order.order_id.to_le_bytes();
+        let bump_seed = [order.bump_seed];
+        let signer_seeds = order.signer_seeds(&order_id, &bump_seed);
+        Self::close_escrow(spl_token_program_id, input_escrow, user_source_token_account, user_owner_account, order_account, &signer_seeds)?;
+        Self::close_escrow(spl_token_program_id, output_escrow, user_destination_token_account, user_owner_account, order_account, &signer_seeds)?;
+        Self::close_program_account(order_account, user_owner_account)
+    }
+
+    fn create_dca(
+        accounts: &[AccountInfo],
+        schedule: CreateDcaInstruction,
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let registry_account = next_account_info(account_info_iter)?;
+        let amm_account = next_account_info(account_info_iter)?;
+        let schedule_account = next_account_info(account_info_iter)?;
+        let input_escrow = next_account_info(account_info_iter)?;
+        let output_escrow = next_account_info(account_info_iter)?;
+        let source_mint = next_account_info(account_info_iter)?;
+        let destination_mint = next_account_info(account_info_iter)?;
+        let user_source_token_account = next_account_info(account_info_iter)?;
+        let user_destination_token_account = next_account_info(account_info_iter)?;
+        let user_owner_account = next_account_info(account_info_iter)?;
+        let system_program = next_account_info(account_info_iter)?;
+        let spl_token_program_id = next_account_info(account_info_iter)?;
+        let rent_sysvar = next_account_info(account_info_iter)?;
+
+        let registry = PoolRegistry::load(program_id, registry_account, amm_account.key)?;
+        registry.check_kind(PoolKind::RaydiumV4)?;
+        if registry.other_mint(source_mint.key) != Some(*destination_mint.key)
+            || Self::token_mint(user_destination_token_account)? != *destination_mint.key {
+            return Err(AmmError::InvalidMint.into());
+        }
+        if *spl_token_program_id.key != spl_token::id() {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        if schedule.total_amount == 0 || schedule.amount_per_period == 0 || schedule.interval_slots == 0 {
+            return Err(AmmError::InvalidInput.into());
+        }
+
+        let (address, bump_seed) = DcaSchedule::find_address(program_id, user_owner_account.key, schedule.schedule_id);
+        if address != *schedule_account.key {
+            return Err(AmmError::InvalidOrder.into());
+        }
+        let schedule_id = schedule.schedule_id.to_le_bytes();
+        Self::create_pda_account(
+            user_owner_account,
+            schedule_account,
+            system_program,
+            DcaSchedule::LEN,
+            program_id,
+            &[DCA_SEED, user_owner_account.key.as_ref(), &schedule_id, &[bump_seed]],
+        )?;
+        for (escrow, mint) in [(input_escrow, source_mint), (output_escrow, destination_mint)] {
+            Self::create_escrow(program_id, user_owner_account, escrow, mint, schedule_account, system_program, spl_token_program_id, rent_sysvar)?;
+        }
+        invoke(
+            &spl_token::instruction::transfer(
+                spl_token_program_id.key,
+                user_source_token_account.key,
+                input_escrow.key,
+                user_owner_account.key,
+                &[],
+                schedule.total_amount,
+            )?,
+            &[user_source_token_account.clone(), input_escrow.clone(), user_owner_account.clone()],
+        )?;
+
+        let dca_schedule = DcaSchedule {
+            is_initialized: true,
+            bump_seed,
+            owner: *user_owner_account.key,
+            amm: *amm_account.key,
+            schedule_id: schedule.schedule_id,
+            input_escrow: *input_escrow.key,
+            output_escrow: *output_escrow.key,
+            destination: *user_destination_token_account.key,
+            amount_per_period: schedule.amount_per_period,
+            interval_slots: schedule.interval_slots,
+            minimum_amount_out: schedule.minimum_amount_out,
+            next_execution_slot: Clock::get()?.slot,
+            executions: 0,
+            total_amount_in: 0,
+            total_amount_out: 0,
+        };
+        DcaSchedule::pack(dca_schedule, &mut schedule_account.data.borrow_mut())
+    }
+
+    fn execute_dca(
+        accounts: &[AccountInfo],
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let registry_account = next_account_info(account_info_iter)?;
+        let schedule_account = next_account_info(account_info_iter)?;
+        let input_escrow = next_account_info(account_info_iter)?;
+        let output_escrow = next_account_info(account_info_iter)?;
+        let pool = Self::next_zap_pool(account_info_iter)?;
+        let user_destination_token_account = next_account_info(account_info_iter)?;
+
+        let mut schedule = DcaSchedule::load(program_id, schedule_account)?;
+        if schedule.input_escrow != *input_escrow.key
+            || schedule.output_escrow != *output_escrow.key
+            || schedule.destination != *user_destination_token_account.key
+            || schedule.amm != *pool.amm_account.key {
+            return Err(AmmError::InvalidOrder.into());
+        }
+        let slot = Clock::get()?.slot;
+        if slot < schedule.next_execution_slot {
+            return Err(AmmError::InvalidInput.into());
+        }
+        if *pool.spl_token_program_id.key != spl_token::id() {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        Self::check_pool(program_id, registry_account, &pool)?;
+        let destination_mint = Self::token_mint(output_escrow)?;
+        let fees = Self::next_commission_accounts(program_id, &destination_mint, account_info_iter)?;
+
+        let amount_in = schedule.amount_per_period.min(Self::token_amount(input_escrow)?);
+        if amount_in == 0 {
+            return Err(AmmError::InvalidInput.into());
+        }
+        let schedule_id = schedule.schedule_id.to_le_bytes();
+        let bump_seed = [schedule.bump_seed];
+        let signer_seeds = schedule.signer_seeds(&schedule_id, &bump_seed);
+        let minimum_amount_out = schedule.minimum_amount_out_for(amount_in)?;
+        let output_before = Self::token_amount(output_escrow)?;
+        Self::invoke_zap_swap(&pool, input_escrow, output_escrow, schedule_account, amount_in, minimum_amount_out, &[&signer_seeds])?;
+        let amount_out = Self::token_amount(output_escrow)?
+            .checked_sub(output_before)
+            .ok_or(AmmError::InvalidInput)?;
+        let (fee, amount_out) = Self::order_commission(&fees, amount_out)?;
+        if amount_out < minimum_amount_out {
+            return Err(AmmError::ExceededSlippage.into());
+        }
+        Self::distribute_fee_signed(pool.spl_token_program_id, output_escrow, schedule_account, &fees, fee, &[&signer_seeds])?;
+        invoke_signed(
+            &spl_token::instruction::transfer(
+                pool.spl_token_program_id.key,
+                output_escrow.key,
+                user_destination_token_account.key,
+                schedule_account.key,
+                &[],
+                amount_out,
+            )?,
+            &[output_escrow.clone(), user_destination_token_account.clone(), schedule_account.clone()],
+            &[&signer_seeds],
+        )?;
+
+        schedule.next_execution_slot = slot.checked_add(schedule.interval_slots).ok_or(AmmError::InvalidInput)?;
+        schedule.executions = schedule.executions.checked_add(1).ok_or(AmmError::InvalidInput)?;
+        schedule.total_amount_in = schedule.total_amount_in.checked_add(amount_in).ok_or(AmmError::InvalidInput)?;
+        schedule.total_amount_out = schedule.total_amount_out.checked_add(amount_out).ok_or(AmmError::InvalidInput)?;
+        DcaSchedule::pack(schedule, &mut schedule_account.data.borrow_mut())
+    }
+
+    fn close_dca(
+        accounts: &[AccountInfo],
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let schedule_account = next_account_info(account_info_iter)?;
+        let input_escrow = next_account_info(account_info_iter)?;
+        let output_escrow = next_account_info(account_info_iter)?;
+        let user_source_token_account = next_account_info(account_info_iter)?;
+        let user_destination_token_account = next_account_info(account_info_iter)?;
+        let user_owner_account = next_account_info(account_info_iter)?;
+        let spl_token_program_id = next_account_info(account_info_iter)?;
+
+        let schedule = DcaSchedule::load(program_id, schedule_account)?;
+        if schedule.input_escrow != *input_escrow.key
+            || schedule.output_escrow != *output_escrow.key
+            || schedule.destination != *user_destination_token_account.key
+            || schedule.owner != *user_owner_account.key {
+            return Err(AmmError::InvalidOrder.into());
+        }
+        if !user_owner_account.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+        if *spl_token_program_id.key != spl_token::id() {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+
+        let schedule_id = schedule.schedule_id.to_le_bytes();
+        let bump_seed = [schedule.bump_seed];
+        let signer_seeds = schedule.signer_seeds(&schedule_id, &bump_seed);
+        Self::close_escrow(spl_token_program_id, input_escrow, user_source_token_account, user_owner_account, schedule_account, &signer_seeds)?;
+        Self::close_escrow(spl_token_program_id, output_escrow, user_destination_token_account, user_owner_account, schedule_account, &signer_seeds)?;
+        Self::close_program_account(schedule_account, user_owner_account)
+    }
+
+    fn place_trigger_order(
+        accounts: &[AccountInfo],
+        order: PlaceTriggerOrderInstruction,
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let registry_account = next_account_info(account_info_iter)?;
+        let amm_account = next_account_info(account_info_iter)?;
+        let order_account = next_account_info(account_info_iter)?;
+        let input_escrow = next_account_info(account_info_iter)?;
+        let output_escrow = next_account_info(account_info_iter)?;
+        let source_mint = next_account_info(account_info_iter)?;
+        let destination_mint = next_account_info(account_info_iter)?;
+        let user_source_token_account = next_account_info(account_info_iter)?;
+        let user_destination_token_account = next_account_info(account_info_iter)?;
+        let user_owner_account = next_account_info(account_info_iter)?;
+        let system_program = next_account_info(account_info_iter)?;
+        let spl_token_program_id = next_account_info(account_info_iter)?;
+        let rent_sysvar = next_account_info(account_info_iter)?;
+
+        let registry = PoolRegistry::load(program_id, registry_account, amm_account.key)?;
+        registry.check_kind(PoolKind::RaydiumV4)?;
+        if registry.other_mint(source_mint.key) != Some(*destination_mint.key)
+            || Self::token_mint(user_destination_token_account)? != *destination_mint.key {
+            return Err(AmmError::InvalidMint.into());
+        }
+        if *spl_token_program_id.key != spl_token::id() {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        if order.amount_in == 0 || order.expiry_slot < Clock::get()?.slot {
+            return Err(AmmError::InvalidInput.into());
+        }
+
+        let (address, bump_seed) = TriggerOrder::find_address(program_id, user_owner_account.key, order.order_id);
+        if address != *order_account.key {
+            return Err(AmmError::InvalidOrder.into());
+        }
+        let trigger_order = TriggerOrder {
+            is_initialized: true,
+            bump_seed,
+            owner: *user_owner_account.key,
+            amm: *amm_account.key,
+            order_id: order.order_id,
+            input_escrow: *input_escrow.key,
+            output_escrow: *output_escrow.key,
+            destination: *user_destination_token_account.key,
+            amount_in: order.amount_in,
+            stop_amount_out: order.stop_amount_out,
+            take_profit_amount_out: order.take_profit_amount_out,
+            max_slippage_bps: order.max_slippage_bps,
+            expiry_slot: order.expiry_slot,
+            keeper_tip_lamports: order.keeper_tip_lamports,
+        };
+        trigger_order.validate()?;
+
+        let order_id = order.order_id.to_le_bytes();
+        Self::create_pda_account(
+            user_owner_account,
+            order_account,
+            system_program,
+            TriggerOrder::LEN,
+            program_id,
+            &[TRIGGER_ORDER_SEED, user_owner_account.key.as_ref(), &order_id, &[bump_seed]],
+        )?;
+        if order.keeper_tip_lamports > 0 {
+            invoke(
+                &system_instruction::transfer(user_owner_account.key, order_account.key, order.keeper_tip_lamports),
+                &[user_owner_account.clone(), order_account.clone(), system_program.clone()],
+            )?;
+        }
+        for (escrow, mint) in [(input_escrow, source_mint), (output_escrow, destination_mint)] {
+            Self::create_escrow(program_id, user_owner_account, escrow, mint, order_account, system_program, spl_token_program_id, rent_sysvar)?;
+        }
+        invoke(
+            &spl_token::instruction::transfer(
+                spl_token_program_id.key,
+                user_source_token_account.key,
+                input_escrow.key,
+                user_owner_account.key,
+                &[],
+                order.amount_in,
+            )?,
+            &[user_source_token_account.clone(), input_escrow.clone(), user_owner_account.clone()],
+        )?;
+
+        TriggerOrder::pack(trigger_order, &mut order_account.data.borrow_mut())
+    }
+
+    fn execute_trigger_order(
+        accounts: &[AccountInfo],
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let registry_account = next_account_info(account_info_iter)?;
+        let order_account = next_account_info(account_info_iter)?;
+        let input_escrow = next_account_info(account_info_iter)?;
+        let output_escrow = next_account_info(account_info_iter)?;
+        let pool = Self::next_zap_pool(account_info_iter)?;
+        let user_source_token_account = next_account_info(account_info_iter)?;
+        let user_destination_token_account = next_account_info(account_info_iter)?;
+        let user_owner_account = next_account_info(account_info_iter)?;
+        let keeper_account = next_account_info(account_info_iter)?;
+
+        let order = TriggerOrder::load(program_id, order_account)?;
+        if order.input_escrow != *input_escrow.key
+            || order.output_escrow != *output_escrow.key
+            || order.destination != *user_destination_token_account.key
+            || order.owner != *user_owner_account.key
+            || order.amm != *pool.amm_account.key {
+            return Err(AmmError::InvalidOrder.into());
+        }
+        // strays left in the input escrow are refunded there
+        if spl_token::state::Account::unpack(&user_source_token_account.data.borrow())?.owner != order.owner {
+            return Err(AmmError::InvalidOrder.into());
+        }
+        if !keeper_account.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+        if Clock::get()?.slot > order.expiry_slot {
+            return Err(AmmError::Expired.into());
+        }
+        if *pool.spl_token_program_id.key != spl_token::id() {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        Self::check_pool(program_id, registry_account, &pool)?;
+        let destination_mint = Self::token_mint(output_escrow)?;
+        let fees = Self::next_commission_accounts(program_id, &destination_mint, account_info_iter)?;
+
+        let (coin_reserve, pc_reserve) = pool.adapter().reserves()?;
+        let (reserve_in, reserve_out) = if Self::token_mint(input_escrow)? == Self::token_mint(pool.pool_token_coin)? {
+            (coin_reserve, pc_reserve)
+        } else {
+            (pc_reserve, coin_reserve)
+        };
+        let spot_amount_out = order.spot_amount_out(reserve_in, reserve_out)?;
+        let trigger_amount_out = order.crossed_trigger(spot_amount_out).ok_or(AmmError::PriceNotReached)?;
+
+        let order_id = order.order_id.to_le_bytes();
+        let bump_seed = [order.bump_seed];
+        let signer_seeds = order.signer_seeds(&order_id, &bump_seed);
+        let minimum_amount_out = order.minimum_amount_out(trigger_amount_out)?;
+        Self::invoke_zap_swap(&pool, input_escrow, output_escrow, order_account, order.amount_in, minimum_amount_out, &[&signer_seeds])?;
+        let (fee, amount_out) = Self::order_commission(&fees, Self::token_amount(output_escrow)?)?;
+        if amount_out < minimum_amount_out {
+            return Err(AmmError::ExceededSlippage.into());
+        }
+        Self::distribute_fee_signed(pool.spl_token_program_id, output_escrow, order_account, &fees, fee, &[&signer_seeds])?;
+
+        Self::close_escrow(pool.spl_token_program_id, input_escrow, user_source_token_account, user_owner_account, order_account, &signer_seeds)?;
+        Self::close_escrow(pool.spl_token_program_id, output_escrow, user_destination_token_account, user_owner_account, order_account, &signer_seeds)?;
+        Self::move_lamports(order_account, keeper_account, order.keeper_tip_lamports)?;
+        Self::close_program_account(order_account, user_owner_account)
+    }
+
+    fn cancel_trigger_order(
+        accounts: &[AccountInfo],
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let order_account = next_account_info(account_info_iter)?;
+        let input_escrow = next_account_info(account_info_iter)?;
+        let output_escrow = next_account_info(account_info_iter)?;
+        let user_source_token_account = next_account_info(account_info_iter)?;
+        let user_destination_token_account = next_account_info(account_info_iter)?;
+        let user_owner_account = next_account_info(account_info_iter)?;
+        let spl_token_program_id = next_account_info(account_info_iter)?;
+
+        let order = TriggerOrder::load(program_id, order_account)?;
+        if order.input_escrow != *input_escrow.key
+            || order.output_escrow != *output_escrow.key
+            || order.destination != *user_destination_token_account.key
+            || order.owner != *user_owner_account.key {
+            return Err(AmmError::InvalidOrder.into());
+        }
+        if !user_owner_account.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+        if *spl_token_program_id.key != spl_token::id() {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+
+        let order_id = order.order_id.to_le_bytes();
+        let bump_seed = [order.bump_seed];
+        let signer_seeds = order.signer_seeds(&order_id, &bump_seed);
+        Self::close_escrow(spl_token_program_id, input_escrow, user_source_token_account, user_owner_account, order_account, &signer_seeds)?;
+        Self::close_escrow(spl_token_program_id, output_escrow, user_destination_token_account, user_owner_account, order_account, &signer_seeds)?;
+        Self::close_program_account(order_account, user_owner_account)
+    }
+
+    /// Swaps on a serum market with an immediate-or-cancel order from the user open orders
+    /// account, settles it back to the user and takes the commission from the output
+    fn serum_swap(
+        accounts: &[AccountInfo],
+        swap: SerumSwapInstruction,
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let registry_account = next_account_info(account_info_iter)?;
+        let dex_program_id = next_account_info(account_info_iter)?;
+        let market_account = next_account_info(account_info_iter)?;
+        let open_orders = next_account_info(account_info_iter)?;
+        let request_queue = next_account_info(account_info_iter)?;
+        let event_queue = next_account_info(account_info_iter)?;
+        let bids = next_account_info(account_info_iter)?;
+        let asks = next_account_info(account_info_iter)?;
+        let coin_vault = next_account_info(account_info_iter)?;
+        let pc_vault = next_account_info(account_info_iter)?;
+        let vault_signer = next_account_info(account_info_iter)?;
+        let user_source_token_account = next_account_info(account_info_iter)?;
+        let user_destination_token_account = next_account_info(account_info_iter)?;
+        let user_owner_account = next_account_info(account_info_iter)?;
+        let spl_token_program_id = next_account_info(account_info_iter)?;
+        let _rent_sysvar = next_account_info(account_info_iter)?;
+        if !user_owner_account.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+        if *spl_token_program_id.key != spl_token::id() {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+
+        let registry = PoolRegistry::load(program_id, registry_account, market_account.key)?;
+        registry.check_kind(PoolKind::SerumMarket)?;
+        let source_mint = Self::token_mint(user_source_token_account)?;
+        let destination_mint = registry.other_mint(&source_mint).ok_or(AmmError::InvalidMint)?;
+        if Self::token_mint(user_destination_token_account)? != destination_mint {
+            return Err(AmmError::InvalidMint.into());
+        }
+        let (side, coin_lot_size) = {
+            let market = MarketState::load(dex_program_id.key, market_account)?;
+            if market.req_q != *request_queue.key
+                || market.event_q != *event_queue.key
+                || market.bids != *bids.key
+                || market.asks != *asks.key
+                || market.coin_vault != *coin_vault.key
+                || market.pc_vault != *pc_vault.key {
+                return Err(AmmError::InvalidInput.into());
+            }
+            let side = if source_mint == market.coin_mint && destination_mint == market.pc_mint {
+                SerumSide::Ask
+            } else if source_mint == market.pc_mint && destination_mint == market.coin_mint {
+                SerumSide::Bid
+            } else {
+                return Err(AmmError::InvalidMint.into());
+            };
+            (side, market.coin_lot_size)
+        };
+        // settling also pays out what the open orders held before, only the order fill is the output
+        let free_before = {
+            let open_orders = OpenOrders::load(dex_program_id.key, open_orders)?;
+            open_orders.check(market_account.key, user_owner_account.key)?;
+            open_orders.free_proceeds(side)
+        };
+        check_serum_vault_signer(dex_program_id, market_account, vault_signer)?;
+        let fees = Self::next_fee_accounts(program_id, &destination_mint, account_info_iter)?;
+
+        // an ask sells whole coin lots of the input, a bid spends the input as pc
+        let (coin_wallet, pc_wallet, max_coin_qty, max_native_pc_qty) = match side {
+            SerumSide::Ask => {
+                let max_coin_qty = swap.amount_in.checked_div(coin_lot_size).ok_or(AmmError::InvalidInput)?;
+                (user_source_token_account, user_destination_token_account, max_coin_qty, u64::MAX)
+            },
+            SerumSide::Bid => (user_destination_token_account, user_source_token_account, u64::MAX, swap.amount_in),
+        };
+        let destination_before = Self::token_amount(user_destination_token_account)?;
+        let order_tx = crate::instruction::serum_new_order_v3(
+            dex_program_id.key,
+            market_account.key,
+            open_orders.key,
+            request_queue.key,
+            event_queue.key,
+            bids.key,
+            asks.key,
+            user_source_token_account.key,
+            user_owner_account.key,
+            coin_vault.key,
+            pc_vault.key,
+            side,
+            swap.limit_price,
+            max_coin_qty,
+            max_native_pc_qty,
+        )?;
+        invoke(&order_tx, accounts)?;
+        let settle_tx = crate::instruction::serum_settle_funds(
+            dex_program_id.key,
+            market_account.key,
+            open_orders.key,
+            user_owner_account.key,
+            coin_vault.key,
+            pc_vault.key,
+            coin_wallet.key,
+            pc_wallet.key,
+            vault_signer.key,
+        )?;
+        invoke(&settle_tx, accounts)?;
+
+        let amount_out = Self::token_amount(user_destination_token_account)?
+            .checked_sub(destination_before)
+            .and_then(|settled| settled.checked_sub(free_before))
+            .ok_or(AmmError::InvalidInput)?;
+        let (fee, amount_out) = Self::order_commission(&fees, amount_out)?;
+        if amount_out < swap.minimum_amount_out {
+            return Err(AmmError::ExceededSlippage.into());
+        }
+        Self::distribute_fee(spl_token_program_id, user_destination_token_account, user_owner_account, &fees, fee)?;
+        Self::collect_flat_fee(user_owner_account, &fees)
+    }
+
+    /// Creates a Raydium pool of two mints with a fee config from the liquidity of the creator,
+    /// who pays the listing fee
+    fn create_pool(
+        accounts: &[AccountInfo],
+        pool: CreatePoolInstruction,
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let config_account = next_account_info(account_info_iter)?;
+        let raydium_program_id = next_account_info(account_info_iter)?;
+        let spl_token_program_id = next_account_info(account_info_iter)?;
+        let associated_token_program = next_account_info(account_info_iter)?;
+        let system_program = next_account_info(account_info_iter)?;
+        let rent_sysvar = next_account_info(account_info_iter)?;
+        let amm_account = next_account_info(account_info_iter)?;
+        let amm_authority = next_account_info(account_info_iter)?;
+        let amm_open_orders = next_account_info(account_info_iter)?;
+        let pool_lp_mint = next_account_info(account_info_iter)?;
+        let coin_mint = next_account_info(account_info_iter)?;
+        let pc_mint = next_account_info(account_info_iter)?;
+        let pool_token_coin = next_account_info(account_info_iter)?;
+        let pool_token_pc = next_account_info(account_info_iter)?;
+        let amm_target_orders = next_account_info(account_info_iter)?;
+        let amm_config = next_account_info(account_info_iter)?;
+        let create_fee_destination = next_account_info(account_info_iter)?;
+        let serum_program_id = next_account_info(account_info_iter)?;
+        let serum_market = next_account_info(account_info_iter)?;
+        let creator = next_account_info(account_info_iter)?;
+        let creator_coin_token_account = next_account_info(account_info_iter)?;
+        let creator_pc_token_account = next_account_info(account_info_iter)?;
+        let creator_lp_token_account = next_account_info(account_info_iter)?;
+        let coin_fee_config_account = next_account_info(account_info_iter)?;
+        let pc_fee_config_account = next_account_info(account_info_iter)?;
+        let registry_account = next_account_info(account_info_iter)?;
+
+        let config = ProviderConfig::load(program_id, config_account)?;
+        config.check_not_paused(Operation::Deposit)?;
+        if !creator.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+        if *spl_token_program_id.key != spl_token::id()
+            || *associated_token_program.key != spl_associated_token_account::id() {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        if *rent_sysvar.key != sysvar::rent::id() {
+            return Err(ProgramError::InvalidArgument);
+        }
+        // Raydium creates the associated lp token account of the creator for the initial lp tokens
+        if *creator_lp_token_account.key != get_associated_token_address(creator.key, pool_lp_mint.key) {
+            return Err(AmmError::InvalidInput.into());
+        }
+        if pool.init_coin_amount == 0 || pool.init_pc_amount == 0 || coin_mint.key == pc_mint.key {
+            return Err(AmmError::InvalidInput.into());
+        }
+        // only mints the provider has a fee config for are listed
+        FeeConfig::load(program_id, coin_fee_config_account, coin_mint.key)?;
+        FeeConfig::load(program_id, pc_fee_config_account, pc_mint.key)?;
+        check_raydium_authority(raydium_program_id.key, u64::from(pool.nonce), amm_authority)?;
+
+        if config.listing_fee_lamports > 0 {
+            let listing_treasury = next_account_info(account_info_iter)?;
+            if *listing_treasury.key != config.listing_treasury {
+                return Err(AmmError::InvalidFeeReceiver.into());
+            }
+            invoke(
+                &system_instruction::transfer(creator.key, listing_treasury.key, config.listing_fee_lamports),
+                &[creator.clone(), listing_treasury.clone(), system_program.clone()],
+            )?;
+        }
+        // listed disabled, the admin reviews the pool before enabling swaps through it
+        let (address, bump_seed) = PoolRegistry::find_address(program_id, amm_account.key);
+        if address != *registry_account.key || !registry_account.data_is_empty() {
+            return Err(AmmError::InvalidConfig.into());
+        }
+        Self::create_pda_account(
+            creator,
+            registry_account,
+            system_program,
+            PoolRegistry::LEN,
+            program_id,
+            &[POOL_REGISTRY_SEED, amm_account.key.as_ref(), &[bump_seed]],
+        )?;
+        let registry = PoolRegistry {
+            is_initialized: true,
+            bump_seed,
+            amm: *amm_account.key,
+            coin_mint: *coin_mint.key,
+            pc_mint: *pc_mint.key,
+            enabled: false,
+            kind: PoolKind::RaydiumV4,
+        };
+        PoolRegistry::pack(registry, &mut registry_account.data.borrow_mut())?;
+
+        // Raydium moves the initial liquidity from the creator and prices the pool at its ratio
+        let initialize_tx = crate::instruction::initialize2(
+            raydium_program_id.key,
+            amm_account.key,
+            amm_open_orders.key,
+            pool_lp_mint.key,
+            coin_mint.key,
+            pc_mint.key,
+            pool_token_coin.key,
+            pool_token_pc.key,
+            amm_target_orders.key,
+            amm_config.key,
+            create_fee_destination.key,
+            serum_program_id.key,
+            serum_market.key,
+            creator.key,
+            creator_coin_token_account.key,
+            creator_pc_token_account.key,
+            pool.nonce,
+            pool.open_time,
+            pool.init_pc_amount,
+            pool.init_coin_amount,
+        )?;
+        invoke(&initialize_tx, accounts)
+    }
+
+    fn set_listing_fee(
+        accounts: &[AccountInfo],
+        listing_fee_lamports: u64,
+        treasury: Pubkey,
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let config_account = next_account_info(account_info_iter)?;
+        let admin = next_account_info(account_info_iter)?;
+
+        let mut config = ProviderConfig::load(program_id, config_account)?;
+        Self::check_admin(&config, admin)?;
+        config.listing_fee_lamports = listing_fee_lamports;
+        config.listing_treasury = treasury;
+        ProviderConfig::pack(config, &mut config_account.data.borrow_mut())
+    }
+
+    fn set_farm_program(
+        accounts: &[AccountInfo],
+        farm_program_id: Pubkey,
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let config_account = next_account_info(account_info_iter)?;
+        let admin = next_account_info(account_info_iter)?;
+
+        let mut config = ProviderConfig::load(program_id, config_account)?;
+        Self::check_admin(&config, admin)?;
+        config.farm_program_id = farm_program_id;
+        ProviderConfig::pack(config, &mut config_account.data.borrow_mut())
+    }
+
+    /// Checks the escrows, destination and owner passed with a limit order
+    fn check_order_accounts(
+        order: &LimitOrder,
+        input_escrow: &AccountInfo,
+        output_escrow: &AccountInfo,
+        user_destination_token_account: &AccountInfo,
+        user_owner_account: &AccountInfo,
+    ) -> ProgramResult {
+        if order.input_escrow != *input_escrow.key
+            || order.output_escrow != *output_escrow.key
+            || order.destination != *user_destination_token_account.key
+            || order.owner != *user_owner_account.key {
+            return Err(AmmError::InvalidOrder.into());
+        }
+        Ok(())
+    }
+
+    /// Creates the `[ESCROW_SEED, order, mint]` token account owned by the order PDA
+    fn create_escrow<'a>(
+        program_id: &Pubkey,
+        payer: &AccountInfo<'a>,
+        escrow: &AccountInfo<'a>,
+        mint: &AccountInfo<'a>,
+        order_account: &AccountInfo<'a>,
+        system_program: &AccountInfo<'a>,
+        spl_token_program_id: &AccountInfo<'a>,
+        rent_sysvar: &AccountInfo<'a>,
+    ) -> ProgramResult {
+        let (address, bump_seed) = Pubkey::find_program_address(
+            &[ESCROW_SEED, order_account.key.as_ref(), mint.key.as_ref()],
+            program_id,
+        );
+        if address != *escrow.key {
+            return Err(AmmError::InvalidOrder.into());
+        }
+        Self::create_pda_account(
+            payer,
+            escrow,
+            system_program,
+            spl_token::state::Account::LEN,
+            spl_token_program_id.key,
+            &[ESCROW_SEED, order_account.key.as_ref(), mint.key.as_ref(), &[bump_seed]],
+        )?;
+        invoke(
+            &spl_token::instruction::initialize_account(
+                spl_token_program_id.key,
+                escrow.key,
+                mint.key,
+                order_account.key,
+            )?,
+            &[escrow.clone(), mint.clone(), order_account.clone(), rent_sysvar.clone()],
+        )
+    }
+
+    /// Moves what is left in an escrow to `refund_token_account` and closes it,
+    /// returning its rent to `owner_account`
+    fn close_escrow<'a>(
+        spl_token_program_id: &AccountInfo<'a>,
+        escrow: &AccountInfo<'a>,
+        refund_token_account: &AccountInfo<'a>,
+        owner_account: &AccountInfo<'a>,
+        order_account: &AccountInfo<'a>,
+        signer_seeds: &[&[u8]],
+    ) -> ProgramResult {
+        let balance = Self::token_amount(escrow)?;
+        if balance > 0 {
+            invoke_signed(
+                &spl_token::instruction::transfer(
+                    spl_token_program_id.key,
+                    escrow.key,
+                    refund_token_account.key,
+                    order_account.key,
+                    &[],
+                    balance,
+                )?,
+                &[escrow.clone(), refund_token_account.clone(), order_account.clone()],
+                &[signer_seeds],
+            )?;
+        }
+        invoke_signed(
+            &spl_token::instruction::close_account(
+                spl_token_program_id.key,
+                escrow.key,
+                owner_account.key,
+                order_account.key,
+                &[],
+            )?,
+            &[escrow.clone(), owner_account.clone(), order_account.clone()],
+            &[signer_seeds],
+        )
+    }
+
+    /// Moves lamports out of an account owned by this program
+    fn move_lamports(from: &AccountInfo, to: &AccountInfo, lamports: u64) -> ProgramResult {
+        **from.try_borrow_mut_lamports()? = from.lamports().checked_sub(lamports).ok_or(AmmError::InvalidInput)?;
+        **to.try_borrow_mut_lamports()? = to.lamports().checked_add(lamports).ok_or(AmmError::InvalidInput)?;
+        Ok(())
+    }
+
+    /// Empties an account owned by this program and sends all its lamports to `recipient`
+    fn close_program_account(account: &AccountInfo, recipient: &AccountInfo) -> ProgramResult {
+        Self::move_lamports(account, recipient, account.lamports())?;
+        account.try_borrow_mut_data()?.fill(0);
+        Ok(())
+    }
+
+    fn zap_in(
+        accounts: &[AccountInfo],
+        amount_in: u64,
+        minimum_lp_amount: u64,
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let registry_account = next_account_info(account_info_iter)?;
+        let pool = Self::next_zap_pool(account_info_iter)?;
+        let user_source_token_account = next_account_info(account_info_iter)?;
+        let user_other_token_account = next_account_info(account_info_iter)?;
+        let user_lp_token_account = next_account_info(account_info_iter)?;
+        let user_owner_account = next_account_info(account_info_iter)?;
+        let registry = Self::check_pool(program_id, registry_account, &pool)?;
+        let source_mint = Self::token_mint(user_source_token_account)?;
+        let other_mint = registry.other_mint(&source_mint).ok_or(AmmError::InvalidMint)?;
+        if Self::token_mint(user_other_token_account)? != other_mint {
+            return Err(AmmError::InvalidMint.into());
+        }
+        let source_is_coin = source_mint == registry.coin_mint;
+        let fees = Self::next_fee_accounts(program_id, &source_mint, account_info_iter)?;
+
+        // the commission is charged once, on the whole input
+        let fee = fees.commission(amount_in)?;
+        Self::distribute_fee(pool.spl_token_program_id, user_source_token_account, user_owner_account, &fees, fee)?;
+        let amount_in = amount_in.checked_sub(fee).ok_or(AmmError::InvalidInput)?;
+
+        let (coin_reserve, pc_reserve) = pool.adapter().reserves()?;
+        let (swap_fee_numerator, swap_fee_denominator) = {
+            let amm = AmmInfo::load_amm_mut(pool.amm_account, true)?;
+            (amm.fees.swap_fee_numerator, amm.fees.swap_fee_denominator)
+        };
+        let reserve_in = if source_is_coin { coin_reserve } else { pc_reserve };
+        let swap_amount = Self::zap_swap_amount(reserve_in, amount_in, swap_fee_numerator, swap_fee_denominator)?;
+
+        let other_before = Self::token_amount(user_other_token_account)?;
+        Self::invoke_zap_swap(&pool, user_source_token_account, user_other_token_account, user_owner_account, swap_amount, 0, &[])?;
+        let other_amount = Self::token_amount(user_other_token_account)?
+            .checked_sub(other_before)
+            .ok_or(AmmError::InvalidInput)?;
+        let source_amount = amount_in.checked_sub(swap_amount).ok_or(AmmError::InvalidInput)?;
 
-        match instruction {
-            AmmInstruction::Swap(SwapInstruction {amount_in, minimum_amount_out}) => {
-                msg!("Instruction: SwapInstruction");
-                Self::swap(accounts, amount_in, minimum_amount_out, program_id)
-            },
-            AmmInstruction::Deposit(DepositInstruction {max_coin_amount, max_pc_amount, base_side}) => {
-                msg!("Instruction: DepositInstruction");
-                Self::deposit(accounts, max_coin_amount, max_pc_amount, base_side, program_id)
-            },
+        let (user_coin_token_account, user_pc_token_account, coin_amount, pc_amount) = if source_is_coin {
+            (user_source_token_account, user_other_token_account, source_amount, other_amount)
+        } else {
+            (user_other_token_account, user_source_token_account, other_amount, source_amount)
+        };
+        let lp_before = Self::token_amount(user_lp_token_account)?;
+        Self::invoke_zap_deposit(
+            &pool,
+            user_coin_token_account,
+            user_pc_token_account,
+            user_lp_token_account,
+            user_owner_account,
+            coin_amount,
+            pc_amount,
+            &[],
+        )?;
+        let lp_amount = Self::token_amount(user_lp_token_account)?
+            .checked_sub(lp_before)
+            .ok_or(AmmError::InvalidInput)?;
+        if lp_amount < minimum_lp_amount {
+            return Err(AmmError::ExceededSlippage.into());
         }
+        Self::collect_flat_fee(user_owner_account, &fees)
     }
 
-    fn deposit(
+    fn zap_out(
         accounts: &[AccountInfo],
-        max_coin_amount: u64, 
-        max_pc_amount: u64, 
-        base_side: u64,
+        amount: u64,
+        minimum_amount_out: u64,
         program_id: &Pubkey,
     ) -> ProgramResult {
         let account_info_iter = &mut accounts.iter();
-        let raydium_program_id = next_account_info(account_info_iter)?;
-
-        let spl_token_program_id = next_account_info(account_info_iter)?;
-        let amm_account = next_account_info(account_info_iter)?;
-        let authority = next_account_info(account_info_iter)?;
-        let amm_open_orders = next_account_info(account_info_iter)?;
-        let amm_target_orders = next_account_info(account_info_iter)?;
-        let pool_lp_mint = next_account_info(account_info_iter)?;
-        let pool_token_coin = next_account_info(account_info_iter)?;
-        let pool_token_pc = next_account_info(account_info_iter)?;
-        let serum_market = next_account_info(account_info_iter)?;
-        let user_coin_token_base_account = next_account_info(account_info_iter)?;
-        let user_pc_token_base_account = next_account_info(account_info_iter)?;
-        let user_lp_token_base_account = next_account_info(account_info_iter)?;
+        let registry_account = next_account_info(account_info_iter)?;
+        let pool = Self::next_zap_pool(account_info_iter)?;
+        let pool_withdraw_queue = next_account_info(account_info_iter)?;
+        let pool_temp_lp = next_account_info(account_info_iter)?;
+        let user_lp_token_account = next_account_info(account_info_iter)?;
+        let user_destination_token_account = next_account_info(account_info_iter)?;
+        let user_other_token_account = next_account_info(account_info_iter)?;
         let user_owner_account = next_account_info(account_info_iter)?;
-        let fee_receiver_first = next_account_info(account_info_iter)?;
-        let fee_receiver_second = next_account_info(account_info_iter)?;
-        let fee_first = max_coin_amount
-            .checked_mul(1).unwrap()
-            .checked_div(10).unwrap();
-
-        let fee_second = max_pc_amount
-            .checked_mul(1).unwrap()
-            .checked_div(10).unwrap();
-
-        let final_amount_coin = max_coin_amount.checked_sub(fee_first).unwrap();
-        let final_amount_pc = max_pc_amount.checked_sub(fee_second).unwrap();
+        let registry = Self::check_pool(program_id, registry_account, &pool)?;
+        let destination_mint = Self::token_mint(user_destination_token_account)?;
+        let other_mint = registry.other_mint(&destination_mint).ok_or(AmmError::InvalidMint)?;
+        if Self::token_mint(user_other_token_account)? != other_mint {
+            return Err(AmmError::InvalidMint.into());
+        }
+        let (user_coin_token_account, user_pc_token_account) = if destination_mint == registry.coin_mint {
+            (user_destination_token_account, user_other_token_account)
+        } else {
+            (user_other_token_account, user_destination_token_account)
+        };
+        let fees = Self::next_fee_accounts(program_id, &destination_mint, account_info_iter)?;
 
-        let deposit_tx = crate::instruction::deposit(
-            raydium_program_id.key,
-            amm_account.key,
-            authority.key,
-            amm_open_orders.key,
-            amm_target_orders.key,
-            pool_lp_mint.key,
-            pool_token_coin.key,
-            pool_token_pc.key,
-            serum_market.key,
-            user_coin_token_base_account.key,
-            user_pc_token_base_account.key,
-            user_lp_token_base_account.key,
+        let destination_before = Self::token_amount(user_destination_token_account)?;
+        let other_before = Self::token_amount(user_other_token_account)?;
+        let (amm_nonce, vault_signer_nonce) = Self::pool_nonces(&pool)?;
+        let withdraw_tx = crate::instruction::withdraw(
+            pool.raydium_program_id.key,
+            pool.amm_account.key,
+            amm_nonce,
+            pool.amm_open_orders.key,
+            pool.amm_target_orders.key,
+            pool.pool_lp_mint.key,
+            pool.pool_token_coin.key,
+            pool.pool_token_pc.key,
+            pool_withdraw_queue.key,
+            pool_temp_lp.key,
+            pool.serum_dex_program_id.key,
+            pool.serum_market.key,
+            pool.serum_coin_vault_account.key,
+            pool.serum_pc_vault_account.key,
+            vault_signer_nonce,
+            user_lp_token_account.key,
+            user_coin_token_account.key,
+            user_pc_token_account.key,
             user_owner_account.key,
-            final_amount_coin, 
-            final_amount_pc, 
-            base_side,
+            pool.serum_event_queue.key,
+            pool.serum_bids.key,
+            pool.serum_asks.key,
+            amount,
         )?;
         invoke(
-            &deposit_tx, 
+            &withdraw_tx,
             &[
-                spl_token_program_id.clone(),
-                amm_account.clone(),
-                authority.clone(),
-                amm_open_orders.clone(),
-                amm_target_orders.clone(),
-                pool_lp_mint.clone(),
-                pool_token_coin.clone(),
-                pool_token_pc.clone(),
-                serum_market.clone(),
-                user_coin_token_base_account.clone(),
-                user_pc_token_base_account.clone(),
-                user_lp_token_base_account.clone(),
+                pool.spl_token_program_id.clone(),
+                pool.amm_account.clone(),
+                pool.authority.clone(),
+                pool.amm_open_orders.clone(),
+                pool.amm_target_orders.clone(),
+                pool.pool_lp_mint.clone(),
+                pool.pool_token_coin.clone(),
+                pool.pool_token_pc.clone(),
+                pool_withdraw_queue.clone(),
+                pool_temp_lp.clone(),
+                pool.serum_dex_program_id.clone(),
+                pool.serum_market.clone(),
+                pool.serum_coin_vault_account.clone(),
+                pool.serum_pc_vault_account.clone(),
+                pool.serum_vault_signer.clone(),
+                user_lp_token_account.clone(),
+                user_coin_token_account.clone(),
+                user_pc_token_account.clone(),
                 user_owner_account.clone(),
-             ]
+                pool.serum_event_queue.clone(),
+                pool.serum_bids.clone(),
+                pool.serum_asks.clone(),
+            ],
         )?;
 
-        let tx_to_receive_first = spl_token::instruction::transfer(
-            spl_token_program_id.key, 
-            user_coin_token_base_account.key, 
-            fee_receiver_first.key,
-            user_owner_account.key,
-            &[],
-            fee_first,
-        )?;
+        let other_amount = Self::token_amount(user_other_token_account)?
+            .checked_sub(other_before)
+            .ok_or(AmmError::InvalidInput)?;
+        if other_amount > 0 {
+            Self::invoke_zap_swap(&pool, user_other_token_account, user_destination_token_account, user_owner_account, other_amount, 0, &[])?;
+        }
+        // the commission is charged once, on everything received in the chosen token
+        let amount_out = Self::token_amount(user_destination_token_account)?
+            .checked_sub(destination_before)
+            .ok_or(AmmError::InvalidInput)?;
+        let fee = fees.commission(amount_out)?;
+        if amount_out.checked_sub(fee).ok_or(AmmError::InvalidInput)? < minimum_amount_out {
+            return Err(AmmError::ExceededSlippage.into());
+        }
+        Self::distribute_fee(pool.spl_token_program_id, user_destination_token_account, user_owner_account, &fees, fee)?;
+        Self::collect_flat_fee(user_owner_account, &fees)
+    }
 
-        let tx_to_receive_second = spl_token::instruction::transfer(
-            spl_token_program_id.key, 
-            user_pc_token_base_account.key, 
-            fee_receiver_second.key,
-            user_owner_account.key,
-            &[],
-            fee_second,
-        )?;
-        invoke(
-            &tx_to_receive_first,
+    /// Reads the Raydium pool and serum market accounts of a zap
+    fn next_zap_pool<'a, 'b, I: Iterator<Item = &'a AccountInfo<'b>>>(
+        account_info_iter: &mut I,
+    ) -> Result<ZapPool<'a, 'b>, ProgramError> {
+        Ok(ZapPool {
+            raydium_program_id: next_account_info(account_info_iter)?,
+            spl_token_program_id: next_account_info(account_info_iter)?,
+            amm_account: next_account_info(account_info_iter)?,
+            authority: next_account_info(account_info_iter)?,
+            amm_open_orders: next_account_info(account_info_iter)?,
+            amm_target_orders: next_account_info(account_info_iter)?,
+            pool_lp_mint: next_account_info(account_info_iter)?,
+            pool_token_coin: next_account_info(account_info_iter)?,
+            pool_token_pc: next_account_info(account_info_iter)?,
+            serum_dex_program_id: next_account_info(account_info_iter)?,
+            serum_market: next_account_info(account_info_iter)?,
+            serum_bids: next_account_info(account_info_iter)?,
+            serum_asks: next_account_info(account_info_iter)?,
+            serum_event_queue: next_account_info(account_info_iter)?,
+            serum_coin_vault_account: next_account_info(account_info_iter)?,
+            serum_pc_vault_account: next_account_info(account_info_iter)?,
+            serum_vault_signer: next_account_info(account_info_iter)?,
+        })
+    }
+
+    /// Swaps `amount_in` through the Raydium v4 adapter of the zap pool, Raydium fails below
+    /// `minimum_amount_out`. Zaps pass 0 and check the amount they end up with instead
+    #[allow(clippy::too_many_arguments)]
+    fn invoke_zap_swap<'a>(
+        pool: &ZapPool<'_, 'a>,
+        user_source_token_account: &AccountInfo<'a>,
+        user_destination_token_account: &AccountInfo<'a>,
+        user_owner_account: &AccountInfo<'a>,
+        amount_in: u64,
+        minimum_amount_out: u64,
+        signer_seeds: &[&[&[u8]]],
+    ) -> ProgramResult {
+        let user = SwapAccounts {
+            source: user_source_token_account,
+            destination: user_destination_token_account,
+            owner: user_owner_account,
+        };
+        let instruction = pool.adapter().swap_instruction(&user, amount_in, minimum_amount_out)?;
+        invoke_signed(
+            &instruction,
             &[
-                user_coin_token_base_account.clone(),
-                fee_receiver_first.clone(),
+                pool.spl_token_program_id.clone(),
+                pool.amm_account.clone(),
+                pool.authority.clone(),
+                pool.amm_open_orders.clone(),
+                pool.amm_target_orders.clone(),
+                pool.pool_token_coin.clone(),
+                pool.pool_token_pc.clone(),
+                pool.serum_dex_program_id.clone(),
+                pool.serum_market.clone(),
+                pool.serum_bids.clone(),
+                pool.serum_asks.clone(),
+                pool.serum_event_queue.clone(),
+                pool.serum_coin_vault_account.clone(),
+                pool.serum_pc_vault_account.clone(),
+                pool.serum_vault_signer.clone(),
+                user_source_token_account.clone(),
+                user_destination_token_account.clone(),
                 user_owner_account.clone(),
             ],
-        )?;
-        invoke(
-            &tx_to_receive_second,
+            signer_seeds,
+        )
+    }
+
+    /// Deposits as much of `coin_amount` and `pc_amount` as the pool ratio allows through the
+    /// Raydium v4 adapter of the zap pool, taking the whole coin amount as base if the pc amount covers it
+    fn invoke_zap_deposit<'a>(
+        pool: &ZapPool<'_, 'a>,
+        user_coin_token_account: &AccountInfo<'a>,
+        user_pc_token_account: &AccountInfo<'a>,
+        user_lp_token_account: &AccountInfo<'a>,
+        user_owner_account: &AccountInfo<'a>,
+        coin_amount: u64,
+        pc_amount: u64,
+        signer_seeds: &[&[&[u8]]],
+    ) -> ProgramResult {
+        let (coin_reserve, pc_reserve) = pool.adapter().reserves()?;
+        let pc_for_coin = to_u128(coin_amount)?
+            .checked_mul(to_u128(pc_reserve)?)
+            .ok_or(AmmError::InvalidInput)?
+            .checked_div(to_u128(coin_reserve)?)
+            .ok_or(AmmError::InvalidInput)?;
+        let base_side = if pc_for_coin <= to_u128(pc_amount)? { 0 } else { 1 };
+
+        let user = DepositAccounts {
+            coin: user_coin_token_account,
+            pc: user_pc_token_account,
+            lp: user_lp_token_account,
+            owner: user_owner_account,
+        };
+        let deposit_tx = pool.adapter().deposit_instruction(&user, coin_amount, pc_amount, base_side)?;
+        invoke_signed(
+            &deposit_tx,
             &[
-                user_pc_token_base_account.clone(),
-                fee_receiver_second.clone(),
+                pool.spl_token_program_id.clone(),
+                pool.amm_account.clone(),
+                pool.authority.clone(),
+                pool.amm_open_orders.clone(),
+                pool.amm_target_orders.clone(),
+                pool.pool_lp_mint.clone(),
+                pool.pool_token_coin.clone(),
+                pool.pool_token_pc.clone(),
+                pool.serum_market.clone(),
+                user_coin_token_account.clone(),
+                user_pc_token_account.clone(),
+                user_lp_token_account.clone(),
                 user_owner_account.clone(),
             ],
-        )?;
-        Ok(())
+            signer_seeds,
+        )
     }
 
-    fn swap(
-        accounts: &[AccountInfo],
-        amount_in: u64, 
-        minimum_amount_out: u64,
+    /// Nonces the pool authority and the market vault signer derive from
+    fn pool_nonces(pool: &ZapPool) -> Result<(u8, u64), ProgramError> {
+        let amm_nonce = AmmInfo::load_amm_mut(pool.amm_account, true)?.nonce;
+        let amm_nonce = u8::try_from(amm_nonce).map_err(|_| AmmError::InvalidAuthority)?;
+        let vault_signer_nonce = MarketState::load(pool.serum_dex_program_id.key, pool.serum_market)?.vault_signer_nonce;
+        Ok((amm_nonce, vault_signer_nonce))
+    }
+
+    /// Part of `amount_in` to swap so the swap output and the rest of `amount_in` match the
+    /// pool ratio after the swap. With the swap fee `n / d`, `a = d - n` and `b = 2d - n`, it is
+    /// the root of `a * s^2 + b * reserve_in * s - d * reserve_in * amount_in`
+    fn zap_swap_amount(
+        reserve_in: u64,
+        amount_in: u64,
+        swap_fee_numerator: u64,
+        swap_fee_denominator: u64,
+    ) -> Result<u64, ProgramError> {
+        let d = U256::from(swap_fee_denominator);
+        let a = d.checked_sub(U256::from(swap_fee_numerator)).ok_or(AmmError::InvalidFee)?;
+        if a.is_zero() {
+            return Err(AmmError::InvalidFee.into());
+        }
+        let b = d.checked_add(a).ok_or(AmmError::InvalidFee)?;
+        let b_reserve = b.checked_mul(U256::from(reserve_in)).ok_or(AmmError::InvalidInput)?;
+        let discriminant = U256::from(4u64)
+            .checked_mul(a)
+            .and_then(|value| value.checked_mul(d))
+            .and_then(|value| value.checked_mul(U256::from(reserve_in)))
+            .and_then(|value| value.checked_mul(U256::from(amount_in)))
+            .and_then(|value| value.checked_add(b_reserve.checked_mul(b_reserve)?))
+            .ok_or(AmmError::InvalidInput)?;
+        let swap_amount = (discriminant.integer_sqrt() - b_reserve) / (a * 2u64);
+        if swap_amount > U256::from(amount_in) {
+            return Err(AmmError::InvalidInput.into());
+        }
+        Ok(swap_amount.as_u64())
+    }
+
+    /// Reads the fee config of `mint` and the receiver accounts listed in it, or just
+    /// its fee vault if the fees accrue there, then the treasury if it charges a flat fee
+    fn next_fee_accounts<'a, 'b, I: Iterator<Item = &'a AccountInfo<'b>>>(
+        program_id: &Pubkey,
+        mint: &Pubkey,
+        account_info_iter: &mut I,
+    ) -> Result<FeeAccounts<'a, 'b>, ProgramError> {
+        let fee_config_account = next_account_info(account_info_iter)?;
+        let config = FeeConfig::load(program_id, fee_config_account, mint)?;
+        let receivers = if config.charges_commission() {
+            Self::next_fee_destinations(&config, account_info_iter)?
+        } else {
+            Vec::new()
+        };
+        let (treasury, system_program) = if config.charges_flat_fee() {
+            let treasury = next_account_info(account_info_iter)?;
+            if *treasury.key != config.treasury {
+                return Err(AmmError::InvalidFeeReceiver.into());
+            }
+            (Some(treasury), Some(next_account_info(account_info_iter)?))
+        } else {
+            (None, None)
+        };
+        Ok(FeeAccounts { config, receivers, treasury, system_program })
+    }
+
+    /// Reads the fee config of `mint` and its receiver accounts or fee vault, for instructions
+    /// without the user signing, which only charge the commission
+    fn next_commission_accounts<'a, 'b, I: Iterator<Item = &'a AccountInfo<'b>>>(
         program_id: &Pubkey,
+        mint: &Pubkey,
+        account_info_iter: &mut I,
+    ) -> Result<FeeAccounts<'a, 'b>, ProgramError> {
+        let fee_config_account = next_account_info(account_info_iter)?;
+        let config = FeeConfig::load(program_id, fee_config_account, mint)?;
+        let receivers = if config.charges_commission() {
+            Self::next_fee_destinations(&config, account_info_iter)?
+        } else {
+            Vec::new()
+        };
+        Ok(FeeAccounts { config, receivers, treasury: None, system_program: None })
+    }
+
+    /// Reads the fee vault if the fee config accrues to it, its receiver accounts otherwise
+    fn next_fee_destinations<'a, 'b, I: Iterator<Item = &'a AccountInfo<'b>>>(
+        fee_config: &FeeConfig,
+        account_info_iter: &mut I,
+    ) -> Result<Vec<&'a AccountInfo<'b>>, ProgramError> {
+        if fee_config.accrue_to_vault {
+            let fee_vault = next_account_info(account_info_iter)?;
+            if *fee_vault.key != fee_config.vault {
+                return Err(AmmError::InvalidFeeReceiver.into());
+            }
+            Ok(vec![fee_vault])
+        } else {
+            Self::next_fee_receivers(fee_config, account_info_iter)
+        }
+    }
+
+    /// Reads one receiver account per fee config receiver, in order
+    fn next_fee_receivers<'a, 'b, I: Iterator<Item = &'a AccountInfo<'b>>>(
+        fee_config: &FeeConfig,
+        account_info_iter: &mut I,
+    ) -> Result<Vec<&'a AccountInfo<'b>>, ProgramError> {
+        let mut fee_receivers = Vec::with_capacity(fee_config.receivers().len());
+        for receiver in fee_config.receivers() {
+            let fee_receiver = next_account_info(account_info_iter)?;
+            if *fee_receiver.key != receiver.token_account {
+                return Err(AmmError::InvalidFeeReceiver.into());
+            }
+            fee_receivers.push(fee_receiver);
+        }
+        Ok(fee_receivers)
+    }
+
+    /// Splits `fee` between the fee config receivers and transfers each share from `source`,
+    /// or moves all of it to the fee vault
+    fn distribute_fee<'a>(
+        spl_token_program_id: &AccountInfo<'a>,
+        source: &AccountInfo<'a>,
+        user_owner_account: &AccountInfo<'a>,
+        fees: &FeeAccounts<'_, 'a>,
+        fee: u64,
     ) -> ProgramResult {
-        msg!("Swap initialized");
-        let account_info_iter = &mut accounts.iter();
-        let raydium_program_id = next_account_info(account_info_iter)?;
+        Self::distribute_fee_signed(spl_token_program_id, source, user_owner_account, fees, fee, &[])
+    }
 
-        let spl_token_program_id = next_account_info(account_info_iter)?;
-        let amm_account = next_account_info(account_info_iter)?;
-        let authority = next_account_info(account_info_iter)?;
-        let amm_open_orders = next_account_info(account_info_iter)?;
-        let amm_target_orders = next_account_info(account_info_iter)?;
-        let pool_token_coin = next_account_info(account_info_iter)?;
-        let pool_token_pc = next_account_info(account_info_iter)?;
-        let serum_dex_program_id = next_account_info(account_info_iter)?;
-        let serum_market = next_account_info(account_info_iter)?;
-        let serum_bids = next_account_info(account_info_iter)?;
-        let serum_asks = next_account_info(account_info_iter)?;
-        let serum_event_queue = next_account_info(account_info_iter)?;
-        let serum_coin_vault_account = next_account_info(account_info_iter)?;
-        let serum_pc_vault_account = next_account_info(account_info_iter)?;
-        let serum_vault_signer = next_account_info(account_info_iter)?;
-        let user_source_token_account = next_account_info(account_info_iter)?;
-        let user_destination_token_account = next_account_info(account_info_iter)?;
-        let user_owner_account = next_account_info(account_info_iter)?;
-        let fee_receiver = next_account_info(account_info_iter)?;
-        msg!("Swap Instruction");
-        let instruction = crate::instruction::swap(
-            raydium_program_id.key,
-            amm_account.key,
-            authority.key,
-            amm_open_orders.key,
-            amm_target_orders.key,
-            pool_token_coin.key,
-            pool_token_pc.key,
-            serum_dex_program_id.key,
-            serum_market.key,
-            serum_bids.key,
-            serum_asks.key,
-            serum_event_queue.key,
-            serum_coin_vault_account.key,
-            serum_pc_vault_account.key,
-            serum_vault_signer.key,
-            user_source_token_account.key,
-            user_destination_token_account.key,
-            user_owner_account.key,
-            amount_in,
-            minimum_amount_out
+    /// Same as `distribute_fee`, for a `source` owned by a PDA of this program signing with `signer_seeds`
+    fn distribute_fee_signed<'a>(
+        spl_token_program_id: &AccountInfo<'a>,
+        source: &AccountInfo<'a>,
+        source_owner: &AccountInfo<'a>,
+        fees: &FeeAccounts<'_, 'a>,
+        fee: u64,
+        signer_seeds: &[&[&[u8]]],
+    ) -> ProgramResult {
+        if fee == 0 {
+            return Ok(());
+        }
+        if fees.config.accrue_to_vault {
+            return Self::transfer_fee(spl_token_program_id, source, fees.receivers[0], source_owner, fee, signer_seeds);
+        }
+        let shares = fees.config.split(fee)?;
+        for (fee_receiver, share) in fees.receivers.iter().zip(shares.iter()) {
+            if *share > 0 {
+                Self::transfer_fee(spl_token_program_id, source, fee_receiver, source_owner, *share, signer_seeds)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Transfers the flat lamport fee from the signer to the treasury, if the config charges one
+    fn collect_flat_fee<'a>(
+        user_owner_account: &AccountInfo<'a>,
+        fees: &FeeAccounts<'_, 'a>,
+    ) -> ProgramResult {
+        if let (Some(treasury), Some(system_program)) = (fees.treasury, fees.system_program) {
+            invoke(
+                &system_instruction::transfer(
+                    user_owner_account.key,
+                    treasury.key,
+                    fees.config.flat_fee_lamports,
+                ),
+                &[
+                    user_owner_account.clone(),
+                    treasury.clone(),
+                    system_program.clone(),
+                ],
+            )?;
+        }
+        Ok(())
+    }
+
+    /// Reads the accounts needed to open the temporary wrapped SOL account of `user_owner_account`
+    fn next_wrapped_sol<'a, 'b, I: Iterator<Item = &'a AccountInfo<'b>>>(
+        program_id: &Pubkey,
+        wrapped_sol_account: &'a AccountInfo<'b>,
+        user_owner_account: &AccountInfo<'b>,
+        account_info_iter: &mut I,
+    ) -> Result<WrappedSol<'a, 'b>, ProgramError> {
+        let system_program = next_account_info(account_info_iter)?;
+        let rent_sysvar = next_account_info(account_info_iter)?;
+        let native_mint = next_account_info(account_info_iter)?;
+        if *native_mint.key != native_mint::id() {
+            return Err(AmmError::InvalidMint.into());
+        }
+        let (address, bump_seed) = Pubkey::find_program_address(
+            &[WSOL_SEED, user_owner_account.key.as_ref()],
+            program_id,
+        );
+        if address != *wrapped_sol_account.key {
+            return Err(AmmError::InvalidInput.into());
+        }
+        Ok(WrappedSol { account: wrapped_sol_account, bump_seed, system_program, rent_sysvar, native_mint })
+    }
+
+    /// Creates the wrapped SOL account holding `amount` lamports from the user on top of its rent.
+    /// Initializing a funded native account sets its token balance, no sync is needed.
+    fn open_wrapped_sol<'a>(
+        wrapped_sol: &WrappedSol<'_, 'a>,
+        spl_token_program_id: &AccountInfo<'a>,
+        user_owner_account: &AccountInfo<'a>,
+        amount: u64,
+    ) -> ProgramResult {
+        let space = spl_token::state::Account::LEN;
+        let lamports = Rent::get()?
+            .minimum_balance(space)
+            .checked_add(amount)
+            .ok_or(AmmError::InvalidInput)?;
+        Self::create_funded_pda_account(
+            user_owner_account,
+            wrapped_sol.account,
+            wrapped_sol.system_program,
+            lamports,
+            space,
+            spl_token_program_id.key,
+            &[WSOL_SEED, user_owner_account.key.as_ref(), &[wrapped_sol.bump_seed]],
         )?;
-        msg!("Swap Invoke");
         invoke(
-            &instruction,
+            &spl_token::instruction::initialize_account(
+                spl_token_program_id.key,
+                wrapped_sol.account.key,
+                wrapped_sol.native_mint.key,
+                user_owner_account.key,
+            )?,
             &[
+                wrapped_sol.account.clone(),
+                wrapped_sol.native_mint.clone(),
+                user_owner_account.clone(),
+                wrapped_sol.rent_sysvar.clone(),
+            ],
+        )
+    }
+
+    /// Closes the wrapped SOL account, returning its balance and rent to the user as lamports
+    fn close_wrapped_sol<'a>(
+        wrapped_sol: &WrappedSol<'_, 'a>,
+        spl_token_program_id: &AccountInfo<'a>,
+        user_owner_account: &AccountInfo<'a>,
+    ) -> ProgramResult {
+        invoke(
+            &spl_token::instruction::close_account(
+                spl_token_program_id.key,
+                wrapped_sol.account.key,
+                user_owner_account.key,
+                user_owner_account.key,
+                &[],
+            )?,
+            &[
+                wrapped_sol.account.clone(),
+                user_owner_account.clone(),
+                user_owner_account.clone(),
+            ],
+        )
+    }
+
+    /// Reads the associated token program, system program and rent sysvar, if the caller passed them
+    fn next_associated_accounts<'a, 'b, I: Iterator<Item = &'a AccountInfo<'b>>>(
+        account_info_iter: &mut I,
+    ) -> Result<Option<AssociatedAccounts<'a, 'b>>, ProgramError> {
+        let associated_token_program = match account_info_iter.next() {
+            Some(account) => account,
+            None => return Ok(None),
+        };
+        if *associated_token_program.key != spl_associated_token_account::id() {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        let system_program = next_account_info(account_info_iter)?;
+        let rent_sysvar = next_account_info(account_info_iter)?;
+        Ok(Some(AssociatedAccounts { associated_token_program, system_program, rent_sysvar }))
+    }
+
+    /// Creates the associated token account of `wallet` for `mint` with rent paid by `payer`,
+    /// unless `token_account` already exists
+    fn create_associated_account<'a>(
+        associated: &AssociatedAccounts<'_, 'a>,
+        spl_token_program_id: &AccountInfo<'a>,
+        payer: &AccountInfo<'a>,
+        token_account: &AccountInfo<'a>,
+        wallet: &AccountInfo<'a>,
+        mint: &AccountInfo<'a>,
+    ) -> ProgramResult {
+        if !token_account.data_is_empty() {
+            return Ok(());
+        }
+        if get_associated_token_address(wallet.key, mint.key) != *token_account.key {
+            return Err(AmmError::InvalidInput.into());
+        }
+        invoke(
+            &create_associated_token_account(payer.key, wallet.key, mint.key),
+            &[
+                payer.clone(),
+                token_account.clone(),
+                wallet.clone(),
+                mint.clone(),
+                associated.system_program.clone(),
                 spl_token_program_id.clone(),
-                amm_account.clone(),
-                authority.clone(),
-                amm_open_orders.clone(),
-                amm_target_orders.clone(),
-                pool_token_coin.clone(),
-                pool_token_pc.clone(),
-                serum_dex_program_id.clone(),
-                serum_market.clone(),
-                serum_bids.clone(),
-                serum_asks.clone(),
-                serum_event_queue.clone(),
-                serum_coin_vault_account.clone(),
-                serum_pc_vault_account.clone(),
-                serum_vault_signer.clone(),
-                user_source_token_account.clone(),
-                user_destination_token_account.clone(),
-                user_owner_account.clone()
+                associated.rent_sysvar.clone(),
+                associated.associated_token_program.clone(),
             ],
-        )?;
-        let fee = minimum_amount_out
-            .checked_mul(1).unwrap()
-            .checked_div(10).unwrap();
+        )
+    }
 
+    /// Creates the missing receiver token accounts of a fee config, reading the wallet
+    /// of each missing receiver in order. The fee vault is never missing
+    fn create_fee_receiver_accounts<'a, 'b, I: Iterator<Item = &'a AccountInfo<'b>>>(
+        associated: &AssociatedAccounts<'a, 'b>,
+        spl_token_program_id: &AccountInfo<'b>,
+        payer: &AccountInfo<'b>,
+        fees: &FeeAccounts<'a, 'b>,
+        mint: &AccountInfo<'b>,
+        account_info_iter: &mut I,
+    ) -> ProgramResult {
+        if fees.config.accrue_to_vault {
+            return Ok(());
+        }
+        for fee_receiver in fees.receivers.iter().filter(|receiver| receiver.data_is_empty()) {
+            let wallet = next_account_info(account_info_iter)?;
+            Self::create_associated_account(associated, spl_token_program_id, payer, fee_receiver, wallet, mint)?;
+        }
+        Ok(())
+    }
+
+    /// Transfers `fee` from a user or escrow token account to the fee receiver
+    fn transfer_fee<'a>(
+        spl_token_program_id: &AccountInfo<'a>,
+        source: &AccountInfo<'a>,
+        fee_receiver: &AccountInfo<'a>,
+        user_owner_account: &AccountInfo<'a>,
+        fee: u64,
+        signer_seeds: &[&[&[u8]]],
+    ) -> ProgramResult {
         let tx_to_receive = spl_token::instruction::transfer(
-            spl_token_program_id.key, 
-            user_destination_token_account.key, 
+            spl_token_program_id.key,
+            source.key,
             fee_receiver.key,
             user_owner_account.key,
             &[],
             fee,
         )?;
-        invoke(
+        invoke_signed(
             &tx_to_receive,
             &[
-                user_destination_token_account.clone(),
+                source.clone(),
                 fee_receiver.clone(),
                 user_owner_account.clone(),
             ],
-        )?;
+            signer_seeds,
+        )
+    }
+
+    /// Balance of an spl token account
+    fn token_amount(token_account: &AccountInfo) -> Result<u64, ProgramError> {
+        Ok(spl_token::state::Account::unpack(&token_account.data.borrow())?.amount)
+    }
 
+    /// Mint of an spl token account
+    fn token_mint(token_account: &AccountInfo) -> Result<Pubkey, ProgramError> {
+        Ok(spl_token::state::Account::unpack(&token_account.data.borrow())?.mint)
+    }
 
-        msg!("OKOKOKO");
+    /// Checks the pool of an adapter is listed and owned by the program of its venue
+    fn check_adapter_pool(
+        program_id: &Pubkey,
+        registry_account: &AccountInfo,
+        pool: &Adapter,
+    ) -> Result<PoolRegistry, ProgramError> {
+        let registry = PoolRegistry::load(program_id, registry_account, pool.pool())?;
+        registry.check_kind(pool.pool_kind())?;
+        pool.check_program()?;
+        Ok(registry)
+    }
+
+    /// Loads the registry entry of the pool and checks the amm belongs to the invoked Raydium
+    /// program, and the authority and vault signer derive from the pool and market nonces.
+    /// Zaps, orders and lp vaults price with the AMM v4 reserves, other venues are rejected
+    fn check_pool(
+        program_id: &Pubkey,
+        registry_account: &AccountInfo,
+        pool: &ZapPool,
+    ) -> Result<PoolRegistry, ProgramError> {
+        let registry = PoolRegistry::load(program_id, registry_account, pool.amm_account.key)?;
+        registry.check_kind(PoolKind::RaydiumV4)?;
+        if pool.amm_account.owner != pool.raydium_program_id.key {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        let amm_nonce = AmmInfo::load_amm_mut(pool.amm_account, true)?.nonce;
+        check_raydium_authority(pool.raydium_program_id.key, amm_nonce, pool.authority)?;
+        check_serum_vault_signer(pool.serum_dex_program_id, pool.serum_market, pool.serum_vault_signer)?;
+        Ok(registry)
+    }
+
+    /// Fails once the current slot is past `valid_until_slot`
+    fn check_deadline(valid_until_slot: Option<u64>) -> ProgramResult {
+        if let Some(valid_until_slot) = valid_until_slot {
+            Self::check_slot(valid_until_slot, Clock::get()?.slot)?;
+        }
+        Ok(())
+    }
+
+    /// Fails if `slot` is past `valid_until_slot`, the deadline slot itself is still valid
+    fn check_slot(valid_until_slot: u64, slot: u64) -> ProgramResult {
+        if slot > valid_until_slot {
+            return Err(AmmError::Expired.into());
+        }
+        Ok(())
+    }
+
+    fn check_admin(config: &ProviderConfig, admin: &AccountInfo) -> ProgramResult {
+        if !admin.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+        if config.admin != *admin.key {
+            return Err(AmmError::InvalidAdmin.into());
+        }
         Ok(())
     }
+
+    /// Creates a rent exempt account owned by `owner` at the PDA signed by `seeds`
+    fn create_pda_account<'a>(
+        payer: &AccountInfo<'a>,
+        new_account: &AccountInfo<'a>,
+        system_program: &AccountInfo<'a>,
+        space: usize,
+        owner: &Pubkey,
+        seeds: &[&[u8]],
+    ) -> ProgramResult {
+        let lamports = Rent::get()?.minimum_balance(space);
+        Self::create_funded_pda_account(payer, new_account, system_program, lamports, space, owner, seeds)
+    }
+
+    /// Creates an account owned by `owner` holding at least `lamports` at the PDA signed by `seeds`.
+    /// Anyone can transfer lamports to the address beforehand, which makes `create_account` fail,
+    /// so a funded address is topped up, allocated and assigned instead
+    fn create_funded_pda_account<'a>(
+        payer: &AccountInfo<'a>,
+        new_account: &AccountInfo<'a>,
+        system_program: &AccountInfo<'a>,
+        lamports: u64,
+        space: usize,
+        owner: &Pubkey,
+        seeds: &[&[u8]],
+    ) -> ProgramResult {
+        if new_account.lamports() == 0 {
+            return invoke_signed(
+                &system_instruction::create_account(payer.key, new_account.key, lamports, space as u64, owner),
+                &[payer.clone(), new_account.clone(), system_program.clone()],
+                &[seeds],
+            );
+        }
+        let top_up = Self::top_up_lamports(new_account.lamports(), lamports);
+        if top_up > 0 {
+            invoke(
+                &system_instruction::transfer(payer.key, new_account.key, top_up),
+                &[payer.clone(), new_account.clone(), system_program.clone()],
+            )?;
+        }
+        invoke_signed(
+            &system_instruction::allocate(new_account.key, space as u64),
+            &[new_account.clone(), system_program.clone()],
+            &[seeds],
+        )?;
+        invoke_signed(
+            &system_instruction::assign(new_account.key, owner),
+            &[new_account.clone(), system_program.clone()],
+            &[seeds],
+        )
+    }
+
+    /// Lamports missing from `balance` to hold `required`
+    fn top_up_lamports(balance: u64, required: u64) -> u64 {
+        required.saturating_sub(balance)
+    }
 }
 
 fn to_u128(val: u64) -> Result<u128, AmmError> {
-    val.try_into().map_err(|_| AmmError::ConversionFailure)
+    Ok(val.into())
 }
 
 fn to_u64(val: u128) -> Result<u64, AmmError> {
     val.try_into().map_err(|_| AmmError::ConversionFailure)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::state::{AmmStatus, FeeReceiver};
+    use bytemuck::Zeroable;
+    use solana_program::{instruction::Instruction, system_program};
+
+    /// Owned account state to lend out as an `AccountInfo`
+    struct TestAccount {
+        key: Pubkey,
+        lamports: u64,
+        data: Vec<u8>,
+        owner: Pubkey,
+    }
+
+    impl TestAccount {
+        fn new(key: Pubkey, owner: Pubkey, data: Vec<u8>) -> Self {
+            Self { key, lamports: 1, data, owner }
+        }
+
+        fn info(&mut self) -> AccountInfo<'_> {
+            AccountInfo::new(&self.key, false, true, &mut self.lamports, &mut self.data, &self.owner, false, 0)
+        }
+    }
+
+    fn fee_config(weights: &[u16]) -> FeeConfig {
+        let mut config = FeeConfig { is_initialized: true, mint: Pubkey::new_unique(), ..FeeConfig::default() };
+        for (receiver, weight_bps) in config.receivers.iter_mut().zip(weights) {
+            *receiver = FeeReceiver { token_account: Pubkey::new_unique(), weight_bps: *weight_bps };
+        }
+        config.receiver_count = weights.len() as u8;
+        config
+    }
+
+    /// Fee config account of `config.mint` at its PDA
+    fn fee_config_account(program_id: &Pubkey, config: &mut FeeConfig) -> TestAccount {
+        let (address, bump_seed) = FeeConfig::find_address(program_id, &config.mint);
+        config.bump_seed = bump_seed;
+        let mut data = vec![0u8; FeeConfig::LEN];
+        config.pack_into_slice(&mut data);
+        TestAccount::new(address, *program_id, data)
+    }
+
+    fn fee_accounts<'a, 'b>(fee_model: FeeModel) -> FeeAccounts<'a, 'b> {
+        let config = FeeConfig { fee_model, ..FeeConfig::default() };
+        FeeAccounts { config, receivers: Vec::new(), treasury: None, system_program: None }
+    }
+
+    #[test]
+    fn test_swap_commission_fee_side() {
+        let fees = fee_accounts(FeeModel::Percentage);
+        // input side, the fee is taken from the amount swapped
+        assert_eq!(Processor::swap_commission(&fees, FeeSide::Input, 1_000, 500).unwrap(), (100, 900));
+        // output side, the whole input is swapped and the fee follows the minimum output
+        assert_eq!(Processor::swap_commission(&fees, FeeSide::Output, 1_000, 500).unwrap(), (50, 1_000));
+        // rounds down, a dust swap pays nothing
+        assert_eq!(Processor::swap_commission(&fees, FeeSide::Input, 9, 0).unwrap(), (0, 9));
+    }
+
+    #[test]
+    fn test_swap_commission_flat_only() {
+        let fees = fee_accounts(FeeModel::Flat);
+        assert_eq!(Processor::swap_commission(&fees, FeeSide::Input, 1_000, 500).unwrap(), (0, 1_000));
+        assert_eq!(Processor::swap_commission(&fees, FeeSide::Output, 1_000, 500).unwrap(), (0, 1_000));
+    }
+
+    #[test]
+    fn test_fee_destinations_receivers_in_order() {
+        let config = fee_config(&[6_000, 4_000]);
+        let mut accounts: Vec<TestAccount> = config.receivers().iter()
+            .map(|receiver| TestAccount::new(receiver.token_account, spl_token::id(), Vec::new()))
+            .collect();
+        let infos: Vec<AccountInfo> = accounts.iter_mut().map(TestAccount::info).collect();
+        let receivers = Processor::next_fee_destinations(&config, &mut infos.iter()).unwrap();
+        assert_eq!(receivers.len(), 2);
+        assert_eq!(*receivers[1].key, config.receivers[1].token_account);
+
+        let swapped = [infos[1].clone(), infos[0].clone()];
+        assert_eq!(
+            Processor::next_fee_destinations(&config, &mut swapped.iter()).err(),
+            Some(AmmError::InvalidFeeReceiver.into()),
+        );
+        assert_eq!(
+            Processor::next_fee_destinations(&config, &mut infos[..1].iter()).err(),
+            Some(ProgramError::NotEnoughAccountKeys),
+        );
+    }
+
+    #[test]
+    fn test_fee_destinations_vault() {
+        let mut config = fee_config(&[6_000, 4_000]);
+        config.accrue_to_vault = true;
+        config.vault = Pubkey::new_unique();
+        let mut vault = TestAccount::new(config.vault, spl_token::id(), Vec::new());
+        let mut other = TestAccount::new(config.receivers[0].token_account, spl_token::id(), Vec::new());
+        // fees accrue to the vault alone, the receivers are only paid by the sweep
+        let infos = [vault.info()];
+        let destinations = Processor::next_fee_destinations(&config, &mut infos.iter()).unwrap();
+        assert_eq!(destinations.len(), 1);
+        assert_eq!(*destinations[0].key, config.vault);
+        let infos = [other.info()];
+        assert_eq!(
+            Processor::next_fee_destinations(&config, &mut infos.iter()).err(),
+            Some(AmmError::InvalidFeeReceiver.into()),
+        );
+    }
+
+    #[test]
+    fn test_next_fee_accounts_flat_fee() {
+        let program_id = Pubkey::new_unique();
+        let mut config = fee_config(&[10_000]);
+        config.fee_model = FeeModel::Flat;
+        config.flat_fee_lamports = 5_000;
+        config.treasury = Pubkey::new_unique();
+        let mut fee_config = fee_config_account(&program_id, &mut config);
+        let mut treasury = TestAccount::new(config.treasury, system_program::id(), Vec::new());
+        let mut system = TestAccount::new(system_program::id(), Pubkey::default(), Vec::new());
+        let mut other = TestAccount::new(Pubkey::new_unique(), system_program::id(), Vec::new());
+
+        // a flat fee only config reads no receivers, just the treasury and the system program
+        let infos = [fee_config.info(), treasury.info(), system.info()];
+        let fees = Processor::next_fee_accounts(&program_id, &config.mint, &mut infos.iter()).unwrap();
+        assert!(fees.receivers.is_empty());
+        assert_eq!(fees.treasury.map(|treasury| *treasury.key), Some(config.treasury));
+        assert_eq!(fees.commission(1_000).unwrap(), 0);
+
+        let infos = [infos[0].clone(), other.info(), infos[2].clone()];
+        assert_eq!(
+            Processor::next_fee_accounts(&program_id, &config.mint, &mut infos.iter()).err(),
+            Some(AmmError::InvalidFeeReceiver.into()),
+        );
+        // instructions the user does not sign never read the treasury
+        let fees = Processor::next_commission_accounts(&program_id, &config.mint, &mut infos[..1].iter()).unwrap();
+        assert!(fees.treasury.is_none());
+    }
+
+    #[test]
+    fn test_next_fee_accounts_wrong_config() {
+        let program_id = Pubkey::new_unique();
+        let mut config = fee_config(&[10_000]);
+        let mut fee_config = fee_config_account(&program_id, &mut config);
+        let infos = [fee_config.info()];
+        assert_eq!(
+            Processor::next_fee_accounts(&program_id, &Pubkey::new_unique(), &mut infos.iter()).err(),
+            Some(AmmError::InvalidConfig.into()),
+        );
+        assert_eq!(
+            Processor::next_fee_accounts(&Pubkey::new_unique(), &config.mint, &mut infos.iter()).err(),
+            Some(AmmError::InvalidConfig.into()),
+        );
+    }
+
+    #[test]
+    fn test_check_vault_farm_program() {
+        let farm_program_id = Pubkey::new_unique();
+        let mut program = TestAccount::new(farm_program_id, Pubkey::default(), Vec::new());
+        let mut farm_account = TestAccount::new(Pubkey::new_unique(), farm_program_id, Vec::new());
+        let mut farm_user_info = TestAccount::new(Pubkey::new_unique(), farm_program_id, Vec::new());
+        let mut authority = TestAccount::new(Pubkey::new_unique(), Pubkey::default(), Vec::new());
+        let mut lp_vault = TestAccount::new(Pubkey::new_unique(), spl_token::id(), Vec::new());
+        let mut reward_vault = TestAccount::new(Pubkey::new_unique(), spl_token::id(), Vec::new());
+        let mut clock = TestAccount::new(sysvar::clock::id(), Pubkey::default(), Vec::new());
+        let mut spl_token_program = TestAccount::new(spl_token::id(), Pubkey::default(), Vec::new());
+        let mut lp_token = TestAccount::new(Pubkey::new_unique(), spl_token::id(), Vec::new());
+        let vault = LpVault {
+            lp_token: lp_token.key,
+            farm: farm_account.key,
+            farm_user_info: farm_user_info.key,
+            ..LpVault::default()
+        };
+        let farm = VaultFarm {
+            farm_program_id: &program.info(),
+            farm_account: &farm_account.info(),
+            farm_authority: &authority.info(),
+            farm_user_info: &farm_user_info.info(),
+            farm_lp_vault: &lp_vault.info(),
+            farm_reward_vault: &reward_vault.info(),
+            clock_sysvar: &clock.info(),
+        };
+        let lp_token = lp_token.info();
+        let spl_token_program = spl_token_program.info();
+        assert!(Processor::check_vault_farm(&vault, &lp_token, &farm, &farm_program_id, &spl_token_program).is_ok());
+        // a farm program the admin did not set, or none set at all
+        for configured in [Pubkey::new_unique(), Pubkey::default()] {
+            assert_eq!(
+                Processor::check_vault_farm(&vault, &lp_token, &farm, &configured, &spl_token_program).err(),
+                Some(ProgramError::IncorrectProgramId),
+            );
+        }
+    }
+
+    #[test]
+    fn test_check_slot() {
+        assert!(Processor::check_slot(100, 99).is_ok());
+        assert!(Processor::check_slot(100, 100).is_ok());
+        assert_eq!(Processor::check_slot(100, 101).err(), Some(AmmError::Expired.into()));
+        assert_eq!(Processor::check_slot(0, u64::MAX).err(), Some(AmmError::Expired.into()));
+        // no deadline never reads the clock
+        assert!(Processor::check_deadline(None).is_ok());
+    }
+
+    #[test]
+    fn test_operation() {
+        let withdraw = ProviderInstruction::Withdraw(WithdrawInstruction {
+            amount: 1,
+            valid_until_slot: None,
+            adapter: AdapterKind::RaydiumV4,
+        });
+        assert_eq!(Processor::operation(&withdraw), Some(Operation::Withdraw));
+        assert_eq!(Processor::operation(&ProviderInstruction::ExecuteLimitOrder), Some(Operation::Swap));
+        assert_eq!(Processor::operation(&ProviderInstruction::ExecuteDca), Some(Operation::Swap));
+        assert_eq!(Processor::operation(&ProviderInstruction::HarvestRewards), Some(Operation::Withdraw));
+        assert_eq!(
+            Processor::operation(&ProviderInstruction::Compound { minimum_lp_amount: 0 }),
+            Some(Operation::Compound),
+        );
+        assert_eq!(Processor::operation(&ProviderInstruction::SweepFees), Some(Operation::SweepFees));
+        // admin instructions stay available while paused, so the guardian can unpause
+        let set_pause = ProviderInstruction::SetPause(SetPauseInstruction {
+            paused: false,
+            swap_paused: false,
+            deposit_paused: false,
+            withdraw_paused: false,
+        });
+        assert_eq!(Processor::operation(&set_pause), None);
+        assert_eq!(Processor::operation(&ProviderInstruction::InitializeFeeVault), None);
+    }
+
+    #[test]
+    fn test_top_up_lamports() {
+        // a griefing transfer below rent is topped up, anything above it is kept as is
+        assert_eq!(Processor::top_up_lamports(1, 2_039_280), 2_039_279);
+        assert_eq!(Processor::top_up_lamports(2_039_280, 2_039_280), 0);
+        assert_eq!(Processor::top_up_lamports(5_000_000, 2_039_280), 0);
+    }
+
+    #[test]
+    fn test_zap_swap_amount() {
+        let (reserve_in, reserve_out) = (1_000_000_000u64, 4_000_000_000u64);
+        let amount_in = 10_000_000u64;
+        let swap_amount = Processor::zap_swap_amount(reserve_in, amount_in, 25, 10_000).unwrap();
+        // about half, the swap fee pushes it up and the price impact down
+        assert!(swap_amount.abs_diff(amount_in / 2) < amount_in / 100);
+
+        // the rest of the input and the swap output match the pool ratio after the swap
+        let swap_in_after_fee = u128::from(swap_amount) * 9_975 / 10_000;
+        let swap_out = swap_in_after_fee * u128::from(reserve_out) / (u128::from(reserve_in) + swap_in_after_fee);
+        let rest = u128::from(amount_in - swap_amount);
+        let pool_in = u128::from(reserve_in + swap_amount);
+        let pool_out = u128::from(reserve_out) - swap_out;
+        let deviation = (rest * pool_out).abs_diff(swap_out * pool_in);
+        assert!(deviation * 100_000 < rest * pool_out);
+
+        assert_eq!(Processor::zap_swap_amount(reserve_in, 0, 25, 10_000).unwrap(), 0);
+        // without a fee and with a negligible price impact, half of the input is swapped
+        let swap_amount = Processor::zap_swap_amount(1_000_000_000_000, 1_000, 0, 1).unwrap();
+        assert!((499..=500).contains(&swap_amount));
+        assert_eq!(
+            Processor::zap_swap_amount(reserve_in, amount_in, 10_000, 10_000).err(),
+            Some(AmmError::InvalidFee.into()),
+        );
+    }
+
+    #[test]
+    fn test_order_commission() {
+        // a 1/10 commission on 1000 out leaves 900, short of a 950 minimum
+        let fees = fee_accounts(FeeModel::Percentage);
+        assert_eq!(Processor::order_commission(&fees, 1_000).unwrap(), (100, 900));
+        assert_eq!(Processor::order_commission(&fees, 0).unwrap(), (0, 0));
+        let fees = fee_accounts(FeeModel::Flat);
+        assert_eq!(Processor::order_commission(&fees, 1_000).unwrap(), (0, 1_000));
+    }
+
+    /// Raydium v4 amm and serum market data with valid authority and vault signer nonces
+    fn raydium_pool(raydium_program_id: &Pubkey) -> (AmmInfo, Vec<u8>) {
+        let mut amm = AmmInfo::zeroed();
+        amm.status = AmmStatus::Initialized as u64;
+        let (_, nonce) = Pubkey::find_program_address(&[crate::instruction::AUTHORITY_AMM], raydium_program_id);
+        amm.nonce = nonce as u64;
+        for key in [
+            &mut amm.coin_mint, &mut amm.pc_mint, &mut amm.lp_mint, &mut amm.token_coin, &mut amm.token_pc,
+            &mut amm.open_orders, &mut amm.target_orders, &mut amm.market, &mut amm.serum_dex,
+        ] {
+            *key = Pubkey::new_unique();
+        }
+
+        let mut market = MarketState::zeroed();
+        market.account_flags = crate::serum::ACCOUNT_FLAG_INITIALIZED | crate::serum::ACCOUNT_FLAG_MARKET;
+        market.own_address = amm.market;
+        market.vault_signer_nonce = (0..)
+            .find(|nonce| crate::instruction::serum_vault_signer(&amm.serum_dex, &amm.market, *nonce).is_ok())
+            .unwrap();
+        let mut market_data = b"serum".to_vec();
+        market_data.extend_from_slice(bytemuck::bytes_of(&market));
+        market_data.extend_from_slice(b"padding");
+        (amm, market_data)
+    }
+
+    /// Accounts of a built instruction, the fee configs at their PDAs
+    fn instruction_accounts(program_id: &Pubkey, instruction: &Instruction, fee_configs: &mut [FeeConfig]) -> Vec<TestAccount> {
+        instruction.accounts.iter().map(|meta| {
+            match fee_configs.iter_mut().find(|config| FeeConfig::find_address(program_id, &config.mint).0 == meta.pubkey) {
+                Some(config) => fee_config_account(program_id, config),
+                None => TestAccount::new(meta.pubkey, Pubkey::default(), Vec::new()),
+            }
+        }).collect()
+    }
+
+    #[test]
+    fn test_provider_swap_accounts_order() {
+        let program_id = Pubkey::new_unique();
+        let raydium_program_id = Pubkey::new_unique();
+        let user_owner = Pubkey::new_unique();
+        let (amm, market_data) = raydium_pool(&raydium_program_id);
+        let amm_id = Pubkey::new_unique();
+        let mut config = fee_config(&[6_000, 4_000]);
+        config.mint = amm.pc_mint;
+
+        let instruction = crate::instruction::provider_swap(
+            &program_id, &raydium_program_id, &amm_id, bytemuck::bytes_of(&amm), &market_data,
+            &user_owner, &amm.coin_mint, &config, 1_000, 900, FeeSide::Output, None,
+        ).unwrap();
+        let mut accounts = instruction_accounts(&program_id, &instruction, std::slice::from_mut(&mut config));
+        let infos: Vec<AccountInfo> = accounts.iter_mut().map(TestAccount::info).collect();
+
+        // the config is consumed by the pause check, the swap parses the rest
+        let iter = &mut infos[1..].iter();
+        let registry = next_account_info(iter).unwrap();
+        assert_eq!(*registry.key, PoolRegistry::find_address(&program_id, &amm_id).0);
+        let (pool, user) = Adapter::next_swap(AdapterKind::RaydiumV4, iter).unwrap();
+        assert_eq!(*pool.pool(), amm_id);
+        assert_eq!(*user.source.key, get_associated_token_address(&user_owner, &amm.coin_mint));
+        assert_eq!(*user.destination.key, get_associated_token_address(&user_owner, &amm.pc_mint));
+        assert_eq!(*user.owner.key, user_owner);
+        let fees = Processor::next_fee_accounts(&program_id, &amm.pc_mint, iter).unwrap();
+        assert_eq!(fees.receivers.len(), 2);
+        assert!(iter.next().is_none());
+    }
+
+    #[test]
+    fn test_provider_deposit_accounts_order() {
+        let program_id = Pubkey::new_unique();
+        let raydium_program_id = Pubkey::new_unique();
+        let user_owner = Pubkey::new_unique();
+        let (amm, _) = raydium_pool(&raydium_program_id);
+        let amm_id = Pubkey::new_unique();
+        let mut coin_config = fee_config(&[10_000]);
+        coin_config.mint = amm.coin_mint;
+        let mut pc_config = fee_config(&[10_000]);
+        pc_config.mint = amm.pc_mint;
+        pc_config.fee_model = FeeModel::Flat;
+        pc_config.flat_fee_lamports = 5_000;
+        pc_config.treasury = Pubkey::new_unique();
+
+        let instruction = crate::instruction::provider_deposit(
+            &program_id, &raydium_program_id, &amm_id, bytemuck::bytes_of(&amm),
+            &user_owner, &coin_config, &pc_config, 10, 20, 0, None,
+        ).unwrap();
+        // the pc side charges a flat fee, so the owner pays it
+        let owner_meta = instruction.accounts.iter().find(|meta| meta.pubkey == user_owner).unwrap();
+        assert!(owner_meta.is_signer && owner_meta.is_writable);
+        let mut configs = [coin_config, pc_config];
+        let mut accounts = instruction_accounts(&program_id, &instruction, &mut configs);
+        let infos: Vec<AccountInfo> = accounts.iter_mut().map(TestAccount::info).collect();
+
+        let iter = &mut infos[1..].iter();
+        let registry = next_account_info(iter).unwrap();
+        assert_eq!(*registry.key, PoolRegistry::find_address(&program_id, &amm_id).0);
+        let (pool, user) = Adapter::next_deposit(AdapterKind::RaydiumV4, iter).unwrap();
+        assert_eq!(*pool.pool(), amm_id);
+        assert_eq!(*user.coin.key, get_associated_token_address(&user_owner, &amm.coin_mint));
+        assert_eq!(*user.pc.key, get_associated_token_address(&user_owner, &amm.pc_mint));
+        assert_eq!(*user.lp.key, get_associated_token_address(&user_owner, &amm.lp_mint));
+        assert_eq!(*user.owner.key, user_owner);
+        let coin_fees = Processor::next_fee_accounts(&program_id, &amm.coin_mint, iter).unwrap();
+        assert_eq!(coin_fees.receivers.len(), 1);
+        let pc_fees = Processor::next_fee_accounts(&program_id, &amm.pc_mint, iter).unwrap();
+        assert_eq!(pc_fees.treasury.map(|treasury| *treasury.key), Some(configs[1].treasury));
+        assert!(iter.next().is_none());
+    }
+}
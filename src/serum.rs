@@ -0,0 +1,263 @@
+//! Serum / OpenBook dex account layouts
+
+use crate::instruction::SerumSide;
+use bytemuck::{from_bytes, Pod, Zeroable};
+use solana_program::{
+    account_info::AccountInfo,
+    program_error::ProgramError,
+    pubkey::Pubkey,
+};
+use arrayref::array_ref;
+use std::{cell::Ref, mem::size_of};
+
+/// Bytes before and after the state of every dex account
+const ACCOUNT_HEAD_PADDING: &[u8; 5] = b"serum";
+const ACCOUNT_TAIL_PADDING: &[u8; 7] = b"padding";
+
+/// Set in `account_flags` of every initialized account
+pub const ACCOUNT_FLAG_INITIALIZED: u64 = 1 << 0;
+/// Set in `account_flags` of a market
+pub const ACCOUNT_FLAG_MARKET: u64 = 1 << 1;
+/// Set in `account_flags` of an open orders account
+pub const ACCOUNT_FLAG_OPEN_ORDERS: u64 = 1 << 2;
+
+/// Market of the dex. Packed like the dex lays it out after the head padding
+#[repr(C, packed)]
+#[derive(Clone, Copy)]
+pub struct MarketState {
+    pub account_flags: u64,
+    pub own_address: Pubkey,
+    /// nonce of the vault signer, PDA of `[market, nonce]` under the dex program
+    pub vault_signer_nonce: u64,
+    pub coin_mint: Pubkey,
+    pub pc_mint: Pubkey,
+    pub coin_vault: Pubkey,
+    pub coin_deposits_total: u64,
+    pub coin_fees_accrued: u64,
+    pub pc_vault: Pubkey,
+    pub pc_deposits_total: u64,
+    pub pc_fees_accrued: u64,
+    pub pc_dust_threshold: u64,
+    pub req_q: Pubkey,
+    pub event_q: Pubkey,
+    pub bids: Pubkey,
+    pub asks: Pubkey,
+    pub coin_lot_size: u64,
+    pub pc_lot_size: u64,
+    pub fee_rate_bps: u64,
+    pub referrer_rebates_accrued: u64,
+}
+
+unsafe impl Zeroable for MarketState {}
+unsafe impl Pod for MarketState {}
+
+impl MarketState {
+    /// Parses the raw data of a market account
+    pub fn unpack(data: &[u8]) -> Result<&Self, ProgramError> {
+        unpack_state(data, ACCOUNT_FLAG_MARKET)
+    }
+
+    /// Borrows a market account owned by `dex_program_id`
+    pub fn load<'a>(dex_program_id: &Pubkey, market_account: &'a AccountInfo) -> Result<Ref<'a, Self>, ProgramError> {
+        load_state(dex_program_id, market_account, ACCOUNT_FLAG_MARKET)
+    }
+
+    /// Vault signer of the market at `market`
+    pub fn vault_signer(&self, dex_program_id: &Pubkey, market: &Pubkey) -> Result<Pubkey, ProgramError> {
+        crate::instruction::serum_vault_signer(dex_program_id, market, self.vault_signer_nonce)
+    }
+}
+
+/// Orders and balances of one owner on a market. Packed like the dex lays it out after the
+/// head padding
+#[repr(C, packed)]
+#[derive(Clone, Copy)]
+pub struct OpenOrders {
+    pub account_flags: u64,
+    pub market: Pubkey,
+    pub owner: Pubkey,
+    pub native_coin_free: u64,
+    pub native_coin_total: u64,
+    pub native_pc_free: u64,
+    pub native_pc_total: u64,
+    pub free_slot_bits: u128,
+    pub is_bid_bits: u128,
+    pub orders: [u128; 128],
+    pub client_order_ids: [u64; 128],
+    pub referrer_rebates_accrued: u64,
+}
+
+unsafe impl Zeroable for OpenOrders {}
+unsafe impl Pod for OpenOrders {}
+
+impl OpenOrders {
+    /// Parses the raw data of an open orders account
+    pub fn unpack(data: &[u8]) -> Result<&Self, ProgramError> {
+        unpack_state(data, ACCOUNT_FLAG_OPEN_ORDERS)
+    }
+
+    /// Borrows an open orders account owned by `dex_program_id`
+    pub fn load<'a>(dex_program_id: &Pubkey, open_orders_account: &'a AccountInfo) -> Result<Ref<'a, Self>, ProgramError> {
+        load_state(dex_program_id, open_orders_account, ACCOUNT_FLAG_OPEN_ORDERS)
+    }
+
+    /// Checks the account trades on `market` for `owner`
+    pub fn check(&self, market: &Pubkey, owner: &Pubkey) -> Result<(), ProgramError> {
+        if self.market != *market || self.owner != *owner {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        Ok(())
+    }
+
+    /// Free balance settling pays out on the side an order of `side` buys, pc for an ask
+    /// and coin for a bid
+    pub fn free_proceeds(&self, side: SerumSide) -> u64 {
+        match side {
+            SerumSide::Ask => self.native_pc_free,
+            SerumSide::Bid => self.native_coin_free,
+        }
+    }
+}
+
+/// State of an initialized dex account of the kind `flag`
+fn unpack_state<T: Pod>(data: &[u8], flag: u64) -> Result<&T, ProgramError> {
+    let state = strip_padding(data, size_of::<T>())?;
+    // every dex account leads with its flags
+    let flags = u64::from_le_bytes(*array_ref![state, 0, 8]);
+    if flags & (ACCOUNT_FLAG_INITIALIZED | flag) != ACCOUNT_FLAG_INITIALIZED | flag {
+        return Err(ProgramError::InvalidAccountData);
+    }
+    Ok(from_bytes(state))
+}
+
+fn load_state<'a, T: Pod>(dex_program_id: &Pubkey, account: &'a AccountInfo, flag: u64) -> Result<Ref<'a, T>, ProgramError> {
+    if account.owner != dex_program_id {
+        return Err(ProgramError::IncorrectProgramId);
+    }
+    let data = account.try_borrow_data()?;
+    unpack_state::<T>(&data, flag)?;
+    Ok(Ref::map(data, |data| {
+        from_bytes(&data[ACCOUNT_HEAD_PADDING.len()..ACCOUNT_HEAD_PADDING.len() + size_of::<T>()])
+    }))
+}
+
+/// Leading `len` bytes of the state of a dex account, permissioned markets append
+/// more fields before the tail padding
+fn strip_padding(data: &[u8], len: usize) -> Result<&[u8], ProgramError> {
+    let head = ACCOUNT_HEAD_PADDING.len();
+    let tail = ACCOUNT_TAIL_PADDING.len();
+    if data.len() < head + len + tail
+        || &data[..head] != ACCOUNT_HEAD_PADDING
+        || &data[data.len() - tail..] != ACCOUNT_TAIL_PADDING
+    {
+        return Err(ProgramError::InvalidAccountData);
+    }
+    Ok(&data[head..head + len])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn account_data(flags: u64, state: &[u8]) -> Vec<u8> {
+        let mut data = ACCOUNT_HEAD_PADDING.to_vec();
+        data.extend_from_slice(&flags.to_le_bytes());
+        data.extend_from_slice(&state[8..]);
+        data.extend_from_slice(ACCOUNT_TAIL_PADDING);
+        data
+    }
+
+    #[test]
+    fn test_layout_sizes() {
+        // sizes of the dex accounts less their padding
+        assert_eq!(size_of::<MarketState>(), 388 - 12);
+        assert_eq!(size_of::<OpenOrders>(), 3228 - 12);
+    }
+
+    #[test]
+    fn test_market_state_unpack() {
+        let coin_mint = Pubkey::new_unique();
+        let pc_mint = Pubkey::new_unique();
+        let mut state = vec![0u8; size_of::<MarketState>()];
+        state[40..48].copy_from_slice(&3u64.to_le_bytes());
+        state[48..80].copy_from_slice(coin_mint.as_ref());
+        state[80..112].copy_from_slice(pc_mint.as_ref());
+        state[344..352].copy_from_slice(&100u64.to_le_bytes());
+        state[360..368].copy_from_slice(&22u64.to_le_bytes());
+        let data = account_data(ACCOUNT_FLAG_INITIALIZED | ACCOUNT_FLAG_MARKET, &state);
+
+        let market = MarketState::unpack(&data).unwrap();
+        assert_eq!({ market.vault_signer_nonce }, 3);
+        assert_eq!({ market.coin_mint }, coin_mint);
+        assert_eq!({ market.pc_mint }, pc_mint);
+        assert_eq!({ market.coin_lot_size }, 100);
+        assert_eq!({ market.fee_rate_bps }, 22);
+
+        // permissioned markets append fields before the tail padding
+        let mut extended = data[..data.len() - ACCOUNT_TAIL_PADDING.len()].to_vec();
+        extended.extend_from_slice(&[0u8; 64]);
+        extended.extend_from_slice(ACCOUNT_TAIL_PADDING);
+        assert_eq!({ MarketState::unpack(&extended).unwrap().coin_mint }, coin_mint);
+
+        // uninitialized or open orders accounts are not markets
+        let uninitialized = account_data(ACCOUNT_FLAG_MARKET, &state);
+        assert_eq!(MarketState::unpack(&uninitialized).err(), Some(ProgramError::InvalidAccountData));
+        let open_orders = account_data(ACCOUNT_FLAG_INITIALIZED | ACCOUNT_FLAG_OPEN_ORDERS, &state);
+        assert_eq!(MarketState::unpack(&open_orders).err(), Some(ProgramError::InvalidAccountData));
+    }
+
+    #[test]
+    fn test_unpack_padding() {
+        let state = vec![0u8; size_of::<MarketState>()];
+        let data = account_data(ACCOUNT_FLAG_INITIALIZED | ACCOUNT_FLAG_MARKET, &state);
+        assert!(MarketState::unpack(&data).is_ok());
+
+        let mut bad_head = data.clone();
+        bad_head[0] = b'x';
+        assert_eq!(MarketState::unpack(&bad_head).err(), Some(ProgramError::InvalidAccountData));
+
+        let mut bad_tail = data.clone();
+        *bad_tail.last_mut().unwrap() = b'x';
+        assert_eq!(MarketState::unpack(&bad_tail).err(), Some(ProgramError::InvalidAccountData));
+
+        let mut short = data[..data.len() - ACCOUNT_TAIL_PADDING.len() - 1].to_vec();
+        short.extend_from_slice(ACCOUNT_TAIL_PADDING);
+        assert_eq!(MarketState::unpack(&short).err(), Some(ProgramError::InvalidAccountData));
+    }
+
+    #[test]
+    fn test_open_orders_unpack() {
+        let market = Pubkey::new_unique();
+        let owner = Pubkey::new_unique();
+        let mut state = vec![0u8; size_of::<OpenOrders>()];
+        state[8..40].copy_from_slice(market.as_ref());
+        state[40..72].copy_from_slice(owner.as_ref());
+        state[72..80].copy_from_slice(&5u64.to_le_bytes());
+        state[88..96].copy_from_slice(&7u64.to_le_bytes());
+        let data = account_data(ACCOUNT_FLAG_INITIALIZED | ACCOUNT_FLAG_OPEN_ORDERS, &state);
+
+        let open_orders = OpenOrders::unpack(&data).unwrap();
+        assert_eq!({ open_orders.native_coin_free }, 5);
+        assert_eq!({ open_orders.native_pc_free }, 7);
+        assert_eq!(open_orders.free_proceeds(SerumSide::Ask), 7);
+        assert_eq!(open_orders.free_proceeds(SerumSide::Bid), 5);
+        assert!(open_orders.check(&market, &owner).is_ok());
+        assert_eq!(open_orders.check(&market, &market).err(), Some(ProgramError::InvalidAccountData));
+        assert_eq!(open_orders.check(&owner, &owner).err(), Some(ProgramError::InvalidAccountData));
+
+        let market_data = account_data(ACCOUNT_FLAG_INITIALIZED | ACCOUNT_FLAG_MARKET, &state);
+        assert_eq!(OpenOrders::unpack(&market_data).err(), Some(ProgramError::InvalidAccountData));
+    }
+
+    #[test]
+    fn test_load_owner() {
+        let dex_program_id = Pubkey::new_unique();
+        let key = Pubkey::new_unique();
+        let state = vec![0u8; size_of::<MarketState>()];
+        let mut data = account_data(ACCOUNT_FLAG_INITIALIZED | ACCOUNT_FLAG_MARKET, &state);
+        let mut lamports = 0;
+        let account = AccountInfo::new(&key, false, false, &mut lamports, &mut data, &dex_program_id, false, 0);
+        assert!(MarketState::load(&dex_program_id, &account).is_ok());
+        assert_eq!(MarketState::load(&key, &account).err(), Some(ProgramError::IncorrectProgramId));
+    }
+}
@@ -0,0 +1,160 @@
+//! StableSwap invariant of two coin stable pools
+//!
+//! `A * n^n * (x + y) + D = A * n^n * D + D^(n+1) / (n^n * x * y)` with `n = 2`,
+//! solved by Newton's method. Amounts must be normalized to the same decimals.
+
+use spl_math::uint::U256;
+use std::convert::TryFrom;
+
+/// Number of coins in the pool
+const N_COINS: u64 = 2;
+/// Newton iterations before giving up
+const MAX_ITERATIONS: usize = 255;
+
+/// Invariant `D` of the balances `x` and `y` for the amplification `amp`
+pub fn compute_d(amp: u64, x: u128, y: u128) -> Option<U256> {
+    let sum = U256::from(x).checked_add(U256::from(y))?;
+    if sum.is_zero() {
+        return Some(U256::zero());
+    }
+    if x == 0 || y == 0 {
+        return None;
+    }
+    let ann = U256::from(amp).checked_mul(U256::from(N_COINS * N_COINS))?;
+    let mut d = sum;
+    for _ in 0..MAX_ITERATIONS {
+        // D^(n+1) / (n^n * x * y)
+        let d_prod = d
+            .checked_mul(d)?
+            .checked_div(U256::from(x).checked_mul(U256::from(N_COINS))?)?
+            .checked_mul(d)?
+            .checked_div(U256::from(y).checked_mul(U256::from(N_COINS))?)?;
+        let d_prev = d;
+        let numerator = ann
+            .checked_mul(sum)?
+            .checked_add(d_prod.checked_mul(U256::from(N_COINS))?)?
+            .checked_mul(d)?;
+        let denominator = ann
+            .checked_sub(U256::one())?
+            .checked_mul(d)?
+            .checked_add(d_prod.checked_mul(U256::from(N_COINS + 1))?)?;
+        d = numerator.checked_div(denominator)?;
+        if abs_diff(d, d_prev) <= U256::one() {
+            return Some(d);
+        }
+    }
+    None
+}
+
+/// Balance `y` keeping the invariant `d` once the other balance is `x`
+pub fn compute_y(amp: u64, x: u128, d: U256) -> Option<U256> {
+    if x == 0 {
+        return None;
+    }
+    let ann = U256::from(amp).checked_mul(U256::from(N_COINS * N_COINS))?;
+    // c = D^(n+1) / (n^n * x * Ann), b = x + D / Ann
+    let c = d
+        .checked_mul(d)?
+        .checked_div(U256::from(x).checked_mul(U256::from(N_COINS))?)?
+        .checked_mul(d)?
+        .checked_div(ann.checked_mul(U256::from(N_COINS))?)?;
+    let b = U256::from(x).checked_add(d.checked_div(ann)?)?;
+    let mut y = d;
+    for _ in 0..MAX_ITERATIONS {
+        let y_prev = y;
+        // y = (y^2 + c) / (2y + b - D)
+        let numerator = y.checked_mul(y)?.checked_add(c)?;
+        let denominator = y
+            .checked_mul(U256::from(2u8))?
+            .checked_add(b)?
+            .checked_sub(d)?;
+        y = numerator.checked_div(denominator)?;
+        if abs_diff(y, y_prev) <= U256::one() {
+            return Some(y);
+        }
+    }
+    None
+}
+
+/// Output for `amount_in` of a pool holding `reserve_in` and `reserve_out`, before any fee.
+/// Rounded down by one unit so the quote never exceeds what the invariant pays.
+pub fn swap_amount_out(amp: u64, reserve_in: u128, reserve_out: u128, amount_in: u128) -> Option<u128> {
+    let d = compute_d(amp, reserve_in, reserve_out)?;
+    let new_reserve_in = reserve_in.checked_add(amount_in)?;
+    let new_reserve_out = compute_y(amp, new_reserve_in, d)?;
+    let amount_out = U256::from(reserve_out)
+        .checked_sub(new_reserve_out)?
+        .saturating_sub(U256::one());
+    if amount_out > U256::from(u128::MAX) {
+        return None;
+    }
+    Some(amount_out.as_u128())
+}
+
+/// Output for `amount_in` of a pool holding `reserve_in` and `reserve_out` of the given
+/// decimals, before any fee. The invariant compares balances at the larger decimals
+pub fn quote(
+    amp: u64,
+    reserve_in: u64,
+    decimals_in: u64,
+    reserve_out: u64,
+    decimals_out: u64,
+    amount_in: u128,
+) -> Option<u128> {
+    let decimals = decimals_in.max(decimals_out);
+    let scale_in = 10u128.checked_pow(u32::try_from(decimals - decimals_in).ok()?)?;
+    let scale_out = 10u128.checked_pow(u32::try_from(decimals - decimals_out).ok()?)?;
+    let amount_out = swap_amount_out(
+        amp,
+        u128::from(reserve_in).checked_mul(scale_in)?,
+        u128::from(reserve_out).checked_mul(scale_out)?,
+        amount_in.checked_mul(scale_in)?,
+    )?;
+    Some(amount_out / scale_out)
+}
+
+fn abs_diff(a: U256, b: U256) -> U256 {
+    if a > b {
+        a - b
+    } else {
+        b - a
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_compute_d() {
+        // a balanced pool holds its invariant
+        assert_eq!(compute_d(100, 1_000_000, 1_000_000), Some(U256::from(2_000_000u64)));
+        assert_eq!(compute_d(100, 0, 0), Some(U256::zero()));
+        assert_eq!(compute_d(100, 1_000_000, 0), None);
+        // an imbalanced pool is worth less than the sum of its balances
+        let d = compute_d(100, 1_500_000_000_000, 500_000_000_000).unwrap();
+        assert!(d < U256::from(2_000_000_000_000u64) && d > U256::from(1_990_000_000_000u64));
+        assert_eq!(compute_y(100, 1_500_000_000_000, d).map(|y| abs_diff(y, U256::from(500_000_000_000u64)) <= U256::one()), Some(true));
+    }
+
+    #[test]
+    fn test_swap_amount_out() {
+        // exact solutions of the invariant are 999995024.89, 991229459.56, 1008807179.88
+        // and 96760741606.77, the quote never exceeds them
+        assert_eq!(swap_amount_out(100, 1_000_000_000_000, 1_000_000_000_000, 1_000_000_000), Some(999_995_024));
+        assert_eq!(swap_amount_out(100, 1_500_000_000_000, 500_000_000_000, 1_000_000_000), Some(991_229_459));
+        assert_eq!(swap_amount_out(100, 500_000_000_000, 1_500_000_000_000, 1_000_000_000), Some(1_008_807_179));
+        assert_eq!(swap_amount_out(1, 1_000_000_000_000, 1_000_000_000_000, 100_000_000_000), Some(96_760_741_606));
+        assert_eq!(swap_amount_out(100, 1_000_000, 1_000_000, 0), Some(0));
+    }
+
+    #[test]
+    fn test_quote_decimals() {
+        // 1M of a 6 decimals coin against 1M of a 9 decimals coin, 1000 in comes out
+        // as the balanced pool above, in 9 decimals
+        assert_eq!(quote(100, 1_000_000_000_000, 6, 1_000_000_000_000_000, 9, 1_000_000_000), Some(999_995_024_895));
+        // back the other way the output is rounded down to 6 decimals
+        assert_eq!(quote(100, 1_000_000_000_000_000, 9, 1_000_000_000_000, 6, 1_000_000_000_000), Some(999_995_024));
+        assert_eq!(quote(100, 0, 6, 1_000_000_000_000, 6, 1), None);
+    }
+}
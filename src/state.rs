@@ -2,7 +2,6 @@
 
 use crate::error::AmmError;
 use solana_program::{
-    entrypoint::ProgramResult,
     program_error::ProgramError,
     pubkey::Pubkey,
     account_info::AccountInfo,
@@ -11,10 +10,10 @@ use solana_program::{
 
 use safe_transmute::{self, trivial::TriviallyTransmutable};
 use bytemuck::{
-     Pod, Zeroable, cast_slice_mut, from_bytes_mut,
+     Pod, Zeroable, bytes_of_mut, cast_slice_mut, from_bytes_mut,
 };
 use arrayref::{array_mut_ref, array_ref, array_refs, mut_array_refs};
-use std::{cell::RefMut};
+use std::{cell::RefMut, convert::TryInto, mem::size_of};
 
 #[repr(u64)]
 pub enum AmmStatus {
@@ -43,12 +42,7 @@ impl AmmStatus {
         }
     }
     pub fn valid_status(status: u64) -> bool {
-        match status {
-            1u64 | 2u64 | 3u64 => {
-                return true
-            },
-            _ => return false
-        }
+        matches!(status, 1u64..=3u64)
     }
 }
 
@@ -76,12 +70,7 @@ impl AmmParams {
         }
     }
     pub fn valid_status(status: u64) -> bool {
-        match status {
-            1u64 | 2u64 | 3u64 => {
-                return true
-            },
-            _ => return false
-        }
+        matches!(status, 1u64..=3u64)
     }
 }
 
@@ -124,12 +113,7 @@ impl AmmState {
         }
     }
     pub fn valid_state(state: u64) -> bool {
-        match state {
-            0u64 | 1u64 | 2u64 | 3u64 | 4u64 | 5u64 | 6u64 | 7u64=> {
-                return true
-            },
-            _ => return false
-        }
+        matches!(state, 0u64..=7u64)
     }
 }
 
@@ -367,11 +351,8 @@ impl AmmInfo {
     /// Helper function to get the more efficient packed size of the struct
     #[inline]
     pub fn load_amm_mut<'a>(amm_account:&'a AccountInfo, check_status: bool) -> Result<RefMut<'a, AmmInfo>, ProgramError> {
-        let account_data: RefMut<'a, [u8]>;
-        let amm_data: RefMut<'a, AmmInfo>;
-
-        account_data = RefMut::map(amm_account.try_borrow_mut_data()?, |data| *data);
-        amm_data = RefMut::map(account_data, |data| {
+        let account_data: RefMut<'a, [u8]> = RefMut::map(amm_account.try_borrow_mut_data()?, |data| *data);
+        let amm_data: RefMut<'a, AmmInfo> = RefMut::map(account_data, |data| {
             from_bytes_mut(cast_slice_mut(data))
         });
         if check_status {
@@ -380,6 +361,17 @@ impl AmmInfo {
         Ok(amm_data)
     }
 
+    /// Copies the amm out of raw account data, which off chain may not be aligned
+    pub fn unpack_amm(data: &[u8]) -> Result<AmmInfo, ProgramError> {
+        if data.len() != size_of::<AmmInfo>() {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        let mut amm = AmmInfo::zeroed();
+        bytes_of_mut(&mut amm).copy_from_slice(data);
+        amm.check_status()?;
+        Ok(amm)
+    }
+
     #[inline]
     pub fn check_status(&self) -> Result<bool, ProgramError> {
         if self.status == AmmStatus::Uninitialized as u64 {
@@ -389,4 +381,1910 @@ impl AmmInfo {
             Ok(true)
         }
     }
-}
\ No newline at end of file
+}
+
+/// Raydium stable pool. Shares the fees and pnl accounting of `AmmInfo` and prices
+/// swaps with the StableSwap invariant of `amp` instead of the constant product
+#[cfg_attr(feature = "client", derive(Debug))]
+#[repr(C)]
+#[derive(Clone, Copy, Default, PartialEq)]
+pub struct StableAmmInfo {
+    /// 1 Initialized status.
+    pub status: u64,
+    /// Nonce used in program address of the pool authority
+    pub nonce: u64,
+    /// max order count
+    pub order_num: u64,
+    /// within this range, 5 => 5% range
+    pub depth: u64,
+    /// coin decimal
+    pub coin_decimals: u64,
+    /// pc decimal
+    pub pc_decimals: u64,
+    /// amm state
+    pub state: u64,
+    /// amm reset_flag
+    pub reset_flag: u64,
+    /// min size 1->0.000001
+    pub min_size: u64,
+    /// vol_max_cut_ratio numerator, sys_decimal_value as denominator
+    pub vol_max_cut_ratio: u64,
+    /// amount wave numerator, sys_decimal_value as denominator
+    pub amount_wave: u64,
+    /// coinLotSize 1 -> 0.000001
+    pub coin_lot_size: u64,
+    /// pcLotSize 1 -> 0.000001
+    pub pc_lot_size: u64,
+    /// min_cur_price: (2 * amm.order_num * amm.pc_lot_size) * max_price_multiplier
+    pub min_price_multiplier: u64,
+    /// max_cur_price: (2 * amm.order_num * amm.pc_lot_size) * max_price_multiplier
+    pub max_price_multiplier: u64,
+    /// system decimal value, used to normalize the value of coin and pc amount
+    pub sys_decimal_value: u64,
+    /// amplification coefficient of the stable invariant
+    pub amp: u64,
+    /// keeps `fees` and `out_put` aligned
+    pub padding: u64,
+    /// All fee information
+    pub fees: Fees,
+    /// data calc to output
+    pub out_put: OutPutData,
+    /// Token coin
+    pub token_coin: Pubkey,
+    /// Token pc
+    pub token_pc: Pubkey,
+    /// Coin mint
+    pub coin_mint: Pubkey,
+    /// Pc mint
+    pub pc_mint: Pubkey,
+    /// lp mint
+    pub lp_mint: Pubkey,
+    /// model data key, the curve table shared by the stable pools
+    pub model_data: Pubkey,
+    /// open_orders key
+    pub open_orders: Pubkey,
+    /// market key
+    pub market: Pubkey,
+    /// serum dex key
+    pub serum_dex: Pubkey,
+    /// target_orders key
+    pub target_orders: Pubkey,
+    /// amm owner key
+    pub amm_owner: Pubkey,
+}
+
+#[cfg(target_endian = "little")]
+unsafe impl Zeroable for StableAmmInfo {}
+#[cfg(target_endian = "little")]
+unsafe impl Pod for StableAmmInfo {}
+#[cfg(target_endian = "little")]
+unsafe impl TriviallyTransmutable for StableAmmInfo {}
+
+impl StableAmmInfo {
+    #[inline]
+    pub fn load_amm_mut<'a>(amm_account: &'a AccountInfo, check_status: bool) -> Result<RefMut<'a, StableAmmInfo>, ProgramError> {
+        let account_data: RefMut<'a, [u8]> = RefMut::map(amm_account.try_borrow_mut_data()?, |data| *data);
+        let amm_data: RefMut<'a, StableAmmInfo> = RefMut::map(account_data, |data| {
+            from_bytes_mut(cast_slice_mut(data))
+        });
+        if check_status && amm_data.status == AmmStatus::Uninitialized as u64 {
+            return Err(AmmError::InvalidStatus.into());
+        }
+        Ok(amm_data)
+    }
+}
+
+/// Seed prefix of the tick arrays of a Raydium CLMM pool
+pub const CLMM_TICK_ARRAY_SEED: &[u8] = b"tick_array";
+/// Ticks held by one Raydium CLMM tick array
+pub const CLMM_TICK_ARRAY_SIZE: i32 = 60;
+
+/// Leading fields of a Raydium CLMM `PoolState` anchor account, up to the current tick
+#[cfg_attr(feature = "client", derive(Debug))]
+#[derive(Clone, Copy, Default, PartialEq)]
+pub struct ClmmPoolState {
+    /// config holding the trade fee rate of the pool
+    pub amm_config: Pubkey,
+    /// pool owner
+    pub owner: Pubkey,
+    /// Token 0 mint, the coin side
+    pub token_mint_0: Pubkey,
+    /// Token 1 mint, the pc side
+    pub token_mint_1: Pubkey,
+    /// Token 0 vault
+    pub token_vault_0: Pubkey,
+    /// Token 1 vault
+    pub token_vault_1: Pubkey,
+    /// observation account written by every swap
+    pub observation_key: Pubkey,
+    /// Token 0 decimals
+    pub mint_decimals_0: u8,
+    /// Token 1 decimals
+    pub mint_decimals_1: u8,
+    /// ticks between two initializable ticks
+    pub tick_spacing: u16,
+    /// liquidity in range at the current price
+    pub liquidity: u128,
+    /// current square root price, Q64.64
+    pub sqrt_price_x64: u128,
+    /// tick of the current price
+    pub tick_current: i32,
+}
+
+impl ClmmPoolState {
+    /// Anchor discriminator of `account:PoolState`
+    pub const DISCRIMINATOR: [u8; 8] = [247, 237, 227, 245, 215, 195, 222, 70];
+    /// Bytes read from the start of the account
+    pub const LEN: usize = 273;
+
+    /// Parses a pool state account owned by the CLMM program `clmm_program_id`
+    pub fn load(clmm_program_id: &Pubkey, pool_account: &AccountInfo) -> Result<Self, ProgramError> {
+        if pool_account.owner != clmm_program_id {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        let data = pool_account.try_borrow_data()?;
+        if data.len() < Self::LEN || data[..8] != Self::DISCRIMINATOR {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        let input = array_ref![data, 0, ClmmPoolState::LEN];
+        #[allow(clippy::ptr_offset_with_cast)]
+        let (
+            _discriminator,
+            _bump,
+            amm_config,
+            owner,
+            token_mint_0,
+            token_mint_1,
+            token_vault_0,
+            token_vault_1,
+            observation_key,
+            mint_decimals_0,
+            mint_decimals_1,
+            tick_spacing,
+            liquidity,
+            sqrt_price_x64,
+            tick_current,
+        ) = array_refs![input, 8, 1, 32, 32, 32, 32, 32, 32, 32, 1, 1, 2, 16, 16, 4];
+        Ok(Self {
+            amm_config: Pubkey::new_from_array(*amm_config),
+            owner: Pubkey::new_from_array(*owner),
+            token_mint_0: Pubkey::new_from_array(*token_mint_0),
+            token_mint_1: Pubkey::new_from_array(*token_mint_1),
+            token_vault_0: Pubkey::new_from_array(*token_vault_0),
+            token_vault_1: Pubkey::new_from_array(*token_vault_1),
+            observation_key: Pubkey::new_from_array(*observation_key),
+            mint_decimals_0: mint_decimals_0[0],
+            mint_decimals_1: mint_decimals_1[0],
+            tick_spacing: u16::from_le_bytes(*tick_spacing),
+            liquidity: u128::from_le_bytes(*liquidity),
+            sqrt_price_x64: u128::from_le_bytes(*sqrt_price_x64),
+            tick_current: i32::from_le_bytes(*tick_current),
+        })
+    }
+
+    /// Start tick of the tick array holding `tick`
+    pub fn tick_array_start_index(&self, tick: i32) -> i32 {
+        let ticks_in_array = CLMM_TICK_ARRAY_SIZE * i32::from(self.tick_spacing);
+        tick.div_euclid(ticks_in_array) * ticks_in_array
+    }
+
+    /// Start ticks of `count` consecutive tick arrays a swap walks through from the current
+    /// tick, downwards for token 0 in and upwards for token 1 in
+    pub fn swap_tick_array_start_indexes(&self, zero_for_one: bool, count: u8) -> Vec<i32> {
+        let ticks_in_array = CLMM_TICK_ARRAY_SIZE * i32::from(self.tick_spacing);
+        let step = if zero_for_one { -ticks_in_array } else { ticks_in_array };
+        let first = self.tick_array_start_index(self.tick_current);
+        (0..i32::from(count)).map(|i| first + i * step).collect()
+    }
+}
+
+/// Header of a Raydium CLMM `TickArrayState` anchor account
+#[cfg_attr(feature = "client", derive(Debug))]
+#[derive(Clone, Copy, Default, PartialEq)]
+pub struct ClmmTickArrayState {
+    /// pool the ticks belong to
+    pub pool_id: Pubkey,
+    /// first tick of the array
+    pub start_tick_index: i32,
+}
+
+impl ClmmTickArrayState {
+    /// Anchor discriminator of `account:TickArrayState`
+    pub const DISCRIMINATOR: [u8; 8] = [192, 155, 85, 205, 49, 249, 129, 42];
+    /// Bytes read from the start of the account
+    pub const LEN: usize = 44;
+
+    /// Address of the tick array of `pool` starting at `start_tick_index`
+    pub fn find_address(clmm_program_id: &Pubkey, pool: &Pubkey, start_tick_index: i32) -> (Pubkey, u8) {
+        Pubkey::find_program_address(
+            &[CLMM_TICK_ARRAY_SEED, pool.as_ref(), &start_tick_index.to_be_bytes()],
+            clmm_program_id,
+        )
+    }
+
+    /// Parses a tick array account owned by the CLMM program `clmm_program_id`
+    pub fn load(clmm_program_id: &Pubkey, tick_array_account: &AccountInfo) -> Result<Self, ProgramError> {
+        if tick_array_account.owner != clmm_program_id {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        let data = tick_array_account.try_borrow_data()?;
+        if data.len() < Self::LEN || data[..8] != Self::DISCRIMINATOR {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        let input = array_ref![data, 0, ClmmTickArrayState::LEN];
+        #[allow(clippy::ptr_offset_with_cast)]
+        let (_discriminator, pool_id, start_tick_index) = array_refs![input, 8, 32, 4];
+        Ok(Self {
+            pool_id: Pubkey::new_from_array(*pool_id),
+            start_tick_index: i32::from_le_bytes(*start_tick_index),
+        })
+    }
+}
+
+/// Trade fee rate of a Raydium CLMM `AmmConfig` anchor account
+#[cfg_attr(feature = "client", derive(Debug))]
+#[derive(Clone, Copy, Default, PartialEq)]
+pub struct ClmmAmmConfig {
+    /// swap fee, in hundredths of a basis point
+    pub trade_fee_rate: u32,
+}
+
+impl ClmmAmmConfig {
+    /// Anchor discriminator of `account:AmmConfig`
+    pub const DISCRIMINATOR: [u8; 8] = [218, 244, 33, 104, 203, 203, 43, 111];
+    /// Bytes read from the start of the account
+    pub const LEN: usize = 51;
+    /// Denominator of `trade_fee_rate`
+    pub const FEE_RATE_DENOMINATOR: u64 = 1_000_000;
+
+    /// Parses an amm config account owned by the CLMM program `clmm_program_id`
+    pub fn load(clmm_program_id: &Pubkey, config_account: &AccountInfo) -> Result<Self, ProgramError> {
+        if config_account.owner != clmm_program_id {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        let data = config_account.try_borrow_data()?;
+        if data.len() < Self::LEN || data[..8] != Self::DISCRIMINATOR {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        let input = array_ref![data, 0, ClmmAmmConfig::LEN];
+        #[allow(clippy::ptr_offset_with_cast)]
+        let (_discriminator, _bump, _index, _owner, _protocol_fee_rate, trade_fee_rate) =
+            array_refs![input, 8, 1, 2, 32, 4, 4];
+        Ok(Self {
+            trade_fee_rate: u32::from_le_bytes(*trade_fee_rate),
+        })
+    }
+}
+
+/// Seed of the provider config PDA
+pub const CONFIG_SEED: &[u8] = b"config";
+/// Seed prefix of the per mint fee config PDA
+pub const FEE_CONFIG_SEED: &[u8] = b"fee_config";
+/// Seed prefix of the per mint fee vault token account
+pub const FEE_VAULT_SEED: &[u8] = b"fee_vault";
+/// Seed prefix of the per user temporary wrapped SOL account
+pub const WSOL_SEED: &[u8] = b"wsol";
+/// Seed prefix of the per pool registry PDA
+pub const POOL_REGISTRY_SEED: &[u8] = b"pool_registry";
+/// Seed prefix of the per pool auto-compounding lp vault PDA
+pub const LP_VAULT_SEED: &[u8] = b"lp_vault";
+/// Seed prefix of the share mint PDA of an lp vault
+pub const SHARE_MINT_SEED: &[u8] = b"share_mint";
+/// Seed prefix of the per user limit order PDA
+pub const LIMIT_ORDER_SEED: &[u8] = b"limit_order";
+/// Seed prefix of the per user dollar-cost-averaging schedule PDA
+pub const DCA_SEED: &[u8] = b"dca";
+/// Seed prefix of the per user stop-loss / take-profit order PDA
+pub const TRIGGER_ORDER_SEED: &[u8] = b"trigger_order";
+/// Seed prefix of the token escrows of an order, `[ESCROW_SEED, order, mint]`
+pub const ESCROW_SEED: &[u8] = b"escrow";
+/// Max number of receivers a single commission can be split between
+pub const MAX_FEE_RECEIVERS: usize = 4;
+/// Receiver weights are basis points and must add up to this
+pub const TOTAL_WEIGHT_BPS: u16 = 10_000;
+
+/// User facing operations the guardian can pause
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Operation {
+    Swap,
+    Deposit,
+    Withdraw,
+    /// only stopped by the global pause
+    SweepFees,
+    /// only stopped by the global pause
+    Compound,
+}
+
+/// Global provider settings, stored at the `CONFIG_SEED` PDA
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct ProviderConfig {
+    /// Initialized state
+    pub is_initialized: bool,
+    /// bump seed of the config PDA
+    pub bump_seed: u8,
+    /// key allowed to change fee configs
+    pub admin: Pubkey,
+    /// key allowed to pause and resume the provider
+    pub guardian: Pubkey,
+    /// stops every user facing operation
+    pub paused: bool,
+    /// stops swaps
+    pub swap_paused: bool,
+    /// stops deposits
+    pub deposit_paused: bool,
+    /// stops withdrawals
+    pub withdraw_paused: bool,
+    /// lamports charged to the creator of a Raydium pool
+    pub listing_fee_lamports: u64,
+    /// system account receiving the listing fee
+    pub listing_treasury: Pubkey,
+    /// Raydium farm program staking instructions go to, unset until the admin sets it
+    pub farm_program_id: Pubkey,
+}
+
+impl ProviderConfig {
+    /// Derives the config PDA
+    pub fn find_address(program_id: &Pubkey) -> (Pubkey, u8) {
+        Pubkey::find_program_address(&[CONFIG_SEED], program_id)
+    }
+
+    /// Unpacks the config and checks it lives at the PDA owned by this program
+    pub fn load(program_id: &Pubkey, config_account: &AccountInfo) -> Result<Self, ProgramError> {
+        if config_account.owner != program_id {
+            return Err(AmmError::InvalidConfig.into());
+        }
+        let config = Self::unpack(&config_account.data.borrow())?;
+        let address = Pubkey::create_program_address(&[CONFIG_SEED, &[config.bump_seed]], program_id)
+            .map_err(|_| AmmError::InvalidConfig)?;
+        if address != *config_account.key {
+            return Err(AmmError::InvalidConfig.into());
+        }
+        Ok(config)
+    }
+
+    /// Fails if `operation` is paused, globally or on its own
+    pub fn check_not_paused(&self, operation: Operation) -> Result<(), AmmError> {
+        let operation_paused = match operation {
+            Operation::Swap => self.swap_paused,
+            Operation::Deposit => self.deposit_paused,
+            Operation::Withdraw => self.withdraw_paused,
+            Operation::SweepFees | Operation::Compound => false,
+        };
+        if self.paused || operation_paused {
+            return Err(AmmError::Paused);
+        }
+        Ok(())
+    }
+}
+
+impl IsInitialized for ProviderConfig {
+    fn is_initialized(&self) -> bool {
+        self.is_initialized
+    }
+}
+
+impl Sealed for ProviderConfig {}
+impl Pack for ProviderConfig {
+    const LEN: usize = 142;
+    fn pack_into_slice(&self, output: &mut [u8]) {
+        let output = array_mut_ref![output, 0, 142];
+        let (
+            is_initialized,
+            bump_seed,
+            admin,
+            guardian,
+            paused,
+            swap_paused,
+            deposit_paused,
+            withdraw_paused,
+            listing_fee_lamports,
+            listing_treasury,
+            farm_program_id,
+        ) = mut_array_refs![output, 1, 1, 32, 32, 1, 1, 1, 1, 8, 32, 32];
+        is_initialized[0] = self.is_initialized as u8;
+        bump_seed[0] = self.bump_seed;
+        admin.copy_from_slice(self.admin.as_ref());
+        guardian.copy_from_slice(self.guardian.as_ref());
+        paused[0] = self.paused as u8;
+        swap_paused[0] = self.swap_paused as u8;
+        deposit_paused[0] = self.deposit_paused as u8;
+        withdraw_paused[0] = self.withdraw_paused as u8;
+        *listing_fee_lamports = self.listing_fee_lamports.to_le_bytes();
+        listing_treasury.copy_from_slice(self.listing_treasury.as_ref());
+        farm_program_id.copy_from_slice(self.farm_program_id.as_ref());
+    }
+
+    fn unpack_from_slice(input: &[u8]) -> Result<ProviderConfig, ProgramError> {
+        let input = array_ref![input, 0, 142];
+        #[allow(clippy::ptr_offset_with_cast)]
+        let (
+            is_initialized,
+            bump_seed,
+            admin,
+            guardian,
+            paused,
+            swap_paused,
+            deposit_paused,
+            withdraw_paused,
+            listing_fee_lamports,
+            listing_treasury,
+            farm_program_id,
+        ) = array_refs![input, 1, 1, 32, 32, 1, 1, 1, 1, 8, 32, 32];
+        Ok(Self {
+            is_initialized: unpack_bool(is_initialized)?,
+            bump_seed: bump_seed[0],
+            admin: Pubkey::new_from_array(*admin),
+            guardian: Pubkey::new_from_array(*guardian),
+            paused: unpack_bool(paused)?,
+            swap_paused: unpack_bool(swap_paused)?,
+            deposit_paused: unpack_bool(deposit_paused)?,
+            withdraw_paused: unpack_bool(withdraw_paused)?,
+            listing_fee_lamports: u64::from_le_bytes(*listing_fee_lamports),
+            listing_treasury: Pubkey::new_from_array(*listing_treasury),
+            farm_program_id: Pubkey::new_from_array(*farm_program_id),
+        })
+    }
+}
+
+/// One destination of a split commission
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct FeeReceiver {
+    /// token account receiving this share, same mint as the fee config
+    pub token_account: Pubkey,
+    /// share of the commission in basis points
+    pub weight_bps: u16,
+}
+
+/// How the provider charges trades of a mint
+#[repr(u8)]
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum FeeModel {
+    /// percentage commission in the token, paid to the receivers
+    #[default]
+    Percentage = 0u8,
+    /// flat lamport fee paid by the signer to the treasury
+    Flat = 1u8,
+    /// both the commission and the flat fee
+    PercentageAndFlat = 2u8,
+}
+impl FeeModel {
+    pub fn from_u8(model: u8) -> Result<Self, AmmError> {
+        match model {
+            0u8 => Ok(FeeModel::Percentage),
+            1u8 => Ok(FeeModel::Flat),
+            2u8 => Ok(FeeModel::PercentageAndFlat),
+            _ => Err(AmmError::InvalidFee),
+        }
+    }
+}
+
+/// Commission split for one mint, stored at the `[FEE_CONFIG_SEED, mint]` PDA
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct FeeConfig {
+    /// Initialized state
+    pub is_initialized: bool,
+    /// bump seed of the fee config PDA
+    pub bump_seed: u8,
+    /// mint the commission is collected in
+    pub mint: Pubkey,
+    /// number of used entries in `receivers`
+    pub receiver_count: u8,
+    /// receivers, the first one also takes the rounding dust
+    pub receivers: [FeeReceiver; MAX_FEE_RECEIVERS],
+    /// fee vault token account, its authority is this fee config. Default key if not created
+    pub vault: Pubkey,
+    /// fees are transferred to `vault` and swept to the receivers later
+    pub accrue_to_vault: bool,
+    /// which fees are charged
+    pub fee_model: FeeModel,
+    /// flat fee in lamports
+    pub flat_fee_lamports: u64,
+    /// system account receiving the flat fee
+    pub treasury: Pubkey,
+    /// commission on harvested farm rewards of this mint, in basis points
+    pub reward_fee_bps: u16,
+}
+
+impl FeeConfig {
+    /// Derives the fee config PDA of `mint`
+    pub fn find_address(program_id: &Pubkey, mint: &Pubkey) -> (Pubkey, u8) {
+        Pubkey::find_program_address(&[FEE_CONFIG_SEED, mint.as_ref()], program_id)
+    }
+
+    /// Unpacks the fee config of `mint` and checks it lives at its PDA
+    pub fn load(program_id: &Pubkey, fee_config_account: &AccountInfo, mint: &Pubkey) -> Result<Self, ProgramError> {
+        if fee_config_account.owner != program_id {
+            return Err(AmmError::InvalidConfig.into());
+        }
+        let fee_config = Self::unpack(&fee_config_account.data.borrow())?;
+        let address = Pubkey::create_program_address(
+            &[FEE_CONFIG_SEED, mint.as_ref(), &[fee_config.bump_seed]],
+            program_id,
+        ).map_err(|_| AmmError::InvalidConfig)?;
+        if address != *fee_config_account.key || fee_config.mint != *mint {
+            return Err(AmmError::InvalidConfig.into());
+        }
+        Ok(fee_config)
+    }
+
+    /// Derives the fee vault token account of `mint`
+    pub fn find_vault_address(program_id: &Pubkey, mint: &Pubkey) -> (Pubkey, u8) {
+        Pubkey::find_program_address(&[FEE_VAULT_SEED, mint.as_ref()], program_id)
+    }
+
+    /// Seeds signing for the fee config PDA, which is the authority of the fee vault
+    pub fn signer_seeds<'a>(&'a self, bump_seed: &'a [u8; 1]) -> [&'a [u8]; 3] {
+        [FEE_CONFIG_SEED, self.mint.as_ref(), bump_seed]
+    }
+
+    /// Whether a percentage commission is taken in the token
+    pub fn charges_commission(&self) -> bool {
+        self.fee_model != FeeModel::Flat
+    }
+
+    /// Whether a flat lamport fee is charged
+    pub fn charges_flat_fee(&self) -> bool {
+        self.fee_model != FeeModel::Percentage
+    }
+
+    /// Whether a commission is taken on harvested farm rewards
+    pub fn charges_reward_fee(&self) -> bool {
+        self.reward_fee_bps > 0
+    }
+
+    /// Commission on `amount` of harvested farm rewards
+    pub fn reward_commission(&self, amount: u64) -> Result<u64, AmmError> {
+        let fee = u128::from(amount)
+            .checked_mul(self.reward_fee_bps.into())
+            .ok_or(AmmError::ConversionFailure)?
+            / u128::from(TOTAL_WEIGHT_BPS);
+        fee.try_into().map_err(|_| AmmError::ConversionFailure)
+    }
+
+    /// Receivers in use
+    pub fn receivers(&self) -> &[FeeReceiver] {
+        &self.receivers[..self.receiver_count as usize]
+    }
+
+    /// Validate that there is at least one receiver and the weights add up to `TOTAL_WEIGHT_BPS`
+    pub fn validate(&self) -> Result<(), AmmError> {
+        let count = self.receiver_count as usize;
+        if count == 0 || count > MAX_FEE_RECEIVERS {
+            return Err(AmmError::InvalidFee);
+        }
+        let total = self.receivers().iter()
+            .try_fold(0u16, |total, receiver| total.checked_add(receiver.weight_bps))
+            .ok_or(AmmError::InvalidFee)?;
+        if total != TOTAL_WEIGHT_BPS {
+            return Err(AmmError::InvalidFee);
+        }
+        if self.accrue_to_vault && self.vault == Pubkey::default() {
+            return Err(AmmError::InvalidConfig);
+        }
+        if self.charges_flat_fee() && (self.flat_fee_lamports == 0 || self.treasury == Pubkey::default()) {
+            return Err(AmmError::InvalidFee);
+        }
+        if self.reward_fee_bps > TOTAL_WEIGHT_BPS {
+            return Err(AmmError::InvalidFee);
+        }
+        Ok(())
+    }
+
+    /// Splits `fee` by weight, the rounding dust goes to the first receiver
+    pub fn split(&self, fee: u64) -> Result<[u64; MAX_FEE_RECEIVERS], AmmError> {
+        let mut shares = [0u64; MAX_FEE_RECEIVERS];
+        let mut distributed = 0u64;
+        for (share, receiver) in shares.iter_mut().zip(self.receivers()) {
+            let amount = u128::from(fee)
+                .checked_mul(receiver.weight_bps.into())
+                .ok_or(AmmError::ConversionFailure)?
+                / u128::from(TOTAL_WEIGHT_BPS);
+            *share = amount.try_into().map_err(|_| AmmError::ConversionFailure)?;
+            distributed = distributed.checked_add(*share).ok_or(AmmError::ConversionFailure)?;
+        }
+        let dust = fee.checked_sub(distributed).ok_or(AmmError::ConversionFailure)?;
+        shares[0] = shares[0].checked_add(dust).ok_or(AmmError::ConversionFailure)?;
+        Ok(shares)
+    }
+}
+
+impl IsInitialized for FeeConfig {
+    fn is_initialized(&self) -> bool {
+        self.is_initialized
+    }
+}
+
+const FEE_RECEIVER_LEN: usize = 34;
+
+impl Sealed for FeeConfig {}
+impl Pack for FeeConfig {
+    const LEN: usize = 111 + FEE_RECEIVER_LEN * MAX_FEE_RECEIVERS;
+    fn pack_into_slice(&self, output: &mut [u8]) {
+        let output = array_mut_ref![output, 0, FeeConfig::LEN];
+        let (
+            is_initialized,
+            bump_seed,
+            mint,
+            receiver_count,
+            receivers,
+            vault,
+            accrue_to_vault,
+            fee_model,
+            flat_fee_lamports,
+            treasury,
+            reward_fee_bps,
+        ) = mut_array_refs![output, 1, 1, 32, 1, FEE_RECEIVER_LEN * MAX_FEE_RECEIVERS, 32, 1, 1, 8, 32, 2];
+        is_initialized[0] = self.is_initialized as u8;
+        bump_seed[0] = self.bump_seed;
+        mint.copy_from_slice(self.mint.as_ref());
+        receiver_count[0] = self.receiver_count;
+        for (dst, receiver) in receivers.chunks_exact_mut(FEE_RECEIVER_LEN).zip(self.receivers.iter()) {
+            let dst = array_mut_ref![dst, 0, FEE_RECEIVER_LEN];
+            let (token_account, weight_bps) = mut_array_refs![dst, 32, 2];
+            token_account.copy_from_slice(receiver.token_account.as_ref());
+            *weight_bps = receiver.weight_bps.to_le_bytes();
+        }
+        vault.copy_from_slice(self.vault.as_ref());
+        accrue_to_vault[0] = self.accrue_to_vault as u8;
+        fee_model[0] = self.fee_model as u8;
+        *flat_fee_lamports = self.flat_fee_lamports.to_le_bytes();
+        treasury.copy_from_slice(self.treasury.as_ref());
+        *reward_fee_bps = self.reward_fee_bps.to_le_bytes();
+    }
+
+    fn unpack_from_slice(input: &[u8]) -> Result<FeeConfig, ProgramError> {
+        let input = array_ref![input, 0, FeeConfig::LEN];
+        #[allow(clippy::ptr_offset_with_cast)]
+        let (
+            is_initialized,
+            bump_seed,
+            mint,
+            receiver_count,
+            receivers_data,
+            vault,
+            accrue_to_vault,
+            fee_model,
+            flat_fee_lamports,
+            treasury,
+            reward_fee_bps,
+        ) = array_refs![input, 1, 1, 32, 1, FEE_RECEIVER_LEN * MAX_FEE_RECEIVERS, 32, 1, 1, 8, 32, 2];
+        if receiver_count[0] as usize > MAX_FEE_RECEIVERS {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        let mut receivers = [FeeReceiver::default(); MAX_FEE_RECEIVERS];
+        for (receiver, src) in receivers.iter_mut().zip(receivers_data.chunks_exact(FEE_RECEIVER_LEN)) {
+            let src = array_ref![src, 0, FEE_RECEIVER_LEN];
+            #[allow(clippy::ptr_offset_with_cast)]
+            let (token_account, weight_bps) = array_refs![src, 32, 2];
+            *receiver = FeeReceiver {
+                token_account: Pubkey::new_from_array(*token_account),
+                weight_bps: u16::from_le_bytes(*weight_bps),
+            };
+        }
+        Ok(Self {
+            is_initialized: unpack_bool(is_initialized)?,
+            bump_seed: bump_seed[0],
+            mint: Pubkey::new_from_array(*mint),
+            receiver_count: receiver_count[0],
+            receivers,
+            vault: Pubkey::new_from_array(*vault),
+            accrue_to_vault: unpack_bool(accrue_to_vault)?,
+            fee_model: FeeModel::from_u8(fee_model[0])?,
+            flat_fee_lamports: u64::from_le_bytes(*flat_fee_lamports),
+            treasury: Pubkey::new_from_array(*treasury),
+            reward_fee_bps: u16::from_le_bytes(*reward_fee_bps),
+        })
+    }
+}
+
+/// Venue a registered pool belongs to
+#[repr(u8)]
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum PoolKind {
+    /// Raydium constant product pool, AMM v4
+    #[default]
+    RaydiumV4 = 0u8,
+    /// Raydium stable pool
+    RaydiumStable = 1u8,
+    /// Raydium concentrated liquidity pool
+    RaydiumClmm = 2u8,
+    /// Serum / OpenBook market, listed under its market account
+    SerumMarket = 3u8,
+}
+impl PoolKind {
+    pub fn from_u8(kind: u8) -> Result<Self, AmmError> {
+        match kind {
+            0u8 => Ok(PoolKind::RaydiumV4),
+            1u8 => Ok(PoolKind::RaydiumStable),
+            2u8 => Ok(PoolKind::RaydiumClmm),
+            3u8 => Ok(PoolKind::SerumMarket),
+            _ => Err(AmmError::InvalidInput),
+        }
+    }
+}
+
+/// Pool reviewed for Inka traffic, stored at the `[POOL_REGISTRY_SEED, amm]` PDA, or
+/// `[POOL_REGISTRY_SEED, market]` for a `PoolKind::SerumMarket`
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct PoolRegistry {
+    /// Initialized state
+    pub is_initialized: bool,
+    /// bump seed of the registry PDA
+    pub bump_seed: u8,
+    /// raydium amm account of the pool, or the market account of a serum market
+    pub amm: Pubkey,
+    /// approved coin mint
+    pub coin_mint: Pubkey,
+    /// approved pc mint
+    pub pc_mint: Pubkey,
+    /// traffic is allowed through the pool
+    pub enabled: bool,
+    /// venue of the pool, instructions only route to the venues they support
+    pub kind: PoolKind,
+}
+
+impl PoolRegistry {
+    /// Derives the registry PDA of `amm`
+    pub fn find_address(program_id: &Pubkey, amm: &Pubkey) -> (Pubkey, u8) {
+        Pubkey::find_program_address(&[POOL_REGISTRY_SEED, amm.as_ref()], program_id)
+    }
+
+    /// Unpacks the registry of `amm`, fails unless the pool is listed and enabled
+    pub fn load(program_id: &Pubkey, registry_account: &AccountInfo, amm: &Pubkey) -> Result<Self, ProgramError> {
+        if registry_account.owner != program_id || registry_account.data_is_empty() {
+            return Err(AmmError::PoolNotListed.into());
+        }
+        let registry = Self::unpack(&registry_account.data.borrow())?;
+        let address = Pubkey::create_program_address(
+            &[POOL_REGISTRY_SEED, amm.as_ref(), &[registry.bump_seed]],
+            program_id,
+        ).map_err(|_| AmmError::PoolNotListed)?;
+        if address != *registry_account.key || registry.amm != *amm {
+            return Err(AmmError::PoolNotListed.into());
+        }
+        if !registry.enabled {
+            return Err(AmmError::PoolDisabled.into());
+        }
+        Ok(registry)
+    }
+
+    /// Fails unless the pool belongs to the `kind` venue
+    pub fn check_kind(&self, kind: PoolKind) -> Result<(), AmmError> {
+        if self.kind != kind {
+            return Err(AmmError::UnsupportedVenue);
+        }
+        Ok(())
+    }
+
+    /// Whether `mint` is one of the approved pool mints
+    pub fn has_mint(&self, mint: &Pubkey) -> bool {
+        *mint == self.coin_mint || *mint == self.pc_mint
+    }
+
+    /// The pool mint on the other side of `mint`, if `mint` is one of the approved pool mints
+    pub fn other_mint(&self, mint: &Pubkey) -> Option<Pubkey> {
+        if *mint == self.coin_mint {
+            Some(self.pc_mint)
+        } else if *mint == self.pc_mint {
+            Some(self.coin_mint)
+        } else {
+            None
+        }
+    }
+}
+
+impl IsInitialized for PoolRegistry {
+    fn is_initialized(&self) -> bool {
+        self.is_initialized
+    }
+}
+
+impl Sealed for PoolRegistry {}
+impl Pack for PoolRegistry {
+    const LEN: usize = 100;
+    fn pack_into_slice(&self, output: &mut [u8]) {
+        let output = array_mut_ref![output, 0, 100];
+        let (is_initialized, bump_seed, amm, coin_mint, pc_mint, enabled, kind) =
+            mut_array_refs![output, 1, 1, 32, 32, 32, 1, 1];
+        is_initialized[0] = self.is_initialized as u8;
+        bump_seed[0] = self.bump_seed;
+        amm.copy_from_slice(self.amm.as_ref());
+        coin_mint.copy_from_slice(self.coin_mint.as_ref());
+        pc_mint.copy_from_slice(self.pc_mint.as_ref());
+        enabled[0] = self.enabled as u8;
+        kind[0] = self.kind as u8;
+    }
+
+    fn unpack_from_slice(input: &[u8]) -> Result<PoolRegistry, ProgramError> {
+        let input = array_ref![input, 0, 100];
+        #[allow(clippy::ptr_offset_with_cast)]
+        let (is_initialized, bump_seed, amm, coin_mint, pc_mint, enabled, kind) =
+            array_refs![input, 1, 1, 32, 32, 32, 1, 1];
+        Ok(Self {
+            is_initialized: unpack_bool(is_initialized)?,
+            bump_seed: bump_seed[0],
+            amm: Pubkey::new_from_array(*amm),
+            coin_mint: Pubkey::new_from_array(*coin_mint),
+            pc_mint: Pubkey::new_from_array(*pc_mint),
+            enabled: unpack_bool(enabled)?,
+            kind: PoolKind::from_u8(kind[0])?,
+        })
+    }
+}
+
+/// Auto-compounding vault staking the lp tokens of one pool in a Raydium farm,
+/// stored at the `[LP_VAULT_SEED, amm]` PDA. The PDA owns the vault token accounts,
+/// the farm stake and is the authority of the share mint.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct LpVault {
+    /// Initialized state
+    pub is_initialized: bool,
+    /// bump seed of the vault PDA
+    pub bump_seed: u8,
+    /// raydium amm account of the pool
+    pub amm: Pubkey,
+    /// raydium farm the lp tokens are staked in
+    pub farm: Pubkey,
+    /// staking info account of the vault in the farm
+    pub farm_user_info: Pubkey,
+    /// mint of the vault shares, PDA of `[SHARE_MINT_SEED, vault]`
+    pub share_mint: Pubkey,
+    /// associated lp token account of the vault
+    pub lp_token: Pubkey,
+    /// associated coin token account of the vault
+    pub coin_token: Pubkey,
+    /// associated pc token account of the vault
+    pub pc_token: Pubkey,
+    /// farm reward mint, the coin or pc mint of the pool. Farms rewarding another mint,
+    /// such as RAY on a pool without RAY, are not supported
+    pub reward_mint: Pubkey,
+    /// reward token account receiving the performance fee
+    pub treasury: Pubkey,
+    /// lp tokens staked in the farm on behalf of the share holders
+    pub staked_lp: u64,
+    /// performance fee on compounded rewards, in basis points
+    pub performance_fee_bps: u16,
+    /// reward token balance left by the last compound, already charged the performance fee
+    pub reward_balance: u64,
+    /// slot of the last compound, deposits must follow a compound in the same slot
+    pub last_compound_slot: u64,
+    /// most the compound swap may get below its quote at the pool reserves, in basis points
+    pub max_slippage_bps: u16,
+}
+
+impl LpVault {
+    /// Derives the vault PDA of `amm`
+    pub fn find_address(program_id: &Pubkey, amm: &Pubkey) -> (Pubkey, u8) {
+        Pubkey::find_program_address(&[LP_VAULT_SEED, amm.as_ref()], program_id)
+    }
+
+    /// Derives the share mint of the vault at `vault`
+    pub fn find_share_mint_address(program_id: &Pubkey, vault: &Pubkey) -> (Pubkey, u8) {
+        Pubkey::find_program_address(&[SHARE_MINT_SEED, vault.as_ref()], program_id)
+    }
+
+    /// Unpacks the vault and checks it lives at its PDA owned by this program
+    pub fn load(program_id: &Pubkey, vault_account: &AccountInfo) -> Result<Self, ProgramError> {
+        if vault_account.owner != program_id {
+            return Err(AmmError::InvalidVault.into());
+        }
+        let vault = Self::unpack(&vault_account.data.borrow())?;
+        let address = Pubkey::create_program_address(
+            &[LP_VAULT_SEED, vault.amm.as_ref(), &[vault.bump_seed]],
+            program_id,
+        ).map_err(|_| AmmError::InvalidVault)?;
+        if address != *vault_account.key {
+            return Err(AmmError::InvalidVault.into());
+        }
+        Ok(vault)
+    }
+
+    /// Seeds signing for the vault PDA
+    pub fn signer_seeds<'a>(&'a self, bump_seed: &'a [u8; 1]) -> [&'a [u8]; 3] {
+        [LP_VAULT_SEED, self.amm.as_ref(), bump_seed]
+    }
+
+    /// Shares minted for `lp_amount` at the current share price, one share per lp for the first deposit
+    pub fn shares_for_lp(&self, lp_amount: u64, share_supply: u64) -> Result<u64, AmmError> {
+        if share_supply == 0 || self.staked_lp == 0 {
+            return Ok(lp_amount);
+        }
+        let shares = u128::from(lp_amount)
+            .checked_mul(share_supply.into())
+            .ok_or(AmmError::ConversionFailure)?
+            / u128::from(self.staked_lp);
+        shares.try_into().map_err(|_| AmmError::ConversionFailure)
+    }
+
+    /// Lp tokens redeemed for `shares` at the current share price
+    pub fn lp_for_shares(&self, shares: u64, share_supply: u64) -> Result<u64, AmmError> {
+        if share_supply == 0 {
+            return Err(AmmError::InvalidInput);
+        }
+        let lp_amount = u128::from(shares)
+            .checked_mul(self.staked_lp.into())
+            .ok_or(AmmError::ConversionFailure)?
+            / u128::from(share_supply);
+        lp_amount.try_into().map_err(|_| AmmError::ConversionFailure)
+    }
+
+    /// Rewards harvested into the reward token account since the last compound, out of its `balance`
+    pub fn harvested_rewards(&self, balance: u64) -> u64 {
+        balance.saturating_sub(self.reward_balance)
+    }
+
+    /// Fails if the pending rewards were not compounded in `slot`, a deposit priced from
+    /// `staked_lp` alone would hand part of them to the new shares
+    pub fn check_compounded(&self, share_supply: u64, slot: u64) -> Result<(), AmmError> {
+        if share_supply > 0 && self.staked_lp > 0 && self.last_compound_slot != slot {
+            return Err(AmmError::VaultNotCompounded);
+        }
+        Ok(())
+    }
+
+    /// Least the compound swap must get out of a `quote` at the pool reserves
+    pub fn minimum_swap_out(&self, quote: u64) -> Result<u64, AmmError> {
+        let bps = TOTAL_WEIGHT_BPS.checked_sub(self.max_slippage_bps).ok_or(AmmError::InvalidInput)?;
+        let minimum = u128::from(quote)
+            .checked_mul(bps.into())
+            .ok_or(AmmError::ConversionFailure)?
+            / u128::from(TOTAL_WEIGHT_BPS);
+        minimum.try_into().map_err(|_| AmmError::ConversionFailure)
+    }
+
+    /// Performance fee on `rewards`
+    pub fn performance_fee(&self, rewards: u64) -> Result<u64, AmmError> {
+        let fee = u128::from(rewards)
+            .checked_mul(self.performance_fee_bps.into())
+            .ok_or(AmmError::ConversionFailure)?
+            / u128::from(TOTAL_WEIGHT_BPS);
+        fee.try_into().map_err(|_| AmmError::ConversionFailure)
+    }
+}
+
+impl IsInitialized for LpVault {
+    fn is_initialized(&self) -> bool {
+        self.is_initialized
+    }
+}
+
+impl Sealed for LpVault {}
+impl Pack for LpVault {
+    const LEN: usize = 318;
+    fn pack_into_slice(&self, output: &mut [u8]) {
+        let output = array_mut_ref![output, 0, 318];
+        let (
+            is_initialized,
+            bump_seed,
+            amm,
+            farm,
+            farm_user_info,
+            share_mint,
+            lp_token,
+            coin_token,
+            pc_token,
+            reward_mint,
+            treasury,
+            staked_lp,
+            performance_fee_bps,
+            reward_balance,
+            last_compound_slot,
+            max_slippage_bps,
+        ) = mut_array_refs![output, 1, 1, 32, 32, 32, 32, 32, 32, 32, 32, 32, 8, 2, 8, 8, 2];
+        is_initialized[0] = self.is_initialized as u8;
+        bump_seed[0] = self.bump_seed;
+        amm.copy_from_slice(self.amm.as_ref());
+        farm.copy_from_slice(self.farm.as_ref());
+        farm_user_info.copy_from_slice(self.farm_user_info.as_ref());
+        share_mint.copy_from_slice(self.share_mint.as_ref());
+        lp_token.copy_from_slice(self.lp_token.as_ref());
+        coin_token.copy_from_slice(self.coin_token.as_ref());
+        pc_token.copy_from_slice(self.pc_token.as_ref());
+        reward_mint.copy_from_slice(self.reward_mint.as_ref());
+        treasury.copy_from_slice(self.treasury.as_ref());
+        *staked_lp = self.staked_lp.to_le_bytes();
+        *performance_fee_bps = self.performance_fee_bps.to_le_bytes();
+        *reward_balance = self.reward_balance.to_le_bytes();
+        *last_compound_slot = self.last_compound_slot.to_le_bytes();
+        *max_slippage_bps = self.max_slippage_bps.to_le_bytes();
+    }
+
+    fn unpack_from_slice(input: &[u8]) -> Result<LpVault, ProgramError> {
+        let input = array_ref![input, 0, 318];
+        #[allow(clippy::ptr_offset_with_cast)]
+        let (
+            is_initialized,
+            bump_seed,
+            amm,
+            farm,
+            farm_user_info,
+            share_mint,
+            lp_token,
+            coin_token,
+            pc_token,
+            reward_mint,
+            treasury,
+            staked_lp,
+            performance_fee_bps,
+            reward_balance,
+            last_compound_slot,
+            max_slippage_bps,
+        ) = array_refs![input, 1, 1, 32, 32, 32, 32, 32, 32, 32, 32, 32, 8, 2, 8, 8, 2];
+        Ok(Self {
+            is_initialized: unpack_bool(is_initialized)?,
+            bump_seed: bump_seed[0],
+            amm: Pubkey::new_from_array(*amm),
+            farm: Pubkey::new_from_array(*farm),
+            farm_user_info: Pubkey::new_from_array(*farm_user_info),
+            share_mint: Pubkey::new_from_array(*share_mint),
+            lp_token: Pubkey::new_from_array(*lp_token),
+            coin_token: Pubkey::new_from_array(*coin_token),
+            pc_token: Pubkey::new_from_array(*pc_token),
+            reward_mint: Pubkey::new_from_array(*reward_mint),
+            treasury: Pubkey::new_from_array(*treasury),
+            staked_lp: u64::from_le_bytes(*staked_lp),
+            performance_fee_bps: u16::from_le_bytes(*performance_fee_bps),
+            reward_balance: u64::from_le_bytes(*reward_balance),
+            last_compound_slot: u64::from_le_bytes(*last_compound_slot),
+            max_slippage_bps: u16::from_le_bytes(*max_slippage_bps),
+        })
+    }
+}
+
+/// Swap of escrowed tokens a keeper executes once the pool pays at least the limit price,
+/// stored at the `[LIMIT_ORDER_SEED, owner, order_id]` PDA which owns both escrows
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct LimitOrder {
+    /// Initialized state
+    pub is_initialized: bool,
+    /// bump seed of the order PDA
+    pub bump_seed: u8,
+    /// user who placed the order and gets the refunds
+    pub owner: Pubkey,
+    /// raydium amm account of the pool
+    pub amm: Pubkey,
+    /// distinguishes the orders of one owner
+    pub order_id: u64,
+    /// escrow token account holding `amount_in`
+    pub input_escrow: Pubkey,
+    /// escrow token account receiving the swap output
+    pub output_escrow: Pubkey,
+    /// user token account paid the output less the commission
+    pub destination: Pubkey,
+    /// escrowed input amount, swapped at once
+    pub amount_in: u64,
+    /// limit price as the minimum output for `amount_in`
+    pub minimum_amount_out: u64,
+    /// last slot the order may execute in
+    pub expiry_slot: u64,
+    /// lamports on top of the rent paid to the executing keeper
+    pub keeper_tip_lamports: u64,
+}
+
+impl LimitOrder {
+    /// Derives the order PDA of `owner`
+    pub fn find_address(program_id: &Pubkey, owner: &Pubkey, order_id: u64) -> (Pubkey, u8) {
+        Pubkey::find_program_address(&[LIMIT_ORDER_SEED, owner.as_ref(), &order_id.to_le_bytes()], program_id)
+    }
+
+    /// Unpacks the order and checks it lives at its PDA owned by this program
+    pub fn load(program_id: &Pubkey, order_account: &AccountInfo) -> Result<Self, ProgramError> {
+        if order_account.owner != program_id {
+            return Err(AmmError::InvalidOrder.into());
+        }
+        let order = Self::unpack(&order_account.data.borrow())?;
+        let address = Pubkey::create_program_address(
+            &[LIMIT_ORDER_SEED, order.owner.as_ref(), &order.order_id.to_le_bytes(), &[order.bump_seed]],
+            program_id,
+        ).map_err(|_| AmmError::InvalidOrder)?;
+        if address != *order_account.key {
+            return Err(AmmError::InvalidOrder.into());
+        }
+        Ok(order)
+    }
+
+    /// Seeds signing for the order PDA, which is the authority of its escrows
+    pub fn signer_seeds<'a>(&'a self, order_id: &'a [u8; 8], bump_seed: &'a [u8; 1]) -> [&'a [u8]; 4] {
+        [LIMIT_ORDER_SEED, self.owner.as_ref(), order_id, bump_seed]
+    }
+}
+
+impl IsInitialized for LimitOrder {
+    fn is_initialized(&self) -> bool {
+        self.is_initialized
+    }
+}
+
+impl Sealed for LimitOrder {}
+impl Pack for LimitOrder {
+    const LEN: usize = 202;
+    fn pack_into_slice(&self, output: &mut [u8]) {
+        let output = array_mut_ref![output, 0, 202];
+        let (
+            is_initialized,
+            bump_seed,
+            owner,
+            amm,
+            order_id,
+            input_escrow,
+            output_escrow,
+            destination,
+            amount_in,
+            minimum_amount_out,
+            expiry_slot,
+            keeper_tip_lamports,
+        ) = mut_array_refs![output, 1, 1, 32, 32, 8, 32, 32, 32, 8, 8, 8, 8];
+        is_initialized[0] = self.is_initialized as u8;
+        bump_seed[0] = self.bump_seed;
+        owner.copy_from_slice(self.owner.as_ref());
+        amm.copy_from_slice(self.amm.as_ref());
+        *order_id = self.order_id.to_le_bytes();
+        input_escrow.copy_from_slice(self.input_escrow.as_ref());
+        output_escrow.copy_from_slice(self.output_escrow.as_ref());
+        destination.copy_from_slice(self.destination.as_ref());
+        *amount_in = self.amount_in.to_le_bytes();
+        *minimum_amount_out = self.minimum_amount_out.to_le_bytes();
+        *expiry_slot = self.expiry_slot.to_le_bytes();
+        *keeper_tip_lamports = self.keeper_tip_lamports.to_le_bytes();
+    }
+
+    fn unpack_from_slice(input: &[u8]) -> Result<LimitOrder, ProgramError> {
+        let input = array_ref![input, 0, 202];
+        #[allow(clippy::ptr_offset_with_cast)]
+        let (
+            is_initialized,
+            bump_seed,
+            owner,
+            amm,
+            order_id,
+            input_escrow,
+            output_escrow,
+            destination,
+            amount_in,
+            minimum_amount_out,
+            expiry_slot,
+            keeper_tip_lamports,
+        ) = array_refs![input, 1, 1, 32, 32, 8, 32, 32, 32, 8, 8, 8, 8];
+        Ok(Self {
+            is_initialized: unpack_bool(is_initialized)?,
+            bump_seed: bump_seed[0],
+            owner: Pubkey::new_from_array(*owner),
+            amm: Pubkey::new_from_array(*amm),
+            order_id: u64::from_le_bytes(*order_id),
+            input_escrow: Pubkey::new_from_array(*input_escrow),
+            output_escrow: Pubkey::new_from_array(*output_escrow),
+            destination: Pubkey::new_from_array(*destination),
+            amount_in: u64::from_le_bytes(*amount_in),
+            minimum_amount_out: u64::from_le_bytes(*minimum_amount_out),
+            expiry_slot: u64::from_le_bytes(*expiry_slot),
+            keeper_tip_lamports: u64::from_le_bytes(*keeper_tip_lamports),
+        })
+    }
+}
+
+/// Recurring swap of an escrowed balance a keeper runs once per interval,
+/// stored at the `[DCA_SEED, owner, schedule_id]` PDA which owns both escrows
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct DcaSchedule {
+    /// Initialized state
+    pub is_initialized: bool,
+    /// bump seed of the schedule PDA
+    pub bump_seed: u8,
+    /// user who created the schedule and gets the refunds
+    pub owner: Pubkey,
+    /// raydium amm account of the pool
+    pub amm: Pubkey,
+    /// distinguishes the schedules of one owner
+    pub schedule_id: u64,
+    /// escrow token account holding the remaining input
+    pub input_escrow: Pubkey,
+    /// escrow token account receiving each swap output
+    pub output_escrow: Pubkey,
+    /// user token account paid each output less the commission
+    pub destination: Pubkey,
+    /// input swapped per execution
+    pub amount_per_period: u64,
+    /// slots between two executions
+    pub interval_slots: u64,
+    /// price bound as the minimum output for `amount_per_period`
+    pub minimum_amount_out: u64,
+    /// first slot the next execution may run in
+    pub next_execution_slot: u64,
+    /// executions run so far
+    pub executions: u64,
+    /// input swapped so far
+    pub total_amount_in: u64,
+    /// output paid to the user so far
+    pub total_amount_out: u64,
+}
+
+impl DcaSchedule {
+    /// Derives the schedule PDA of `owner`
+    pub fn find_address(program_id: &Pubkey, owner: &Pubkey, schedule_id: u64) -> (Pubkey, u8) {
+        Pubkey::find_program_address(&[DCA_SEED, owner.as_ref(), &schedule_id.to_le_bytes()], program_id)
+    }
+
+    /// Unpacks the schedule and checks it lives at its PDA owned by this program
+    pub fn load(program_id: &Pubkey, schedule_account: &AccountInfo) -> Result<Self, ProgramError> {
+        if schedule_account.owner != program_id {
+            return Err(AmmError::InvalidOrder.into());
+        }
+        let schedule = Self::unpack(&schedule_account.data.borrow())?;
+        let address = Pubkey::create_program_address(
+            &[DCA_SEED, schedule.owner.as_ref(), &schedule.schedule_id.to_le_bytes(), &[schedule.bump_seed]],
+            program_id,
+        ).map_err(|_| AmmError::InvalidOrder)?;
+        if address != *schedule_account.key {
+            return Err(AmmError::InvalidOrder.into());
+        }
+        Ok(schedule)
+    }
+
+    /// Seeds signing for the schedule PDA, which is the authority of its escrows
+    pub fn signer_seeds<'a>(&'a self, schedule_id: &'a [u8; 8], bump_seed: &'a [u8; 1]) -> [&'a [u8]; 4] {
+        [DCA_SEED, self.owner.as_ref(), schedule_id, bump_seed]
+    }
+
+    /// Minimum output for a last execution swapping less than `amount_per_period`
+    pub fn minimum_amount_out_for(&self, amount_in: u64) -> Result<u64, AmmError> {
+        if self.amount_per_period == 0 {
+            return Err(AmmError::InvalidInput);
+        }
+        let minimum = u128::from(self.minimum_amount_out)
+            .checked_mul(amount_in.into())
+            .ok_or(AmmError::ConversionFailure)?
+            / u128::from(self.amount_per_period);
+        minimum.try_into().map_err(|_| AmmError::ConversionFailure)
+    }
+}
+
+impl IsInitialized for DcaSchedule {
+    fn is_initialized(&self) -> bool {
+        self.is_initialized
+    }
+}
+
+impl Sealed for DcaSchedule {}
+impl Pack for DcaSchedule {
+    const LEN: usize = 226;
+    fn pack_into_slice(&self, output: &mut [u8]) {
+        let output = array_mut_ref![output, 0, 226];
+        let (
+            is_initialized,
+            bump_seed,
+            owner,
+            amm,
+            schedule_id,
+            input_escrow,
+            output_escrow,
+            destination,
+            amount_per_period,
+            interval_slots,
+            minimum_amount_out,
+            next_execution_slot,
+            executions,
+            total_amount_in,
+            total_amount_out,
+        ) = mut_array_refs![output, 1, 1, 32, 32, 8, 32, 32, 32, 8, 8, 8, 8, 8, 8, 8];
+        is_initialized[0] = self.is_initialized as u8;
+        bump_seed[0] = self.bump_seed;
+        owner.copy_from_slice(self.owner.as_ref());
+        amm.copy_from_slice(self.amm.as_ref());
+        *schedule_id = self.schedule_id.to_le_bytes();
+        input_escrow.copy_from_slice(self.input_escrow.as_ref());
+        output_escrow.copy_from_slice(self.output_escrow.as_ref());
+        destination.copy_from_slice(self.destination.as_ref());
+        *amount_per_period = self.amount_per_period.to_le_bytes();
+        *interval_slots = self.interval_slots.to_le_bytes();
+        *minimum_amount_out = self.minimum_amount_out.to_le_bytes();
+        *next_execution_slot = self.next_execution_slot.to_le_bytes();
+        *executions = self.executions.to_le_bytes();
+        *total_amount_in = self.total_amount_in.to_le_bytes();
+        *total_amount_out = self.total_amount_out.to_le_bytes();
+    }
+
+    fn unpack_from_slice(input: &[u8]) -> Result<DcaSchedule, ProgramError> {
+        let input = array_ref![input, 0, 226];
+        #[allow(clippy::ptr_offset_with_cast)]
+        let (
+            is_initialized,
+            bump_seed,
+            owner,
+            amm,
+            schedule_id,
+            input_escrow,
+            output_escrow,
+            destination,
+            amount_per_period,
+            interval_slots,
+            minimum_amount_out,
+            next_execution_slot,
+            executions,
+            total_amount_in,
+            total_amount_out,
+        ) = array_refs![input, 1, 1, 32, 32, 8, 32, 32, 32, 8, 8, 8, 8, 8, 8, 8];
+        Ok(Self {
+            is_initialized: unpack_bool(is_initialized)?,
+            bump_seed: bump_seed[0],
+            owner: Pubkey::new_from_array(*owner),
+            amm: Pubkey::new_from_array(*amm),
+            schedule_id: u64::from_le_bytes(*schedule_id),
+            input_escrow: Pubkey::new_from_array(*input_escrow),
+            output_escrow: Pubkey::new_from_array(*output_escrow),
+            destination: Pubkey::new_from_array(*destination),
+            amount_per_period: u64::from_le_bytes(*amount_per_period),
+            interval_slots: u64::from_le_bytes(*interval_slots),
+            minimum_amount_out: u64::from_le_bytes(*minimum_amount_out),
+            next_execution_slot: u64::from_le_bytes(*next_execution_slot),
+            executions: u64::from_le_bytes(*executions),
+            total_amount_in: u64::from_le_bytes(*total_amount_in),
+            total_amount_out: u64::from_le_bytes(*total_amount_out),
+        })
+    }
+}
+
+/// Swap of escrowed tokens a keeper executes once the pool spot price crosses the stop or
+/// take-profit price, stored at the `[TRIGGER_ORDER_SEED, owner, order_id]` PDA which owns
+/// both escrows. Prices are the spot value of `amount_in` in the output token
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct TriggerOrder {
+    /// Initialized state
+    pub is_initialized: bool,
+    /// bump seed of the order PDA
+    pub bump_seed: u8,
+    /// user who placed the order and gets the refunds
+    pub owner: Pubkey,
+    /// raydium amm account of the pool
+    pub amm: Pubkey,
+    /// distinguishes the orders of one owner
+    pub order_id: u64,
+    /// escrow token account holding `amount_in`
+    pub input_escrow: Pubkey,
+    /// escrow token account receiving the swap output
+    pub output_escrow: Pubkey,
+    /// user token account paid the output less the commission
+    pub destination: Pubkey,
+    /// escrowed input amount, swapped at once
+    pub amount_in: u64,
+    /// executes once the spot value falls to it, 0 if unset
+    pub stop_amount_out: u64,
+    /// executes once the spot value rises to it, 0 if unset
+    pub take_profit_amount_out: u64,
+    /// largest shortfall of the output against the crossed trigger
+    pub max_slippage_bps: u16,
+    /// last slot the order may execute in
+    pub expiry_slot: u64,
+    /// lamports on top of the rent paid to the executing keeper
+    pub keeper_tip_lamports: u64,
+}
+
+impl TriggerOrder {
+    /// Derives the order PDA of `owner`
+    pub fn find_address(program_id: &Pubkey, owner: &Pubkey, order_id: u64) -> (Pubkey, u8) {
+        Pubkey::find_program_address(&[TRIGGER_ORDER_SEED, owner.as_ref(), &order_id.to_le_bytes()], program_id)
+    }
+
+    /// Unpacks the order and checks it lives at its PDA owned by this program
+    pub fn load(program_id: &Pubkey, order_account: &AccountInfo) -> Result<Self, ProgramError> {
+        if order_account.owner != program_id {
+            return Err(AmmError::InvalidOrder.into());
+        }
+        let order = Self::unpack(&order_account.data.borrow())?;
+        let address = Pubkey::create_program_address(
+            &[TRIGGER_ORDER_SEED, order.owner.as_ref(), &order.order_id.to_le_bytes(), &[order.bump_seed]],
+            program_id,
+        ).map_err(|_| AmmError::InvalidOrder)?;
+        if address != *order_account.key {
+            return Err(AmmError::InvalidOrder.into());
+        }
+        Ok(order)
+    }
+
+    /// Seeds signing for the order PDA, which is the authority of its escrows
+    pub fn signer_seeds<'a>(&'a self, order_id: &'a [u8; 8], bump_seed: &'a [u8; 1]) -> [&'a [u8]; 4] {
+        [TRIGGER_ORDER_SEED, self.owner.as_ref(), order_id, bump_seed]
+    }
+
+    /// Checks a trigger is set, the stop lies below the take-profit and the slippage is a fraction
+    pub fn validate(&self) -> Result<(), AmmError> {
+        if self.stop_amount_out == 0 && self.take_profit_amount_out == 0 {
+            return Err(AmmError::InvalidInput);
+        }
+        if self.stop_amount_out != 0 && self.take_profit_amount_out != 0
+            && self.stop_amount_out >= self.take_profit_amount_out {
+            return Err(AmmError::InvalidInput);
+        }
+        if self.max_slippage_bps > TOTAL_WEIGHT_BPS {
+            return Err(AmmError::InvalidInput);
+        }
+        Ok(())
+    }
+
+    /// Spot value of `amount_in` in the output token at the given reserves
+    pub fn spot_amount_out(&self, reserve_in: u64, reserve_out: u64) -> Result<u64, AmmError> {
+        if reserve_in == 0 {
+            return Err(AmmError::InvalidInput);
+        }
+        let amount_out = u128::from(self.amount_in)
+            .checked_mul(reserve_out.into())
+            .ok_or(AmmError::ConversionFailure)?
+            / u128::from(reserve_in);
+        amount_out.try_into().map_err(|_| AmmError::ConversionFailure)
+    }
+
+    /// Trigger the spot value has crossed, the stop taking precedence
+    pub fn crossed_trigger(&self, spot_amount_out: u64) -> Option<u64> {
+        if self.stop_amount_out != 0 && spot_amount_out <= self.stop_amount_out {
+            Some(self.stop_amount_out)
+        } else if self.take_profit_amount_out != 0 && spot_amount_out >= self.take_profit_amount_out {
+            Some(self.take_profit_amount_out)
+        } else {
+            None
+        }
+    }
+
+    /// Minimum output accepted for the crossed `trigger_amount_out`
+    pub fn minimum_amount_out(&self, trigger_amount_out: u64) -> Result<u64, AmmError> {
+        let minimum = u128::from(trigger_amount_out)
+            .checked_mul((TOTAL_WEIGHT_BPS - self.max_slippage_bps).into())
+            .ok_or(AmmError::ConversionFailure)?
+            / u128::from(TOTAL_WEIGHT_BPS);
+        minimum.try_into().map_err(|_| AmmError::ConversionFailure)
+    }
+}
+
+impl IsInitialized for TriggerOrder {
+    fn is_initialized(&self) -> bool {
+        self.is_initialized
+    }
+}
+
+impl Sealed for TriggerOrder {}
+impl Pack for TriggerOrder {
+    const LEN: usize = 212;
+    fn pack_into_slice(&self, output: &mut [u8]) {
+        let output = array_mut_ref![output, 0, 212];
+        let (
+            is_initialized,
+            bump_seed,
+            owner,
+            amm,
+            order_id,
+            input_escrow,
+            output_escrow,
+            destination,
+            amount_in,
+            stop_amount_out,
+            take_profit_amount_out,
+            max_slippage_bps,
+            expiry_slot,
+            keeper_tip_lamports,
+        ) = mut_array_refs![output, 1, 1, 32, 32, 8, 32, 32, 32, 8, 8, 8, 2, 8, 8];
+        is_initialized[0] = self.is_initialized as u8;
+        bump_seed[0] = self.bump_seed;
+        owner.copy_from_slice(self.owner.as_ref());
+        amm.copy_from_slice(self.amm.as_ref());
+        *order_id = self.order_id.to_le_bytes();
+        input_escrow.copy_from_slice(self.input_escrow.as_ref());
+        output_escrow.copy_from_slice(self.output_escrow.as_ref());
+        destination.copy_from_slice(self.destination.as_ref());
+        *amount_in = self.amount_in.to_le_bytes();
+        *stop_amount_out = self.stop_amount_out.to_le_bytes();
+        *take_profit_amount_out = self.take_profit_amount_out.to_le_bytes();
+        *max_slippage_bps = self.max_slippage_bps.to_le_bytes();
+        *expiry_slot = self.expiry_slot.to_le_bytes();
+        *keeper_tip_lamports = self.keeper_tip_lamports.to_le_bytes();
+    }
+
+    fn unpack_from_slice(input: &[u8]) -> Result<TriggerOrder, ProgramError> {
+        let input = array_ref![input, 0, 212];
+        #[allow(clippy::ptr_offset_with_cast)]
+        let (
+            is_initialized,
+            bump_seed,
+            owner,
+            amm,
+            order_id,
+            input_escrow,
+            output_escrow,
+            destination,
+            amount_in,
+            stop_amount_out,
+            take_profit_amount_out,
+            max_slippage_bps,
+            expiry_slot,
+            keeper_tip_lamports,
+        ) = array_refs![input, 1, 1, 32, 32, 8, 32, 32, 32, 8, 8, 8, 2, 8, 8];
+        Ok(Self {
+            is_initialized: unpack_bool(is_initialized)?,
+            bump_seed: bump_seed[0],
+            owner: Pubkey::new_from_array(*owner),
+            amm: Pubkey::new_from_array(*amm),
+            order_id: u64::from_le_bytes(*order_id),
+            input_escrow: Pubkey::new_from_array(*input_escrow),
+            output_escrow: Pubkey::new_from_array(*output_escrow),
+            destination: Pubkey::new_from_array(*destination),
+            amount_in: u64::from_le_bytes(*amount_in),
+            stop_amount_out: u64::from_le_bytes(*stop_amount_out),
+            take_profit_amount_out: u64::from_le_bytes(*take_profit_amount_out),
+            max_slippage_bps: u16::from_le_bytes(*max_slippage_bps),
+            expiry_slot: u64::from_le_bytes(*expiry_slot),
+            keeper_tip_lamports: u64::from_le_bytes(*keeper_tip_lamports),
+        })
+    }
+}
+
+fn unpack_bool(src: &[u8; 1]) -> Result<bool, ProgramError> {
+    match src[0] {
+        0 => Ok(false),
+        1 => Ok(true),
+        _ => Err(ProgramError::InvalidAccountData),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fee_config(weights: &[u16]) -> FeeConfig {
+        let mut receivers = [FeeReceiver::default(); MAX_FEE_RECEIVERS];
+        for (receiver, weight_bps) in receivers.iter_mut().zip(weights) {
+            *receiver = FeeReceiver { token_account: Pubkey::new_unique(), weight_bps: *weight_bps };
+        }
+        FeeConfig {
+            is_initialized: true,
+            mint: Pubkey::new_unique(),
+            receiver_count: weights.len() as u8,
+            receivers,
+            ..FeeConfig::default()
+        }
+    }
+
+    #[test]
+    fn test_fee_split_by_weight() {
+        let config = fee_config(&[5_000, 3_000, 2_000]);
+        let shares = config.split(1_000).unwrap();
+        assert_eq!(&shares[..3], &[500, 300, 200]);
+        assert!(shares[3..].iter().all(|share| *share == 0));
+    }
+
+    #[test]
+    fn test_fee_split_dust_to_first() {
+        let config = fee_config(&[3_333, 3_333, 3_334]);
+        let shares = config.split(10).unwrap();
+        // 3 + 3 + 3 rounded down, the dust unit goes to the first receiver
+        assert_eq!(&shares[..3], &[4, 3, 3]);
+        assert_eq!(shares.iter().sum::<u64>(), 10);
+        assert_eq!(config.split(0).unwrap(), [0; MAX_FEE_RECEIVERS]);
+    }
+
+    #[test]
+    fn test_fee_config_validate_weights() {
+        assert!(fee_config(&[10_000]).validate().is_ok());
+        assert!(fee_config(&[6_000, 4_000]).validate().is_ok());
+        assert!(fee_config(&[]).validate().is_err());
+        assert!(fee_config(&[6_000, 3_999]).validate().is_err());
+        assert!(fee_config(&[6_000, 4_001]).validate().is_err());
+        assert!(fee_config(&[u16::MAX, 2]).validate().is_err());
+    }
+
+    #[test]
+    fn test_fee_config_pack_round_trip() {
+        let mut config = fee_config(&[7_000, 3_000]);
+        config.bump_seed = 253;
+        config.reward_fee_bps = 150;
+        let mut data = vec![0; FeeConfig::LEN];
+        FeeConfig::pack(config, &mut data).unwrap();
+        assert_eq!(FeeConfig::unpack(&data).unwrap(), config);
+    }
+
+    #[test]
+    fn test_fee_config_vault() {
+        let program_id = Pubkey::new_unique();
+        let mut config = fee_config(&[10_000]);
+        config.accrue_to_vault = true;
+        assert!(matches!(config.validate(), Err(AmmError::InvalidConfig)));
+        config.vault = FeeConfig::find_vault_address(&program_id, &config.mint).0;
+        assert!(config.validate().is_ok());
+        // the vault is owned by the fee config, so it must not collide with it
+        assert_ne!(config.vault, FeeConfig::find_address(&program_id, &config.mint).0);
+    }
+
+    #[test]
+    fn test_fee_config_flat_fee() {
+        let mut config = fee_config(&[10_000]);
+        assert!(config.charges_commission() && !config.charges_flat_fee());
+        config.fee_model = FeeModel::Flat;
+        assert!(!config.charges_commission() && config.charges_flat_fee());
+        config.fee_model = FeeModel::PercentageAndFlat;
+        assert!(config.charges_commission() && config.charges_flat_fee());
+        // a flat fee needs both an amount and a treasury
+        assert!(config.validate().is_err());
+        config.flat_fee_lamports = 5_000;
+        assert!(config.validate().is_err());
+        config.treasury = Pubkey::new_unique();
+        assert!(config.validate().is_ok());
+        assert!(FeeModel::from_u8(3).is_err());
+    }
+
+    #[test]
+    fn test_provider_config_pack_round_trip() {
+        let config = ProviderConfig {
+            is_initialized: true,
+            bump_seed: 254,
+            admin: Pubkey::new_unique(),
+            guardian: Pubkey::new_unique(),
+            withdraw_paused: true,
+            listing_fee_lamports: 1_000_000,
+            listing_treasury: Pubkey::new_unique(),
+            farm_program_id: Pubkey::new_unique(),
+            ..ProviderConfig::default()
+        };
+        let mut data = vec![0u8; ProviderConfig::LEN];
+        ProviderConfig::pack(config, &mut data).unwrap();
+        assert_eq!(ProviderConfig::unpack(&data).unwrap(), config);
+    }
+
+    #[test]
+    fn test_check_not_paused() {
+        let mut config = ProviderConfig { is_initialized: true, ..ProviderConfig::default() };
+        let operations = [
+            Operation::Swap,
+            Operation::Deposit,
+            Operation::Withdraw,
+            Operation::SweepFees,
+            Operation::Compound,
+        ];
+        assert!(operations.iter().all(|operation| config.check_not_paused(*operation).is_ok()));
+
+        config.deposit_paused = true;
+        assert!(matches!(config.check_not_paused(Operation::Deposit), Err(AmmError::Paused)));
+        assert!(config.check_not_paused(Operation::Swap).is_ok());
+        assert!(config.check_not_paused(Operation::Withdraw).is_ok());
+        assert!(config.check_not_paused(Operation::Compound).is_ok());
+
+        config.deposit_paused = false;
+        config.paused = true;
+        assert!(operations.iter().all(|operation| config.check_not_paused(*operation).is_err()));
+    }
+
+    #[test]
+    fn test_pool_registry_load() {
+        let program_id = Pubkey::new_unique();
+        let amm = Pubkey::new_unique();
+        let (address, bump_seed) = PoolRegistry::find_address(&program_id, &amm);
+        let mut registry = PoolRegistry {
+            is_initialized: true,
+            bump_seed,
+            amm,
+            coin_mint: Pubkey::new_unique(),
+            pc_mint: Pubkey::new_unique(),
+            enabled: true,
+            kind: PoolKind::RaydiumStable,
+        };
+        let mut lamports = 0u64;
+        let mut data = vec![0u8; PoolRegistry::LEN];
+        registry.pack_into_slice(&mut data);
+        let account = AccountInfo::new(&address, false, false, &mut lamports, &mut data, &program_id, false, 0);
+        assert_eq!(PoolRegistry::load(&program_id, &account, &amm).unwrap(), registry);
+        assert_eq!(
+            PoolRegistry::load(&program_id, &account, &Pubkey::new_unique()).err(),
+            Some(AmmError::PoolNotListed.into()),
+        );
+        assert_eq!(
+            PoolRegistry::load(&Pubkey::new_unique(), &account, &amm).err(),
+            Some(AmmError::PoolNotListed.into()),
+        );
+        registry.enabled = false;
+        registry.pack_into_slice(&mut account.data.borrow_mut());
+        assert_eq!(
+            PoolRegistry::load(&program_id, &account, &amm).err(),
+            Some(AmmError::PoolDisabled.into()),
+        );
+
+        // an unlisted pool has no account at its registry address yet
+        let mut lamports = 0u64;
+        let mut data = Vec::new();
+        let system_program = Pubkey::default();
+        let account = AccountInfo::new(&address, false, false, &mut lamports, &mut data, &system_program, false, 0);
+        assert_eq!(
+            PoolRegistry::load(&program_id, &account, &amm).err(),
+            Some(AmmError::PoolNotListed.into()),
+        );
+    }
+
+    #[test]
+    fn test_pool_registry_mints() {
+        let registry = PoolRegistry {
+            coin_mint: Pubkey::new_unique(),
+            pc_mint: Pubkey::new_unique(),
+            ..PoolRegistry::default()
+        };
+        assert!(registry.has_mint(&registry.coin_mint) && registry.has_mint(&registry.pc_mint));
+        assert_eq!(registry.other_mint(&registry.coin_mint), Some(registry.pc_mint));
+        assert_eq!(registry.other_mint(&registry.pc_mint), Some(registry.coin_mint));
+        assert_eq!(registry.other_mint(&Pubkey::new_unique()), None);
+    }
+
+    #[test]
+    fn test_lp_vault_share_price() {
+        let mut vault = LpVault { performance_fee_bps: 1_000, ..LpVault::default() };
+        // one share per lp for the first deposit
+        assert_eq!(vault.shares_for_lp(1_000, 0).unwrap(), 1_000);
+        vault.staked_lp = 1_000;
+        assert_eq!(vault.shares_for_lp(500, 1_000).unwrap(), 500);
+        // compounding raises the lp behind each share
+        vault.staked_lp = 1_500;
+        assert_eq!(vault.shares_for_lp(300, 1_000).unwrap(), 200);
+        assert_eq!(vault.lp_for_shares(200, 1_000).unwrap(), 300);
+        assert_eq!(vault.lp_for_shares(1_000, 1_000).unwrap(), 1_500);
+        // rounding favours the vault both ways
+        assert_eq!(vault.shares_for_lp(2, 1_000).unwrap(), 1);
+        assert_eq!(vault.lp_for_shares(1, 1_000).unwrap(), 1);
+        assert!(vault.lp_for_shares(1, 0).is_err());
+    }
+
+    #[test]
+    fn test_lp_vault_performance_fee() {
+        let mut vault = LpVault { performance_fee_bps: 1_000, reward_balance: 40, ..LpVault::default() };
+        assert_eq!(vault.performance_fee(1_000).unwrap(), 100);
+        assert_eq!(vault.performance_fee(9).unwrap(), 0);
+        // only what was harvested on top of the charged leftovers pays the fee
+        assert_eq!(vault.harvested_rewards(1_040), 1_000);
+        assert_eq!(vault.harvested_rewards(30), 0);
+        vault.performance_fee_bps = 0;
+        assert_eq!(vault.performance_fee(1_000).unwrap(), 0);
+    }
+
+    #[test]
+    fn test_lp_vault_minimum_swap_out() {
+        let mut vault = LpVault { max_slippage_bps: 100, ..LpVault::default() };
+        assert_eq!(vault.minimum_swap_out(10_000).unwrap(), 9_900);
+        assert_eq!(vault.minimum_swap_out(99).unwrap(), 98);
+        vault.max_slippage_bps = 0;
+        assert_eq!(vault.minimum_swap_out(10_000).unwrap(), 10_000);
+        vault.max_slippage_bps = TOTAL_WEIGHT_BPS + 1;
+        assert!(vault.minimum_swap_out(10_000).is_err());
+    }
+
+    #[test]
+    fn test_lp_vault_check_compounded() {
+        let mut vault = LpVault { last_compound_slot: 10, ..LpVault::default() };
+        // nothing staked, no pending rewards to share
+        assert!(vault.check_compounded(0, 11).is_ok());
+        vault.staked_lp = 1_000;
+        assert!(vault.check_compounded(1_000, 10).is_ok());
+        assert!(matches!(vault.check_compounded(1_000, 11), Err(AmmError::VaultNotCompounded)));
+    }
+
+    #[test]
+    fn test_lp_vault_pack_round_trip() {
+        let vault = LpVault {
+            is_initialized: true,
+            bump_seed: 254,
+            amm: Pubkey::new_unique(),
+            reward_mint: Pubkey::new_unique(),
+            treasury: Pubkey::new_unique(),
+            staked_lp: 1_500,
+            performance_fee_bps: 1_000,
+            reward_balance: 40,
+            last_compound_slot: 10,
+            max_slippage_bps: 100,
+            ..LpVault::default()
+        };
+        let mut data = vec![0u8; LpVault::LEN];
+        vault.pack_into_slice(&mut data);
+        assert_eq!(LpVault::unpack_from_slice(&data).unwrap(), vault);
+    }
+
+    #[test]
+    fn test_limit_order_load() {
+        let program_id = Pubkey::new_unique();
+        let owner = Pubkey::new_unique();
+        let (address, bump_seed) = LimitOrder::find_address(&program_id, &owner, 7);
+        let order = LimitOrder {
+            is_initialized: true,
+            bump_seed,
+            owner,
+            order_id: 7,
+            amount_in: 1_000,
+            minimum_amount_out: 950,
+            expiry_slot: 100,
+            ..LimitOrder::default()
+        };
+        let mut lamports = 0u64;
+        let mut data = vec![0u8; LimitOrder::LEN];
+        order.pack_into_slice(&mut data);
+        let account = AccountInfo::new(&address, false, true, &mut lamports, &mut data, &program_id, false, 0);
+        assert_eq!(LimitOrder::load(&program_id, &account).unwrap(), order);
+
+        // another order id of the same owner lives elsewhere
+        let (other_address, _) = LimitOrder::find_address(&program_id, &owner, 8);
+        let mut lamports = 0u64;
+        let mut data = vec![0u8; LimitOrder::LEN];
+        order.pack_into_slice(&mut data);
+        let account = AccountInfo::new(&other_address, false, true, &mut lamports, &mut data, &program_id, false, 0);
+        assert_eq!(LimitOrder::load(&program_id, &account).err(), Some(AmmError::InvalidOrder.into()));
+    }
+
+    #[test]
+    fn test_dca_minimum_amount_out_for() {
+        let schedule = DcaSchedule { amount_per_period: 1_000, minimum_amount_out: 2_000, ..DcaSchedule::default() };
+        assert_eq!(schedule.minimum_amount_out_for(1_000).unwrap(), 2_000);
+        // a short last period is held to the same price
+        assert_eq!(schedule.minimum_amount_out_for(250).unwrap(), 500);
+        assert_eq!(schedule.minimum_amount_out_for(1).unwrap(), 2);
+        assert_eq!(schedule.minimum_amount_out_for(0).unwrap(), 0);
+        let schedule = DcaSchedule { amount_per_period: 3, minimum_amount_out: 10, ..DcaSchedule::default() };
+        assert_eq!(schedule.minimum_amount_out_for(1).unwrap(), 3);
+        assert!(DcaSchedule::default().minimum_amount_out_for(1).is_err());
+    }
+
+    #[test]
+    fn test_trigger_order_checks() {
+        let mut order = TriggerOrder {
+            amount_in: 1_000,
+            stop_amount_out: 1_800,
+            take_profit_amount_out: 2_500,
+            max_slippage_bps: 100,
+            ..TriggerOrder::default()
+        };
+        assert!(order.validate().is_ok());
+        assert_eq!(order.spot_amount_out(1_000_000, 2_000_000).unwrap(), 2_000);
+        assert!(order.spot_amount_out(0, 2_000_000).is_err());
+
+        assert_eq!(order.crossed_trigger(2_000), None);
+        assert_eq!(order.crossed_trigger(1_800), Some(1_800));
+        assert_eq!(order.crossed_trigger(2_600), Some(2_500));
+        // 1% slippage below the crossed trigger
+        assert_eq!(order.minimum_amount_out(1_800).unwrap(), 1_782);
+        assert_eq!(order.minimum_amount_out(2_500).unwrap(), 2_475);
+
+        order.take_profit_amount_out = 0;
+        assert_eq!(order.crossed_trigger(10_000), None);
+        order.stop_amount_out = 0;
+        assert!(order.validate().is_err());
+        order.stop_amount_out = 2_500;
+        order.take_profit_amount_out = 1_800;
+        assert!(order.validate().is_err());
+        order.take_profit_amount_out = 0;
+        order.max_slippage_bps = TOTAL_WEIGHT_BPS + 1;
+        assert!(order.validate().is_err());
+    }
+
+    #[test]
+    fn test_pool_registry_kind() {
+        let registry = PoolRegistry { kind: PoolKind::RaydiumClmm, ..PoolRegistry::default() };
+        assert!(registry.check_kind(PoolKind::RaydiumClmm).is_ok());
+        assert!(matches!(registry.check_kind(PoolKind::RaydiumV4), Err(AmmError::UnsupportedVenue)));
+        assert_eq!(PoolKind::from_u8(PoolKind::RaydiumStable as u8).unwrap(), PoolKind::RaydiumStable);
+        assert!(PoolKind::from_u8(4).is_err());
+
+        let mut data = vec![0u8; PoolRegistry::LEN];
+        registry.pack_into_slice(&mut data);
+        data[PoolRegistry::LEN - 1] = 4;
+        assert!(PoolRegistry::unpack_from_slice(&data).is_err());
+    }
+
+    #[test]
+    fn test_pool_registry_serum_market() {
+        // a listed market cannot stand in for an amm, nor an amm for a market
+        let market = PoolRegistry { kind: PoolKind::SerumMarket, ..PoolRegistry::default() };
+        assert!(market.check_kind(PoolKind::SerumMarket).is_ok());
+        assert!(market.check_kind(PoolKind::RaydiumV4).is_err());
+        let amm = PoolRegistry::default();
+        assert!(matches!(amm.check_kind(PoolKind::SerumMarket), Err(AmmError::UnsupportedVenue)));
+
+        let mut data = vec![0u8; PoolRegistry::LEN];
+        market.pack_into_slice(&mut data);
+        assert_eq!(PoolRegistry::unpack_from_slice(&data).unwrap().kind, PoolKind::SerumMarket);
+    }
+}